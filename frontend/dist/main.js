@@ -4,6 +4,212 @@ const llmModelSelectEl = document.querySelector("#llm-model-select");
 const executeTaskButtonEl = document.querySelector("#execute-task-button");
 const taskStatusEl = document.querySelector("#task-status");
 const taskResultEl = document.querySelector("#task-result");
+const taskStreamEl = document.querySelector("#task-stream");
+const providerSelectEl = document.querySelector("#provider-select");
+const apiKeyInputEl = document.querySelector("#api-key-input");
+const saveApiKeyButtonEl = document.querySelector("#save-api-key-button");
+const providerStatusEl = document.querySelector("#provider-status");
+const testApiKeyButtonEl = document.querySelector("#test-api-key-button");
+const deleteApiKeyButtonEl = document.querySelector("#delete-api-key-button");
+const credentialPassphraseInputEl = document.querySelector("#credential-passphrase-input");
+const credentialPassphraseConfirmInputEl = document.querySelector("#credential-passphrase-confirm-input");
+const unlockCredentialsButtonEl = document.querySelector("#unlock-credentials-button");
+const credentialStoreStatusEl = document.querySelector("#credential-store-status");
+const cancelTaskButtonEl = document.querySelector("#cancel-task-button");
+const mcpDefinitionsStatusEl = document.querySelector("#mcp-definitions-status");
+
+// Task submitted last, which the cancel button targets
+let currentTaskId = null;
+// Whether the credential store file exists; creating it requires typing the passphrase twice
+let credentialStoreExists = true;
+
+// Streamed LLM tokens, published by the backend as they are generated
+if (taskStreamEl && window.__TAURI__) {
+    const { listen } = window.__TAURI__.event;
+    listen("task-data-fragment", (event) => {
+        taskStreamEl.textContent += event.payload.data;
+    });
+}
+
+// MCP definitions reloaded after an edit on disk; files that do not parse keep their last good version
+if (mcpDefinitionsStatusEl && window.__TAURI__) {
+    const { listen } = window.__TAURI__.event;
+    listen("mcp-definitions-reloaded", (event) => {
+        const { added, changed, removed, errors } = event.payload;
+        const lines = [`Rechargées à ${new Date().toLocaleTimeString()} : ${added.length} ajoutée(s), ${changed.length} modifiée(s), ${removed.length} supprimée(s)`];
+        for (const error of errors) {
+            lines.push(`Erreur dans ${error.path} : ${error.message}`);
+        }
+        mcpDefinitionsStatusEl.textContent = lines.join("\n");
+    });
+}
+
+// Model list from the provider catalog; the options in index.html stay as a fallback when it is unavailable
+async function loadLlmModels() {
+    if (!llmModelSelectEl || !window.__TAURI__) {
+        return;
+    }
+    const { invoke } = window.__TAURI__.core;
+    try {
+        const models = await invoke("list_llm_models", { provider: "OpenRouter" });
+        if (models.length === 0) {
+            return;
+        }
+        const previousValue = llmModelSelectEl.value;
+        llmModelSelectEl.replaceChildren(
+            ...models
+                .slice()
+                .sort((a, b) => a.id.localeCompare(b.id))
+                .map((model) => {
+                    const option = document.createElement("option");
+                    option.value = model.id;
+                    option.textContent = model.name ? `${model.name} (${model.id})` : model.id;
+                    return option;
+                })
+        );
+        if (models.some((model) => model.id === previousValue)) {
+            llmModelSelectEl.value = previousValue;
+        }
+    } catch (error) {
+        console.error("Error loading LLM models:", error);
+    }
+}
+
+loadLlmModels();
+
+// Shows whether the selected provider has an API key
+async function refreshProviderStatus() {
+    if (!providerStatusEl || !window.__TAURI__) {
+        return;
+    }
+    const { invoke } = window.__TAURI__.core;
+    try {
+        const statuses = await invoke("list_llm_providers");
+        const status = statuses.find((provider) => provider.name === providerSelectEl.value);
+        providerStatusEl.textContent = status && status.configured ? "Configuré" : "Non configuré";
+    } catch (error) {
+        console.error("Error loading provider statuses:", error);
+    }
+}
+
+refreshProviderStatus();
+
+if (providerSelectEl) {
+    providerSelectEl.addEventListener("change", refreshProviderStatus);
+}
+
+// API keys are saved in an encrypted store, unlocked with its passphrase (created on first unlock, once confirmed)
+async function refreshCredentialStoreStatus() {
+    if (!credentialStoreStatusEl || !window.__TAURI__) {
+        return;
+    }
+    const { invoke } = window.__TAURI__.core;
+    try {
+        const status = await invoke("credential_store_status");
+        credentialStoreExists = status.exists;
+        credentialPassphraseConfirmInputEl.hidden = status.exists || status.unlocked;
+        unlockCredentialsButtonEl.textContent = status.exists ? "Déverrouiller" : "Créer le coffre";
+        if (status.unlocked) {
+            credentialStoreStatusEl.textContent = "Déverrouillé";
+        } else {
+            credentialStoreStatusEl.textContent = status.exists ? "Verrouillé" : "Non créé : choisissez une phrase secrète";
+        }
+    } catch (error) {
+        console.error("Error loading credential store status:", error);
+    }
+}
+
+refreshCredentialStoreStatus();
+
+if (unlockCredentialsButtonEl) {
+    unlockCredentialsButtonEl.addEventListener("click", async () => {
+        const { invoke } = window.__TAURI__.core;
+
+        const passphrase = credentialPassphraseInputEl.value;
+        if (!passphrase) {
+            alert("Veuillez saisir la phrase secrète du coffre.");
+            return;
+        }
+
+        // A new store is protected by this passphrase, so a typo would lock the keys away
+        const create = !credentialStoreExists;
+        if (create && credentialPassphraseConfirmInputEl.value !== passphrase) {
+            alert("Les deux phrases secrètes ne correspondent pas.");
+            return;
+        }
+
+        unlockCredentialsButtonEl.disabled = true;
+        try {
+            await invoke("unlock_credential_store", { passphrase: passphrase, create: create });
+            credentialPassphraseInputEl.value = "";
+            credentialPassphraseConfirmInputEl.value = "";
+            await refreshCredentialStoreStatus();
+            await refreshProviderStatus();
+            loadLlmModels();
+        } catch (error) {
+            console.error("Error unlocking credential store:", error);
+            credentialStoreStatusEl.textContent = `Erreur : ${error}`;
+        } finally {
+            unlockCredentialsButtonEl.disabled = false;
+        }
+    });
+}
+
+if (testApiKeyButtonEl) {
+    testApiKeyButtonEl.addEventListener("click", async () => {
+        const { invoke } = window.__TAURI__.core;
+        providerStatusEl.textContent = "Vérification...";
+        try {
+            await invoke("test_provider_api_key", { provider: providerSelectEl.value });
+            providerStatusEl.textContent = "Clé valide";
+        } catch (error) {
+            console.error("Error testing API key:", error);
+            providerStatusEl.textContent = `Erreur : ${error}`;
+        }
+    });
+}
+
+if (deleteApiKeyButtonEl) {
+    deleteApiKeyButtonEl.addEventListener("click", async () => {
+        const { invoke } = window.__TAURI__.core;
+        if (!confirm(`Supprimer la clé API de ${providerSelectEl.value} ?`)) {
+            return;
+        }
+        try {
+            await invoke("delete_provider_api_key", { provider: providerSelectEl.value });
+            await refreshProviderStatus();
+        } catch (error) {
+            console.error("Error deleting API key:", error);
+            providerStatusEl.textContent = `Erreur : ${error}`;
+        }
+    });
+}
+
+if (saveApiKeyButtonEl) {
+    saveApiKeyButtonEl.addEventListener("click", async () => {
+        const { invoke } = window.__TAURI__.core;
+
+        const apiKey = apiKeyInputEl.value.trim();
+        if (!apiKey) {
+            alert("Veuillez saisir une clé API.");
+            return;
+        }
+
+        providerStatusEl.textContent = "Vérification...";
+        saveApiKeyButtonEl.disabled = true;
+        try {
+            await invoke("set_provider_api_key", { provider: providerSelectEl.value, apiKey: apiKey });
+            apiKeyInputEl.value = "";
+            await refreshProviderStatus();
+            loadLlmModels();
+        } catch (error) {
+            console.error("Error setting API key:", error);
+            providerStatusEl.textContent = `Erreur : ${error}`;
+        } finally {
+            saveApiKeyButtonEl.disabled = false;
+        }
+    });
+}
 
 if (executeTaskButtonEl) {
     executeTaskButtonEl.addEventListener("click", async () => {
@@ -20,6 +226,9 @@ if (executeTaskButtonEl) {
 
         taskStatusEl.textContent = "En cours...";
         taskResultEl.textContent = "Exécution de la tâche...";
+        if (taskStreamEl) {
+            taskStreamEl.textContent = "";
+        }
         executeTaskButtonEl.disabled = true;
 
         try {
@@ -31,6 +240,11 @@ if (executeTaskButtonEl) {
             console.log("Task execution result:", result);
             taskStatusEl.textContent = "Terminée";
             taskResultEl.textContent = result;
+            const taskIdMatch = /Task ID: (\S+)/.exec(result);
+            currentTaskId = taskIdMatch ? taskIdMatch[1] : null;
+            if (cancelTaskButtonEl) {
+                cancelTaskButtonEl.disabled = currentTaskId === null;
+            }
         } catch (error) {
             console.error("Error executing task:", error);
             taskStatusEl.textContent = "Échec";
@@ -39,4 +253,25 @@ if (executeTaskButtonEl) {
             executeTaskButtonEl.disabled = false;
         }
     });
+}
+
+if (cancelTaskButtonEl) {
+    cancelTaskButtonEl.addEventListener("click", async () => {
+        const { invoke } = window.__TAURI__.core;
+        if (!currentTaskId) {
+            return;
+        }
+
+        cancelTaskButtonEl.disabled = true;
+        try {
+            // The in-flight LLM call is aborted and the task fails as cancelled
+            await invoke("cancel_task", { taskId: currentTaskId });
+            taskStatusEl.textContent = "Annulée";
+            currentTaskId = null;
+        } catch (error) {
+            console.error("Error cancelling task:", error);
+            taskStatusEl.textContent = `Échec de l'annulation : ${error}`;
+            cancelTaskButtonEl.disabled = false;
+        }
+    });
 }
\ No newline at end of file
@@ -35,6 +35,13 @@ impl AgentManager {
         let agent_id = agent_config.id.clone();
         println!("Spawning agent with ID: {}", agent_id);
 
+        // Reject unknown models now rather than when the agent's first LLM call fails
+        if let (Some(provider_name), Some(model)) = (&agent_config.llm_provider_name, &agent_config.llm_model) {
+            if !model.is_empty() {
+                self.mcp_manager.external_api_client().validate_model(provider_name, model).await?;
+            }
+        }
+
         // Create a new receiver for this agent from the communication bus
         let bus_receiver = self.communication_bus.subscribe();
         // Get the sender for BusRequests from the communication bus for agents to use
@@ -1,11 +1,13 @@
 use std::sync::Arc;
 use tokio::sync::{mpsc};
 
-use crate::common_types::{AgentConfig};
+use crate::common_types::{AgentConfig, MCPCallFailed, MCPContextOverrides, MCPOutput, Message, MessageContent, TaskNode, generate_id};
 use crate::communication_bus::BusRequest;
 use crate::common_types::agent_defs::{AgentStatus, AgentCapabilities}; // Import AgentStatus and AgentCapabilities
 use crate::mcp_manager::MCPManager;
 
+const FRAGMENT_CHANNEL_CAPACITY: usize = 64;
+
 pub struct BaseAgentComponents {
     pub id: String,
     pub name: String,
@@ -66,4 +68,160 @@ impl BaseAgentComponents {
     pub fn get_config(&self) -> AgentConfig {
         self.config.clone()
     }
-}
\ No newline at end of file
+
+    /// Context overrides carrying this agent's configured provider, model and sampling parameters,
+    /// whether it may let the model call tools, and the task the call is made for, so cancelling
+    /// that task aborts the call.
+    pub fn llm_context_overrides(&self, task_id: &str) -> MCPContextOverrides {
+        MCPContextOverrides {
+            llm_provider: self.config.llm_provider_name.clone(),
+            llm_model: self.config.llm_model.clone(),
+            llm_parameters: self.config.llm_parameters.clone(),
+            additional_context: None,
+            routing_policy: None,
+            bypass_cache: false,
+            prior_messages: Vec::new(),
+            tools_enabled: self.capabilities.can_use_tool,
+            task_id: Some(task_id.to_string()),
+        }
+    }
+
+    /// `llm_context_overrides` for an MCP call made to process `task`. A retried task skips the
+    /// response cache: the cached completion is the one that made the previous attempt fail.
+    pub fn task_context_overrides(&self, task: &TaskNode) -> MCPContextOverrides {
+        MCPContextOverrides {
+            bypass_cache: task.retry_count > 0,
+            ..self.llm_context_overrides(&task.id.to_string())
+        }
+    }
+
+    /// Reports the LLM usage of an MCP call made for `task_id` so the orchestrator can account for it,
+    /// then turns an output whose LLM call failed into an error carrying the provider's message.
+    pub async fn settle_mcp_result(&self, task_id: &str, mcp_result: anyhow::Result<MCPOutput>) -> anyhow::Result<MCPOutput> {
+        settle_mcp_result(&self.bus_sender, &self.id, task_id, mcp_result).await
+    }
+
+    /// Spawns a task publishing streamed LLM chunks for `task_id` as `DataFragment` messages on the
+    /// bus's fragment channel.
+    /// Returns the sender to hand to `MCPManager::invoke_mcp_streaming`.
+    pub fn spawn_fragment_forwarder(&self, task_id: String) -> mpsc::Sender<String> {
+        let (chunk_sender, mut chunk_receiver) = mpsc::channel::<String>(FRAGMENT_CHANNEL_CAPACITY);
+        let bus_sender = self.bus_sender.clone();
+        let agent_id = self.id.clone();
+
+        tokio::spawn(async move {
+            while let Some(data) = chunk_receiver.recv().await {
+                let message = Message {
+                    id: generate_id(),
+                    sender_id: agent_id.clone(),
+                    receiver_id: None,
+                    content: MessageContent::DataFragment { task_id: Some(task_id.clone()), data },
+                };
+                if let Err(e) = bus_sender.send(BusRequest::DataFragment { message }).await {
+                    eprintln!("Agent {} failed to forward data fragment for task {}: {}", agent_id, task_id, e);
+                    break;
+                }
+            }
+        });
+
+        chunk_sender
+    }
+}
+
+/// `BaseAgentComponents::settle_mcp_result` for agents working from a spawned task.
+pub async fn settle_mcp_result(bus_sender: &mpsc::Sender<BusRequest>, agent_id: &str, task_id: &str, mcp_result: anyhow::Result<MCPOutput>) -> anyhow::Result<MCPOutput> {
+    let mcp_output = mcp_result?;
+    send_usage_report(bus_sender, agent_id, task_id, &mcp_output).await;
+    Ok(mcp_output.into_result()?)
+}
+
+/// Whether a task failure should not be retried: an MCP call whose provider rejected the key,
+/// does not know the model, filtered the content... would fail the same way on the next attempt.
+pub fn is_fatal_failure(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<MCPCallFailed>().is_some_and(|failed| failed.status.is_fatal())
+}
+
+/// Publishes a `UsageReport` for an MCP call; does nothing if the call carried no usage metrics.
+/// For agents reporting from a spawned task without access to their components.
+pub async fn send_usage_report(bus_sender: &mpsc::Sender<BusRequest>, agent_id: &str, task_id: &str, mcp_output: &MCPOutput) {
+    let Some(usage) = mcp_output.usage_metrics.clone() else {
+        return;
+    };
+    let message = Message {
+        id: generate_id(),
+        sender_id: agent_id.to_string(),
+        receiver_id: None,
+        content: MessageContent::UsageReport { task_id: task_id.to_string(), agent_id: agent_id.to_string(), usage },
+    };
+    if let Err(e) = bus_sender.send(BusRequest::GeneralMessage { message }).await {
+        eprintln!("Agent {} failed to send usage report for task {}: {}", agent_id, task_id, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common_types::{MCPDefinition, MCPInput};
+    use crate::communication_bus::CommunicationBus;
+    use crate::external_api_client::providers::scripted_provider::ScriptedProvider;
+    use serde_json::json;
+    use std::time::Duration;
+
+    fn components(bus: &CommunicationBus, mcp_manager: Arc<MCPManager>) -> BaseAgentComponents {
+        let capabilities = AgentCapabilities {
+            can_research: false,
+            can_write: true,
+            can_plan: false,
+            can_code: false,
+            can_design: false,
+            can_test: false,
+            can_debug: false,
+            can_architect: false,
+            can_manage_sprint: false,
+            can_use_tool: false,
+        };
+        BaseAgentComponents::new(
+            "writer-1".to_string(),
+            "Writer-writer-1".to_string(),
+            AgentStatus::Idle,
+            capabilities,
+            mcp_manager,
+            AgentConfig::default(),
+            bus.get_bus_request_sender(),
+        )
+    }
+
+    fn draft_definition() -> MCPDefinition {
+        serde_json::from_value(json!({
+            "id": "draft_v1",
+            "description": "Drafts a text",
+            "template": "Write about {{topic}}",
+            "logic_module_path": null,
+            "default_llm_provider": "Scripted",
+            "default_llm_model": "scripted-model",
+            "default_llm_parameters": null
+        })).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_streamed_chunks_reach_fragment_subscribers() {
+        let bus = CommunicationBus::new();
+        bus.start_request_forwarding();
+        let mut fragments = bus.subscribe_fragments();
+        let mut bus_receiver = bus.subscribe();
+        let scripted = ScriptedProvider::from_json_str(r#"{ "responses": [ { "response": "Bees pollinate flowers." } ] }"#).unwrap();
+        let mcp_manager = Arc::new(MCPManager::for_test([draft_definition()], vec![Box::new(scripted)]));
+        let components = components(&bus, Arc::clone(&mcp_manager));
+
+        let chunk_sender = components.spawn_fragment_forwarder("task-1".to_string());
+        let input = MCPInput { mcp_id: "draft_v1".to_string(), data: json!({ "topic": "bees" }), context_overrides: Some(components.llm_context_overrides("task-1")) };
+        let output = mcp_manager.invoke_mcp_streaming(input, chunk_sender).await.unwrap();
+        assert_eq!(output.llm_response_details.unwrap().content, "Bees pollinate flowers.");
+
+        let fragment = tokio::time::timeout(Duration::from_secs(1), fragments.recv()).await.unwrap().unwrap();
+        assert_eq!(fragment.sender_id, "writer-1");
+        assert_eq!(fragment.content, MessageContent::DataFragment { task_id: Some("task-1".to_string()), data: "Bees pollinate flowers.".to_string() });
+        // The agents' bus does not carry the tokens
+        assert!(bus_receiver.try_recv().is_err());
+    }
+}
@@ -1,5 +1,5 @@
 use crate::agents::base_agent::Agent;
-use crate::agents::base_agent_components::BaseAgentComponents; // Import directly
+use crate::agents::base_agent_components::{BaseAgentComponents, is_fatal_failure}; // Import directly
 use crate::common_types::agent_defs::{AgentCapabilities, AgentStatus, AgentRole}; // Import directly
 use crate::common_types::task_graph_defs::TaskNode; // Corrected import
 use crate::common_types::sprint_defs::Deliverable;
@@ -158,25 +158,35 @@ impl Agent for CoderAgent {
                             task_id: task.id.clone().to_string(),
                             agent_id: components.id.clone(),
                             error: "Target language not specified in task details.".to_string(),
+                            is_fatal: false,
                         };
                         components.bus_sender.send(BusRequest::AgentResponse { message: response }).await?;
                         return Ok(());
                     }
                 };
 
-                let mcp_args = serde_json::json!({
+                let mut mcp_args = serde_json::json!({
                     "instruction": instruction,
                     "language": language,
-                    "context": context,
                 });
+                // Left out rather than sent as null, which the input schema rejects
+                if let Some(context) = context {
+                    mcp_args["context"] = serde_json::json!(context);
+                }
+
+                // Stream the generated code so the UI can follow long generations
+                let fragment_sender = components.spawn_fragment_forwarder(task.id.to_string());
 
-                match components.mcp_manager.invoke_mcp(
+                let mcp_result = components.mcp_manager.invoke_mcp_streaming(
                     crate::common_types::mcp_defs::MCPInput {
                         mcp_id: "generate_code_v1".to_string(),
                         data: mcp_args,
-                        context_overrides: None,
-                    }
-                ).await {
+                        context_overrides: Some(components.task_context_overrides(&task)),
+                    },
+                    fragment_sender,
+                ).await;
+
+                match components.settle_mcp_result(&task.id.to_string(), mcp_result).await {
                     Ok(mcp_output) => {
                         match serde_json::from_value::<CodeGenMcpOutput>(mcp_output.processed_content.unwrap_or_default()) { // Use processed_content
                             Ok(parsed_output) => {
@@ -228,6 +238,7 @@ impl Agent for CoderAgent {
                                         task_id: task.id.to_string(),
                                         agent_id: components.id.clone(),
                                         error: format!("Failed to delegate validation sub-task: {}", e),
+                                        is_fatal: false,
                                     };
                                     if let Err(e) = components.bus_sender.send(BusRequest::AgentResponse { message: response }).await {
                                         error!("Failed to send TaskFailed response after delegation failure from CoderAgent {}: {}", components.id, e);
@@ -245,6 +256,7 @@ impl Agent for CoderAgent {
                                     task_id: task.id.to_string(),
                                     agent_id: components.id.clone(),
                                     error: format!("Failed to parse MCP output: {}", e),
+                                    is_fatal: false,
                                 };
                                 if let Err(e) = components.bus_sender.send(BusRequest::AgentResponse { message: response }).await {
                                     error!("Failed to send TaskFailed response from CoderAgent {}: {}", components.id, e);
@@ -258,6 +270,7 @@ impl Agent for CoderAgent {
                             task_id: task.id.to_string(),
                             agent_id: components.id.clone(),
                             error: format!("MCP tool invocation failed: {}", e),
+                            is_fatal: is_fatal_failure(&e),
                         };
                         if let Err(e) = components.bus_sender.send(BusRequest::AgentResponse { message: response }).await {
                             error!("Failed to send TaskFailed response from CoderAgent {}: {}", components.id, e);
@@ -14,7 +14,7 @@ use crate::common_types::task_graph_defs::TaskEdgeType; // Corrected import path
 use crate::common_types::message_defs::{MessageContent, AgentResponse}; // Import MessageContent enum and AgentResponse
 use crate::mcp_manager::MCPManager;
 use super::base_agent::Agent;
-use crate::agents::base_agent_components::BaseAgentComponents;
+use crate::agents::base_agent_components::{BaseAgentComponents, is_fatal_failure};
 use crate::communication_bus::BusRequest; // Import BusRequest
 
 pub struct PlannerAgent {
@@ -112,20 +112,23 @@ impl Agent for PlannerAgent {
         let objective = task.description.clone();
         let context = task.task_spec.context.clone(); // Use task.task_spec.context
 
+        // Absent fields are left out rather than sent as null, which the input schema rejects
+        let mut mcp_input_data = serde_json::json!({ "objective": objective });
+        if let Some(context) = context {
+            mcp_input_data["context"] = serde_json::json!(context);
+        }
+
         let mcp_input = MCPInput {
             mcp_id: "decompose_task_v1".to_string(),
-            data: serde_json::json!({
-                "objective": objective,
-                "context": context,
-            }),
-            context_overrides: None,
+            data: mcp_input_data,
+            context_overrides: Some(components.task_context_overrides(&task)),
         };
 
         let mcp_result = components.mcp_manager.invoke_mcp(
             mcp_input,
         ).await;
 
-        match mcp_result {
+        match components.settle_mcp_result(&task.id.to_string(), mcp_result).await {
             Ok(mcp_output) => {
                 #[derive(Deserialize)]
                 struct RawSubTask {
@@ -211,6 +214,7 @@ impl Agent for PlannerAgent {
                             task_id: task.id.to_string(),
                             agent_id: self.id().await,
                             error: format!("Failed to parse MCP output: {}", e),
+                            is_fatal: false,
                         };
                         if let Err(e) = components.bus_sender.send(BusRequest::AgentResponse { message: response }).await {
                             eprintln!("PlannerAgent {} failed to send TaskFailed response after parsing error: {}", components.id, e);
@@ -224,6 +228,7 @@ impl Agent for PlannerAgent {
                     task_id: task.id.to_string(),
                     agent_id: self.id().await,
                     error: format!("Failed to invoke MCP: {}", e),
+                    is_fatal: is_fatal_failure(&e),
                 };
                 if let Err(e) = components.bus_sender.send(BusRequest::AgentResponse { message: response }).await {
                     eprintln!("PlannerAgent {} failed to send TaskFailed response after MCP invocation error: {}", components.id, e);
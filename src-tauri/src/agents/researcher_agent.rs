@@ -6,7 +6,7 @@ use serde_json::json;
 use std::sync::Arc; // Import Arc
 
 use crate::agents::base_agent::Agent;
-use crate::agents::base_agent_components::BaseAgentComponents;
+use crate::agents::base_agent_components::{BaseAgentComponents, is_fatal_failure, settle_mcp_result};
 use crate::common_types::agent_defs::{AgentCapabilities, AgentConfig, AgentStatus};
 use crate::common_types::message_defs::{Message, MessageContent, InformationResponse, AgentResponse};
 use crate::common_types::sprint_defs::Deliverable;
@@ -101,6 +101,7 @@ impl Agent for ResearcherAgent {
                                     agent_id: agent_id.clone(),
                                     task_id: task_id.to_string(),
                                     error: format!("Task processing failed: {}", e),
+                                    is_fatal: is_fatal_failure(&e),
                                 };
                                 let components = self.components.lock().await;
                                 if let Err(e) = components.bus_sender.send(BusRequest::AgentResponse { message: response }).await {
@@ -117,6 +118,7 @@ impl Agent for ResearcherAgent {
                             let original_requesting_agent_id_clone = info_request.requesting_agent_id.clone(); // Clone for the async block
                             let request_query_clone = info_request.query.clone(); // Clone for the async block
                             let mcp_manager = components_lock.mcp_manager.clone(); // Clone mcp_manager for the async block
+                            let context_overrides = components_lock.llm_context_overrides(&original_task_id_clone);
                             drop(components_lock); // Release the lock
 
                             tokio::spawn(async move {
@@ -126,10 +128,12 @@ impl Agent for ResearcherAgent {
                                         "query": info_request.query,
                                         "num_results_to_summarize": 3 // Using default from MCP
                                     }),
-                                    context_overrides: None,
+                                    context_overrides: Some(context_overrides),
                                 };
 
                                 let mcp_result = mcp_manager.invoke_mcp(mcp_input).await;
+                                // Research done on behalf of another task is billed to that task
+                                let mcp_result = settle_mcp_result(&response_sender, &agent_id_clone, &original_task_id_clone, mcp_result).await;
 
                                 let info_response_payload = match mcp_result {
                                     Ok(mcp_output) => {
@@ -210,14 +214,14 @@ impl Agent for ResearcherAgent {
                 "query": query,
                 "num_results_to_summarize": 3 // Using default from MCP
             }),
-            context_overrides: None, // Added context_overrides
+            context_overrides: Some(components.task_context_overrides(&task)),
         };
 
         let mcp_result = components.mcp_manager.invoke_mcp(
             mcp_input,
         ).await;
 
-        match mcp_result {
+        match components.settle_mcp_result(&task.id.to_string(), mcp_result).await {
             Ok(mcp_output) => {
                 match serde_json::from_value::<ResearchMcpOutput>(mcp_output.processed_content.unwrap_or_default()) { // Handle None case
                     Ok(parsed_output) => {
@@ -237,6 +241,7 @@ impl Agent for ResearcherAgent {
                             agent_id: components.id.clone(),
                             task_id: task.id.to_string(),
                             error: format!("Failed to parse MCP output: {}", e),
+                            is_fatal: false,
                         };
                         components.bus_sender.send(BusRequest::AgentResponse { message: response }).await?;
                         return Err(e.into());
@@ -248,6 +253,7 @@ impl Agent for ResearcherAgent {
                     agent_id: components.id.clone(),
                     task_id: task.id.to_string(),
                     error: format!("MCP invocation failed: {}", e),
+                    is_fatal: is_fatal_failure(&e),
                 };
                 components.bus_sender.send(BusRequest::AgentResponse { message: response }).await?;
                 return Err(e.into());
@@ -10,7 +10,7 @@ use serde_json::Value;
 
 use crate::mcp_manager::MCPManager;
 
-use crate::agents::base_agent_components::BaseAgentComponents;
+use crate::agents::base_agent_components::{BaseAgentComponents, is_fatal_failure};
 
 pub struct SimpleWorkerAgent {
     components: Mutex<BaseAgentComponents>,
@@ -106,7 +106,7 @@ impl Agent for SimpleWorkerAgent {
         let mcp_input = crate::common_types::mcp_defs::MCPInput {
             mcp_id: task.mcp_id.clone().unwrap_or_default(), // Use mcp_id from TaskNode, provide default if None
             data: mcp_input_data,
-            context_overrides: None, // Assuming context_overrides is not part of TaskNode yet
+            context_overrides: Some(components.task_context_overrides(&task)),
         };
 
         let mcp_manager = Arc::clone(&components.mcp_manager);
@@ -114,7 +114,9 @@ impl Agent for SimpleWorkerAgent {
         let task_id_for_response = task.id.to_string();
         let _assigned_agent_id_for_response = task.assigned_agent_id.clone(); // Unused variable
 
-        match mcp_manager.invoke_mcp(mcp_input).await {
+        let mcp_result = mcp_manager.invoke_mcp(mcp_input).await;
+
+        match components.settle_mcp_result(&task_id_for_response, mcp_result).await {
             Ok(output) => {
                 println!("Agent received MCP output: {:?}", output.processed_content);
                 let response = crate::common_types::message_defs::AgentResponse::TaskCompleted {
@@ -137,6 +139,7 @@ impl Agent for SimpleWorkerAgent {
                     task_id: task_id_for_response.clone(),
                     agent_id: agent_id_for_response.clone(),
                     error: format!("Failed to invoke MCP: {}", e),
+                    is_fatal: is_fatal_failure(&e),
                 };
                 if let Err(e) = components.bus_sender.send(BusRequest::AgentResponse { message: response }).await {
                     eprintln!("Agent {} failed to send TaskFailed response: {}", agent_id_for_response, e);
@@ -1,5 +1,5 @@
 use crate::agents::base_agent::Agent;
-use crate::agents::base_agent_components::BaseAgentComponents;
+use crate::agents::base_agent_components::{BaseAgentComponents, is_fatal_failure};
 use crate::common_types::agent_defs::{AgentConfig, AgentStatus, AgentCapabilities};
 use crate::common_types::sprint_defs::{Deliverable};
 use crate::common_types::task_graph_defs::TaskNode;
@@ -197,6 +197,7 @@ impl Agent for ValidatorAgent {
                     task_id: task.id.to_string(),
                     agent_id: components.id.clone(),
                     error: error_msg,
+                    is_fatal: false,
                 };
                 components.bus_sender.send(BusRequest::AgentResponse { message: response }).await?;
                 return Ok(());
@@ -217,12 +218,12 @@ impl Agent for ValidatorAgent {
         let mcp_input_obj = MCPInput {
             mcp_id: "validate_content_v1".to_string(),
             data: mcp_input,
-            context_overrides: None,
+            context_overrides: Some(components.task_context_overrides(&task)),
         };
 
         let mcp_result = components.mcp_manager.invoke_mcp(mcp_input_obj).await;
 
-        match mcp_result {
+        match components.settle_mcp_result(&task.id.to_string(), mcp_result).await {
             Ok(mcp_output) => {
                 match mcp_output.processed_content {
                     Some(processed_content_value) => {
@@ -250,6 +251,7 @@ impl Agent for ValidatorAgent {
                                     task_id: task.id.to_string(),
                                     agent_id: components.id.clone(),
                                     error: error_msg,
+                                    is_fatal: false,
                                 };
                                 components.bus_sender.send(BusRequest::AgentResponse { message: response }).await?;
                             }
@@ -262,6 +264,7 @@ impl Agent for ValidatorAgent {
                             task_id: task.id.to_string(),
                             agent_id: components.id.clone(),
                             error: error_msg,
+                            is_fatal: false,
                         };
                         components.bus_sender.send(BusRequest::AgentResponse { message: response }).await?;
                     }
@@ -274,6 +277,7 @@ impl Agent for ValidatorAgent {
                     task_id: task.id.to_string(),
                     agent_id: components.id.clone(),
                     error: error_msg,
+                    is_fatal: is_fatal_failure(&e),
                 };
                 components.bus_sender.send(BusRequest::AgentResponse { message: response }).await?;
             }
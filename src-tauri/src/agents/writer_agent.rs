@@ -8,7 +8,7 @@ use chrono::Utc;
 use uuid::Uuid;
 
 use crate::agents::base_agent::{Agent};
-use crate::agents::base_agent_components::BaseAgentComponents;
+use crate::agents::base_agent_components::{BaseAgentComponents, is_fatal_failure};
 use crate::common_types::agent_defs::{AgentStatus, AgentCapabilities};
 use crate::common_types::task_graph_defs::TaskNode;
 use crate::common_types::sprint_defs::Deliverable;
@@ -107,6 +107,7 @@ impl Agent for WriterAgent {
                                             task_id: task_id.to_string(),
                                             agent_id: agent_id.clone(),
                                             error: format!("Task processing failed: {}", e),
+                                            is_fatal: is_fatal_failure(&e),
                                         };
                                         let components = self.components.lock().await;
                                         if let Err(e) = components.bus_sender.send(BusRequest::AgentResponse { message: response }).await {
@@ -132,6 +133,7 @@ impl Agent for WriterAgent {
                                                     task_id: task_id.to_string(),
                                                     agent_id,
                                                     error: format!("Task processing failed after research: {}", e),
+                                                    is_fatal: is_fatal_failure(&e),
                                                 };
                                                 let components = self.components.lock().await;
                                                 if let Err(e) = components.bus_sender.send(BusRequest::AgentResponse { message: response }).await {
@@ -241,7 +243,7 @@ impl Agent for WriterAgent {
         let mcp_input = MCPInput {
             mcp_id: "draft_content_v1".to_string(),
             data: mcp_input_data,
-            context_overrides: None,
+            context_overrides: Some(components.task_context_overrides(&task)),
         };
 
         #[derive(Deserialize, Debug)]
@@ -249,9 +251,15 @@ impl Agent for WriterAgent {
             draft_text: String,
         }
 
-        match components.mcp_manager.invoke_mcp(
+        // Stream the draft so the UI can follow long generations
+        let fragment_sender = components.spawn_fragment_forwarder(task.id.to_string());
+
+        let mcp_result = components.mcp_manager.invoke_mcp_streaming(
             mcp_input,
-        ).await {
+            fragment_sender,
+        ).await;
+
+        match components.settle_mcp_result(&task.id.to_string(), mcp_result).await {
             Ok(mcp_output) => {
                 match serde_json::from_value::<WritingMcpOutput>(mcp_output.processed_content.unwrap_or_default()) {
                     Ok(parsed_output) => {
@@ -273,6 +281,7 @@ impl Agent for WriterAgent {
                             task_id: task.id.to_string(),
                             agent_id: components.id.clone(),
                             error: format!("Failed to parse MCP output: {}", e),
+                            is_fatal: false,
                         };
                         if let Err(e) = components.bus_sender.send(BusRequest::AgentResponse { message: response }).await {
                             error!("Failed to send TaskFailed response from WriterAgent: {}", e);
@@ -286,6 +295,7 @@ impl Agent for WriterAgent {
                     task_id: task.id.to_string(),
                     agent_id: components.id.clone(),
                     error: format!("Failed to invoke MCP: {}", e),
+                    is_fatal: is_fatal_failure(&e),
                 };
                 if let Err(e) = components.bus_sender.send(BusRequest::AgentResponse { message: response }).await {
                     error!("Failed to send TaskFailed response from WriterAgent: {}", e);
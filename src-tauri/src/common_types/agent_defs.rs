@@ -94,6 +94,9 @@ pub struct AgentConfig {
     pub role: AgentRole,
     pub llm_model: Option<String>,
     pub llm_provider_name: Option<String>,
+    // Sampling parameters (`LLMParameters` shape) applied to every MCP call made by this agent
+    #[serde(default)]
+    pub llm_parameters: Option<Value>,
     pub specialized_config: Option<Value>,
 }
 
@@ -1,18 +1,100 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::fmt;
 
-use crate::external_api_client::{LLMRequest, LLMResponse, LLMTokenCounts};
+use crate::external_api_client::{LLMError, LLMMessage, LLMParameters, LLMRequest, LLMResponse, LLMRoute, LLMTokenCounts, RoutingPolicy};
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct MCPDefinition {
     pub id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub version: Option<String>,
     pub description: String,
+    // JSON Schema of `MCPInput.data`, checked before the template is rendered
+    #[serde(default)]
+    pub input_schema: Option<Value>,
+    // JSON Schema of the completion; when set the completion must be JSON matching it
+    #[serde(default)]
+    pub output_schema: Option<Value>,
+    // Only "handlebars" is supported; definitions naming another engine are not loaded
+    #[serde(default)]
+    pub template_engine: Option<String>,
+    // Unused when logic_module_path names a Rust implementation
+    #[serde(default)]
     pub template: String,
     pub logic_module_path: Option<String>,
     pub default_llm_provider: Option<String>,
+    pub default_llm_model: Option<String>,
+    // Sampling parameters used unless the caller overrides them
+    pub default_llm_parameters: Option<LLMParameters>,
+    // Routes tried, in order, when the default provider fails
+    #[serde(default)]
+    pub fallback_routes: Vec<LLMRoute>,
+    // Example exchanges placed between the system prompt and the conversation
+    #[serde(default)]
+    pub few_shot_messages: Vec<LLMMessage>,
+    // Names of registered local tools the model may call
+    #[serde(default)]
+    pub tools: Vec<String>,
+    // What to do when the rendered prompt overflows the model's context window; fails the call when unset
+    #[serde(default)]
+    pub context_overflow: Option<ContextOverflowPolicy>,
+    // Follow-up turns asking the model to fix a completion that does not match output_schema
+    #[serde(default = "default_max_repair_attempts")]
+    pub max_repair_attempts: u32,
     // Add other fields as needed
 }
 
+fn default_max_repair_attempts() -> u32 {
+    1
+}
+
+/// What to do when a rendered prompt would not fit in the model's context window.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextOverflowStrategy {
+    #[default]
+    Fail,
+    // Cut the end of the marked input fields
+    Truncate,
+    // Replace the marked input fields by a summary from the summarize_text MCP
+    Summarize,
+}
+
+/// Context-window guard settings of an MCP definition.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ContextOverflowPolicy {
+    #[serde(default)]
+    pub strategy: ContextOverflowStrategy,
+    // Top-level string fields of the MCP input that may be shortened, e.g. upstream deliverables
+    #[serde(default)]
+    pub fields: Vec<String>,
+    // Used when the model's catalog gives no context length
+    #[serde(default)]
+    pub max_context_tokens: Option<u64>,
+    // Tokens kept for the completion when the request does not set max_tokens
+    #[serde(default = "default_reserved_completion_tokens")]
+    pub reserved_completion_tokens: u32,
+}
+
+fn default_reserved_completion_tokens() -> u32 {
+    1024
+}
+
+// Same values as a definition without a `context_overflow` block gets from serde
+impl Default for ContextOverflowPolicy {
+    fn default() -> Self {
+        Self {
+            strategy: ContextOverflowStrategy::default(),
+            fields: Vec::new(),
+            max_context_tokens: None,
+            reserved_completion_tokens: default_reserved_completion_tokens(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct MCPInput {
     pub mcp_id: String,
@@ -24,11 +106,28 @@ pub struct MCPInput {
 pub struct MCPContextOverrides {
     pub llm_provider: Option<String>,
     pub llm_model: Option<String>,
+    // Parsed as `LLMParameters`; set fields take precedence over the MCP definition defaults
     pub llm_parameters: Option<Value>,
     pub additional_context: Option<Value>,
+    // Replaces llm_provider and the definition's routes entirely when set
+    #[serde(default)]
+    pub routing_policy: Option<RoutingPolicy>,
+    // Call the provider even when the response cache holds an answer
+    #[serde(default)]
+    pub bypass_cache: bool,
+    // Earlier turns of the conversation (e.g. a rejected answer and the repair request),
+    // sent before the rendered template
+    #[serde(default)]
+    pub prior_messages: Vec<LLMMessage>,
+    // Offer the MCP's tools to the model; set for agents that can use tools
+    #[serde(default)]
+    pub tools_enabled: bool,
+    // Task the call is made for; cancelling the task aborts the call
+    #[serde(default)]
+    pub task_id: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct MCPOutput {
     pub request_id: String,
     pub mcp_id: String,
@@ -36,23 +135,164 @@ pub struct MCPOutput {
     pub llm_request_details: Option<LLMRequest>,
     pub llm_response_details: Option<LLMResponse>,
     pub processed_content: Option<Value>,
+    // Completion text as the model wrote it, before the JSON object was extracted into processed_content
+    #[serde(default)]
+    pub raw_completion: Option<String>,
+    // Every completion the model gave, the first answer then each repair, with why it was rejected
+    #[serde(default)]
+    pub attempts: Vec<MCPAttempt>,
     pub error_message: Option<String>,
     pub usage_metrics: Option<MCPUsageMetrics>,
 }
 
+/// A place where a value does not match its JSON Schema.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SchemaViolation {
+    // JSON pointer (RFC 6901) to the offending value; empty for the document itself
+    pub pointer: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pointer = if self.pointer.is_empty() { "/" } else { &self.pointer };
+        write!(f, "{}: {}", pointer, self.message)
+    }
+}
+
+/// One completion of an MCP invocation. `violations` is empty for the accepted one.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MCPAttempt {
+    pub completion: String,
+    pub violations: Vec<SchemaViolation>,
+}
+
+/// MCP invocation whose LLM call did not succeed, as surfaced to agents.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("MCP {mcp_id} failed: {message}")]
+pub struct MCPCallFailed {
+    pub mcp_id: String,
+    pub status: MCPStatus,
+    pub message: String,
+}
+
+impl MCPOutput {
+    /// The output itself when its status is `Success`, an `MCPCallFailed` otherwise.
+    pub fn into_result(self) -> Result<MCPOutput, MCPCallFailed> {
+        if self.status == MCPStatus::Success {
+            return Ok(self);
+        }
+        Err(MCPCallFailed {
+            message: self.error_message.clone().unwrap_or_else(|| format!("{:?}", self.status)),
+            mcp_id: self.mcp_id,
+            status: self.status,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum MCPStatus {
     Success,
+    // The provider rejected the request as built (bad parameter, unknown provider...)
     LLMError { provider_error: String },
-    MCPProcessingError,
+    // The routed provider has no credentials; retrying cannot help until a key is set
+    ProviderNotConfigured { provider: String },
+    // The provider rejected the API key, or the account has no credits left
+    AuthenticationFailed { provider: String },
+    RateLimited { provider: String },
+    // The prompt did not fit in the model's context window. The sizes are known when the
+    // guard caught it before the call, not when the provider rejected the prompt
+    ContextLengthExceeded { estimated_tokens: Option<u64>, context_length: Option<u64> },
+    ModelNotFound { provider: String },
+    ContentFiltered { provider: String },
+    // Network failure, timeout or server error: usually gone on a later attempt
+    ProviderUnavailable { provider: String },
+    MalformedResponse { provider: String },
+    // The task was cancelled while the call was in flight
+    Cancelled,
+    // `MCPInput.data` does not match the definition's input_schema
+    InvalidInput { violations: Vec<SchemaViolation> },
+    // The completion is not JSON or does not match the definition's output_schema
+    MCPProcessingError { violations: Vec<SchemaViolation> },
     ConfigurationError,
     // Add more statuses as needed
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+impl MCPStatus {
+    /// Whether a task failing with this status would fail the same way when retried,
+    /// so it should not be.
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            MCPStatus::LLMError { .. }
+                | MCPStatus::ProviderNotConfigured { .. }
+                | MCPStatus::AuthenticationFailed { .. }
+                | MCPStatus::ContextLengthExceeded { .. }
+                | MCPStatus::ModelNotFound { .. }
+                | MCPStatus::ContentFiltered { .. }
+                | MCPStatus::Cancelled
+                | MCPStatus::InvalidInput { .. }
+                | MCPStatus::ConfigurationError
+        )
+    }
+}
+
+impl From<&LLMError> for MCPStatus {
+    fn from(error: &LLMError) -> Self {
+        let provider = error.provider().to_string();
+        match error {
+            LLMError::Auth { .. } => MCPStatus::AuthenticationFailed { provider },
+            LLMError::NotConfigured { .. } => MCPStatus::ProviderNotConfigured { provider },
+            LLMError::RateLimited { .. } => MCPStatus::RateLimited { provider },
+            LLMError::ContextLength { .. } => MCPStatus::ContextLengthExceeded { estimated_tokens: None, context_length: None },
+            LLMError::ModelNotFound { .. } => MCPStatus::ModelNotFound { provider },
+            LLMError::ContentFiltered { .. } => MCPStatus::ContentFiltered { provider },
+            LLMError::Transport { .. } => MCPStatus::ProviderUnavailable { provider },
+            LLMError::MalformedResponse { .. } => MCPStatus::MalformedResponse { provider },
+            LLMError::Cancelled { .. } => MCPStatus::Cancelled,
+            LLMError::InvalidRequest { .. } => MCPStatus::LLMError { provider_error: error.to_string() },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct MCPUsageMetrics {
     pub llm_token_counts: Option<LLMTokenCounts>,
     pub processing_time_ms: Option<u64>,
     pub llm_call_duration_ms: Option<u64>,
+    // Model that served the call, used to price it
+    #[serde(default)]
+    pub llm_model: Option<String>,
+    // USD, from the price table; None when the model has no known price
+    #[serde(default)]
+    pub estimated_cost_usd: Option<f64>,
     // Add more metrics as needed
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::external_api_client::providers::ProviderHttpError;
+
+    fn status_for(http_status: u16) -> MCPStatus {
+        let error = LLMError::from_http(&ProviderHttpError { provider: "Test".to_string(), status: http_status, body: String::new(), retry_after: None });
+        MCPStatus::from(&error)
+    }
+
+    #[test]
+    fn test_bad_key_is_fatal_and_outage_is_not() {
+        assert_eq!(status_for(401), MCPStatus::AuthenticationFailed { provider: "Test".to_string() });
+        assert!(status_for(401).is_fatal());
+        assert_eq!(status_for(503), MCPStatus::ProviderUnavailable { provider: "Test".to_string() });
+        assert!(!status_for(503).is_fatal());
+        assert!(!status_for(429).is_fatal());
+        assert!(status_for(404).is_fatal());
+    }
+
+    #[test]
+    fn test_default_overflow_policy_reserves_completion_tokens() {
+        let from_serde: ContextOverflowPolicy = serde_json::from_value(serde_json::json!({})).unwrap();
+        assert_eq!(ContextOverflowPolicy::default(), from_serde);
+        assert_eq!(ContextOverflowPolicy::default().reserved_completion_tokens, 1024);
+    }
+}
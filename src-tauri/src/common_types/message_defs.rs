@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 
 use crate::common_types::task_graph_defs::TaskNode;
-use crate::common_types::mcp_defs::{MCPInput, MCPOutput};
+use crate::common_types::mcp_defs::{MCPInput, MCPOutput, MCPUsageMetrics};
 use crate::common_types::sprint_defs::Deliverable;
 use crate::common_types::task_defs::{TaskSpecification, SubTaskDefinition, SubTaskEdgeDefinition};
 use crate::common_types::agent_defs::AgentRole;
@@ -47,9 +47,10 @@ pub enum MessageContent {
     TaskAssignment { task: TaskNode },
     TaskAcknowledgement { task_id: String, agent_id: String },
     StatusUpdate { agent_id: String, status: String, message: Option<String> },
-    DataFragment { data: String },
+    DataFragment { task_id: Option<String>, data: String }, // Partial output, e.g. streamed LLM tokens for a task
     InvokeMCPRequest { request_id: String, input: MCPInput },
     InvokeMCPResponse { response: MCPOutput },
+    UsageReport { task_id: String, agent_id: String, usage: MCPUsageMetrics }, // LLM usage of one MCP call made for a task
     AgentResponse(AgentResponse),
     RequestInformation(InformationRequest),
     ReturnInformation(InformationResponse),
@@ -68,5 +69,12 @@ pub enum MessageContent {
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum AgentResponse {
     TaskCompleted { task_id: String, agent_id: String, deliverable: Deliverable },
-    TaskFailed { task_id: String, agent_id: String, error: String },
+    TaskFailed {
+        task_id: String,
+        agent_id: String,
+        error: String,
+        // Retrying would fail the same way (rejected key, unknown model...), so the task fails at once
+        #[serde(default)]
+        is_fatal: bool,
+    },
 }
\ No newline at end of file
@@ -7,6 +7,7 @@ use serde_json::Value;
 use crate::common_types::sprint_defs::Deliverable; // Import Deliverable
 use crate::common_types::task_defs::{TaskStatus, TaskSpecification}; // Import TaskSpecification
 use crate::common_types::agent_defs::AgentRole;
+use crate::common_types::mcp_defs::MCPUsageMetrics;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum TaskEdgeType {
@@ -41,7 +42,7 @@ pub struct TaskInput {
 }
 
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct TaskNode {
     pub id: Uuid,
     pub name: String,
@@ -63,6 +64,40 @@ pub struct TaskNode {
     pub assigned_agent_id: Option<String>,
     pub error_message: Option<String>, // Added to store failure details
     pub sprint_id: Option<Uuid>, // Added sprint_id field
+    #[serde(default)]
+    pub usage: TaskUsage, // LLM usage accumulated over every MCP call made for this task
+}
+
+/// Accumulated LLM usage and estimated cost of a task or of a whole graph.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TaskUsage {
+    pub llm_calls: u32,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+    pub estimated_cost_usd: f64,
+}
+
+impl TaskUsage {
+    /// Adds the metrics of one MCP call.
+    pub fn record_call(&mut self, metrics: &MCPUsageMetrics) {
+        self.llm_calls += 1;
+        if let Some(tokens) = &metrics.llm_token_counts {
+            self.prompt_tokens += tokens.prompt_tokens;
+            self.completion_tokens += tokens.completion_tokens;
+            self.total_tokens += tokens.total_tokens;
+        }
+        self.estimated_cost_usd += metrics.estimated_cost_usd.unwrap_or(0.0);
+    }
+
+    /// Adds another usage total, e.g. a task's usage into its graph's.
+    pub fn accumulate(&mut self, other: &TaskUsage) {
+        self.llm_calls += other.llm_calls;
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_tokens += other.total_tokens;
+        self.estimated_cost_usd += other.estimated_cost_usd;
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)] // Add Default
@@ -88,7 +123,7 @@ pub struct TaskEdge {
     pub edge_type: TaskEdgeType, // Added TaskEdgeType
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct TaskGraph {
     pub id: Uuid,
     pub name: String,
@@ -100,6 +135,8 @@ pub struct TaskGraph {
     pub overall_goal: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    #[serde(default)]
+    pub usage: TaskUsage, // Sum of the usage of all nodes
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -1,20 +1,58 @@
+use std::sync::Mutex;
 use tokio::sync::{broadcast, mpsc};
-use crate::common_types::Message;
+use crate::common_types::{Message, MessageContent, generate_id};
+use crate::common_types::message_defs::AgentResponse;
 
 const BUS_CHANNEL_CAPACITY: usize = 100; // Define a capacity for the broadcast channel
+// Streamed tokens come in bursts, and only the frontend forwarder listens to them
+const FRAGMENT_CHANNEL_CAPACITY: usize = 1024;
 
 #[derive(Debug)] // Add Debug trait
 pub struct CommunicationBus {
     sender: broadcast::Sender<Message>,
     request_sender: mpsc::Sender<BusRequest>,
+    // Taken by `start_request_forwarding`
+    request_receiver: Mutex<Option<mpsc::Receiver<BusRequest>>>,
+    // Kept apart from the agents' bus so a stream of tokens does not make the agents lag
+    fragment_sender: broadcast::Sender<Message>,
 }
 
 impl CommunicationBus {
     /// Creates a new instance of the CommunicationBus.
     pub fn new() -> Self {
         let (sender, _receiver) = broadcast::channel(BUS_CHANNEL_CAPACITY);
-        let (request_sender, _request_receiver) = mpsc::channel(BUS_CHANNEL_CAPACITY);
-        CommunicationBus { sender, request_sender }
+        let (request_sender, request_receiver) = mpsc::channel(BUS_CHANNEL_CAPACITY);
+        let (fragment_sender, _fragment_receiver) = broadcast::channel(FRAGMENT_CHANNEL_CAPACITY);
+        CommunicationBus { sender, request_sender, request_receiver: Mutex::new(Some(request_receiver)), fragment_sender }
+    }
+
+    /// Spawns the task delivering the requests sent with `get_bus_request_sender`: messages and agent
+    /// responses are published on the bus, data fragments on the fragment channel. Does nothing once started.
+    pub fn start_request_forwarding(&self) {
+        let Some(mut request_receiver) = self.request_receiver.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take() else {
+            return;
+        };
+        let sender = self.sender.clone();
+        let fragment_sender = self.fragment_sender.clone();
+        tokio::spawn(async move {
+            while let Some(request) = request_receiver.recv().await {
+                // Sending only fails when nobody listens, in which case the message has no use
+                match request {
+                    BusRequest::GeneralMessage { message } => {
+                        let _ = sender.send(message);
+                    }
+                    BusRequest::AgentResponse { message } => {
+                        let sender_id = match &message {
+                            AgentResponse::TaskCompleted { agent_id, .. } | AgentResponse::TaskFailed { agent_id, .. } => agent_id.clone(),
+                        };
+                        let _ = sender.send(Message { id: generate_id(), sender_id, receiver_id: None, content: MessageContent::AgentResponse(message) });
+                    }
+                    BusRequest::DataFragment { message } => {
+                        let _ = fragment_sender.send(message);
+                    }
+                }
+            }
+        });
     }
 
     /// Allows an agent or component to subscribe to messages on the bus.
@@ -39,6 +77,11 @@ impl CommunicationBus {
     pub fn get_bus_request_sender(&self) -> tokio::sync::mpsc::Sender<BusRequest> {
         self.request_sender.clone()
     }
+
+    /// Receives the `DataFragment` messages sent as `BusRequest::DataFragment`, e.g. streamed LLM tokens.
+    pub fn subscribe_fragments(&self) -> broadcast::Receiver<Message> {
+        self.fragment_sender.subscribe()
+    }
 }
 
 // Define the types of requests that can be sent TO the communication bus
@@ -46,13 +89,13 @@ impl CommunicationBus {
 pub enum BusRequest {
     GeneralMessage { message: Message }, // Request to publish a general message
     AgentResponse { message: crate::common_types::message_defs::AgentResponse },
+    DataFragment { message: Message }, // Request to publish a partial output on the fragment channel
 }
 
 // Add tests later if needed
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::common_types::{MessageContent, generate_id};
     use tokio::time::{self, Duration};
 
     #[tokio::test]
@@ -65,7 +108,7 @@ mod tests {
             id: generate_id(),
             sender_id: "test_sender".to_string(),
             receiver_id: None,
-            content: MessageContent::DataFragment { data: "Hello, HIVE!".to_string() },
+            content: MessageContent::DataFragment { task_id: None, data: "Hello, HIVE!".to_string() },
         };
 
         // Publish the message
@@ -84,4 +127,30 @@ mod tests {
         assert!(received2.is_ok());
         assert_eq!(received2.unwrap().content, message.content);
     }
+
+    #[tokio::test]
+    async fn test_requests_are_forwarded_to_their_channel() {
+        let bus = CommunicationBus::new();
+        bus.start_request_forwarding();
+        let mut receiver = bus.subscribe();
+        let mut fragments = bus.subscribe_fragments();
+        let request_sender = bus.get_bus_request_sender();
+
+        let fragment = Message {
+            id: generate_id(),
+            sender_id: "agent-1".to_string(),
+            receiver_id: None,
+            content: MessageContent::DataFragment { task_id: Some("task-1".to_string()), data: "Hel".to_string() },
+        };
+        request_sender.send(BusRequest::DataFragment { message: fragment.clone() }).await.unwrap();
+        let response = AgentResponse::TaskFailed { task_id: "task-1".to_string(), agent_id: "agent-1".to_string(), error: "Cancelled".to_string(), is_fatal: true };
+        request_sender.send(BusRequest::AgentResponse { message: response.clone() }).await.unwrap();
+
+        let received = time::timeout(Duration::from_secs(1), receiver.recv()).await.unwrap().unwrap();
+        assert_eq!(received.sender_id, "agent-1");
+        assert_eq!(received.content, MessageContent::AgentResponse(response));
+        assert_eq!(fragments.recv().await.unwrap().content, fragment.content);
+        // Fragments are not published on the bus
+        assert!(receiver.try_recv().is_err());
+    }
 }
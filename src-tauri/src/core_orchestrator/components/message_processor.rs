@@ -59,8 +59,8 @@ impl MessageProcessor {
                                             eprintln!("Failed to send TaskCompleted message to results channel: {}", e);
                                         }
                                     }
-                                    crate::common_types::message_defs::AgentResponse::TaskFailed { task_id, agent_id, error } => {
-                                        if let Err(e) = task_results_sender.send((task_id.clone(), MessageContent::AgentResponse(crate::common_types::message_defs::AgentResponse::TaskFailed { task_id, agent_id: agent_id.clone(), error }))).await {
+                                    crate::common_types::message_defs::AgentResponse::TaskFailed { task_id, agent_id, error, is_fatal } => {
+                                        if let Err(e) = task_results_sender.send((task_id.clone(), MessageContent::AgentResponse(crate::common_types::message_defs::AgentResponse::TaskFailed { task_id, agent_id: agent_id.clone(), error, is_fatal }))).await {
                                             eprintln!("Failed to send TaskFailed message to results channel: {}", e);
                                         }
                                     }
@@ -70,6 +70,11 @@ impl MessageProcessor {
                                     }
                                 }
                             }
+                            MessageContent::UsageReport { task_id, agent_id, usage } => {
+                                if let Err(e) = task_results_sender.send((task_id.clone(), MessageContent::UsageReport { task_id, agent_id, usage })).await {
+                                    eprintln!("Failed to send UsageReport message to results channel: {}", e);
+                                }
+                            }
                             MessageContent::RequestInformation(info_request) => {
                                 println!("Message Processor received RequestInformation message: {:?}", info_request);
                                 // Attempt to find a ResearcherAgent
@@ -127,6 +132,7 @@ impl MessageProcessor {
                                                 task_id: parent_task_id_clone,
                                                 agent_id: delegating_agent_id_clone,
                                                 error: error_msg,
+                                                is_fatal: false,
                                             }),
                                         };
                                         if let Err(e) = bus_writer_clone.send(failure_message) {
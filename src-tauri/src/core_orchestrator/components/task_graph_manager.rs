@@ -1,7 +1,7 @@
 // src-tauri/src/core_orchestrator/components/task_graph_manager.rs
 
 use std::collections::HashMap;
-use crate::common_types::{TaskGraph, TaskNode, TaskEdge, Sprint, TaskSpecification};
+use crate::common_types::{TaskGraph, TaskGraphStatus, TaskNode, TaskEdge, TaskStatus, Sprint, TaskSpecification, TaskUsage, MCPUsageMetrics};
 use std::sync::Arc;
 use tokio::sync::{Mutex, RwLock};
 use anyhow::Result;
@@ -59,7 +59,9 @@ impl TaskGraphManager {
                     status: crate::common_types::TaskGraphStatus::Pending,
                     created_at: Utc::now(),
                     updated_at: Utc::now(),
+                    usage: TaskUsage::default(),
                 });
+            graph.usage.accumulate(&task.usage);
             graph.nodes.insert(task.id.to_string(), task);
         }
 
@@ -105,6 +107,7 @@ impl TaskGraphManager {
             status: crate::common_types::TaskGraphStatus::Pending,
             created_at: now,
             updated_at: now,
+            usage: TaskUsage::default(),
         };
 
         {
@@ -195,6 +198,7 @@ impl TaskGraphManager {
             assigned_agent_id: None,
             error_message: None,
             sprint_id,
+            usage: TaskUsage::default(),
         };
 
         // Persist first
@@ -260,6 +264,51 @@ impl TaskGraphManager {
         Ok(edge_id.to_string())
     }
 
+    /// Adds the usage of one MCP call to a task and its graph, then persists the task.
+    pub async fn record_task_usage(&self, graph_id: &str, task_id: &str, metrics: &MCPUsageMetrics) -> Result<()> {
+        let mut graphs = self.active_task_graphs.write().await;
+        let graph = graphs.get_mut(graph_id)
+            .ok_or_else(|| anyhow::anyhow!("TaskGraph with ID {} not found", graph_id))?;
+        let node = graph.nodes.get_mut(task_id)
+            .ok_or_else(|| anyhow::anyhow!("Task node with ID {} not found in graph {}", task_id, graph_id))?;
+
+        node.usage.record_call(metrics);
+        // Saved under the graph lock, so a status change saved concurrently cannot be overwritten by this older copy
+        {
+            let conn = self.db_connection.lock().await;
+            persistence::save_task(&conn, node)?;
+        }
+        graph.usage.record_call(metrics);
+        Ok(())
+    }
+
+    /// Persists a task after its status changed, then settles its graph: once every task has
+    /// completed or failed, the graph is Completed, or Failed if any of its tasks failed.
+    /// Returns the graph's status when this change finished it.
+    pub async fn save_task_state(&self, graph_id: &str, task_id: &str) -> Result<Option<TaskGraphStatus>> {
+        let mut graphs = self.active_task_graphs.write().await;
+        let graph = graphs.get_mut(graph_id)
+            .ok_or_else(|| anyhow::anyhow!("TaskGraph with ID {} not found", graph_id))?;
+        let node = graph.nodes.get(task_id)
+            .ok_or_else(|| anyhow::anyhow!("Task node with ID {} not found in graph {}", task_id, graph_id))?;
+        {
+            let conn = self.db_connection.lock().await;
+            persistence::save_task(&conn, node)?;
+        }
+
+        let finished = graph.nodes.values().all(|node| matches!(node.status, TaskStatus::Completed | TaskStatus::Failed));
+        if !finished || matches!(graph.status, TaskGraphStatus::Completed | TaskGraphStatus::Failed) {
+            return Ok(None);
+        }
+        graph.status = if graph.nodes.values().any(|node| node.status == TaskStatus::Failed) {
+            TaskGraphStatus::Failed
+        } else {
+            TaskGraphStatus::Completed
+        };
+        graph.updated_at = Utc::now();
+        Ok(Some(graph.status.clone()))
+    }
+
     /// Finds the ID of the TaskGraph containing the given task ID.
     pub async fn find_graph_id_for_task(&self, task_id: &str) -> Option<String> {
         let graphs = self.active_task_graphs.read().await;
@@ -381,4 +430,52 @@ impl TaskGraphManager {
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::external_api_client::LLMTokenCounts;
+
+    fn metrics(prompt_tokens: u64, completion_tokens: u64, estimated_cost_usd: Option<f64>) -> MCPUsageMetrics {
+        MCPUsageMetrics {
+            llm_token_counts: Some(LLMTokenCounts { prompt_tokens, completion_tokens, total_tokens: prompt_tokens + completion_tokens }),
+            processing_time_ms: None,
+            llm_call_duration_ms: None,
+            llm_model: Some("scripted-model".to_string()),
+            estimated_cost_usd,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_recorded_usage_adds_up_per_task_and_graph_and_is_saved() {
+        let db_connection = Arc::new(Mutex::new(persistence::establish_in_memory_connection().unwrap()));
+        let manager = TaskGraphManager::new(Arc::clone(&db_connection)).await.unwrap();
+        let graph_id = manager.create_task_graph("Graph".to_string(), "Test graph".to_string(), "Goal".to_string()).await.unwrap();
+        let first = manager.add_task_node_to_graph(&graph_id, TaskSpecification::default(), None, None).await.unwrap();
+        let second = manager.add_task_node_to_graph(&graph_id, TaskSpecification::default(), None, None).await.unwrap();
+
+        manager.record_task_usage(&graph_id, &first, &metrics(100, 20, Some(0.5))).await.unwrap();
+        manager.record_task_usage(&graph_id, &first, &metrics(50, 10, None)).await.unwrap();
+        // A cached answer reports neither tokens nor cost, but is still a call
+        let cached = MCPUsageMetrics { llm_token_counts: None, estimated_cost_usd: None, ..metrics(0, 0, None) };
+        manager.record_task_usage(&graph_id, &second, &cached).await.unwrap();
+        assert!(manager.record_task_usage(&graph_id, "unknown-task", &cached).await.is_err());
+
+        let expected_first = TaskUsage { llm_calls: 2, prompt_tokens: 150, completion_tokens: 30, total_tokens: 180, estimated_cost_usd: 0.5 };
+        let expected_graph = TaskUsage { llm_calls: 3, ..expected_first.clone() };
+        {
+            let graphs = manager.get_active_graphs().await;
+            let graph = &graphs[&graph_id];
+            assert_eq!(graph.nodes[&first].usage, expected_first);
+            assert_eq!(graph.nodes[&second].usage.llm_calls, 1);
+            assert_eq!(graph.usage, expected_graph);
+        }
+
+        // The graph's usage is rebuilt from its saved tasks
+        let reloaded = TaskGraphManager::new(db_connection).await.unwrap();
+        let graphs = reloaded.get_active_graphs().await;
+        let graph = graphs.values().next().unwrap();
+        assert_eq!(graph.nodes[&first].usage, expected_first);
+        assert_eq!(graph.usage, expected_graph);
+    }
+}
@@ -4,6 +4,7 @@ use tokio::sync::mpsc;
 use std::collections::HashMap; // Added HashMap import
 use crate::common_types::{MessageContent, TaskStatus, Deliverable, SubTaskDefinition, SubTaskEdgeDefinition, TaskSpecification};
 use crate::core_orchestrator::components::task_graph_manager::TaskGraphManager;
+use crate::mcp_manager::cancellation::TaskCancellationRegistry;
 use chrono::{Utc, DateTime};
 use anyhow::Result;
 use std::sync::Arc;
@@ -16,6 +17,7 @@ pub struct TaskResultProcessor {
     task_graph_manager: Arc<Mutex<TaskGraphManager>>,
     agent_manager: Arc<Mutex<crate::agent_manager::AgentManager>>, // Changed to Arc<Mutex<AgentManager>>
     bus_sender: tokio::sync::broadcast::Sender<crate::common_types::Message>, // Added bus_sender to send messages
+    cancellation: Arc<TaskCancellationRegistry>, // Finished tasks release their cancellation token
 }
 
 impl TaskResultProcessor {
@@ -25,12 +27,14 @@ impl TaskResultProcessor {
         task_graph_manager: Arc<Mutex<TaskGraphManager>>,
         agent_manager: Arc<Mutex<crate::agent_manager::AgentManager>>,
         bus_sender: tokio::sync::broadcast::Sender<crate::common_types::Message>,
+        cancellation: Arc<TaskCancellationRegistry>,
     ) -> Self {
         TaskResultProcessor {
             task_results_receiver,
             task_graph_manager,
             agent_manager,
             bus_sender,
+            cancellation,
         }
     }
 
@@ -57,12 +61,8 @@ impl TaskResultProcessor {
                         match agent_response {
                             crate::common_types::message_defs::AgentResponse::TaskCompleted { task_id: completed_task_id, agent_id: _, deliverable } => {
                                 if task_id == completed_task_id {
-                                    let agent_manager_clone = self.agent_manager.clone();
-                                    let bus_sender_clone = self.bus_sender.clone();
-                                    if let Err(e) = TaskResultProcessor::handle_task_completed_success(
-                                        agent_manager_clone,
-                                        bus_sender_clone,
-                                        &task_id, vec![deliverable], now, &graph_id, graph_manager_arc.clone()
+                                    if let Err(e) = self.handle_task_completed_success(
+                                        &task_id, vec![deliverable], now, &graph_id
                                     ).await {
                                         eprintln!("Error in handle_task_completed_success for task {}: {}", task_id, e);
                                     }
@@ -70,10 +70,10 @@ impl TaskResultProcessor {
                                     eprintln!("Received TaskCompleted for mismatched task_id: expected {}, received {}", task_id, completed_task_id);
                                 }
                             },
-                            crate::common_types::message_defs::AgentResponse::TaskFailed { task_id: failed_task_id, agent_id: _, error } => {
+                            crate::common_types::message_defs::AgentResponse::TaskFailed { task_id: failed_task_id, agent_id: _, error, is_fatal } => {
                                 if task_id == failed_task_id {
                                     if let Err(e) = TaskResultProcessor::handle_task_failure(
-                                        &task_id, error, false, now, &graph_id, graph_manager_arc.clone() // is_fatal is not part of AgentResponse::TaskFailed
+                                        &task_id, error, is_fatal, now, &graph_id, graph_manager_arc.clone(), &self.cancellation
                                     ).await {
                                         eprintln!("Error in handle_task_failure for task {}: {}", task_id, e);
                                     }
@@ -83,10 +83,16 @@ impl TaskResultProcessor {
                             },
                         }
                     },
+                    MessageContent::UsageReport { task_id: usage_task_id, agent_id: _, usage } => {
+                        let graph_manager_guard = graph_manager_arc.lock().await;
+                        if let Err(e) = graph_manager_guard.record_task_usage(&graph_id, &usage_task_id, &usage).await {
+                            eprintln!("Error recording LLM usage for task {}: {}", usage_task_id, e);
+                        }
+                    },
                     MessageContent::SubTasksGenerated { original_task_id, sub_tasks, sub_task_edges } => {
                         if task_id == original_task_id {
                             if let Err(e) = TaskResultProcessor::handle_subtasks_generated(
-                                &original_task_id, sub_tasks, sub_task_edges, now, &graph_id, graph_manager_arc.clone()
+                                &original_task_id, sub_tasks, sub_task_edges, now, &graph_id, graph_manager_arc.clone(), &self.cancellation
                             ).await {
                                 eprintln!("Error processing subtasks for task {}: {}", original_task_id, e);
                             }
@@ -107,15 +113,17 @@ impl TaskResultProcessor {
 
     /// Handles a successful TaskCompleted message content.
     async fn handle_task_completed_success(
-        agent_manager_arc: Arc<Mutex<crate::agent_manager::AgentManager>>, // Changed to Arc<Mutex<AgentManager>>
-        bus_sender_clone: tokio::sync::broadcast::Sender<crate::common_types::Message>,
+        &self,
         task_id: &str,
         outputs: Vec<Deliverable>,
         now: DateTime<Utc>,
         graph_id_str: &str,
-        tgm_arc: Arc<Mutex<TaskGraphManager>> // Changed to Arc<Mutex<TaskGraphManager>>
     ) -> Result<()> {
         println!("Task {} completed successfully.", task_id);
+        let agent_manager_arc = &self.agent_manager;
+        let bus_sender_clone = &self.bus_sender;
+        let tgm_arc = &self.task_graph_manager;
+        let cancellation = &self.cancellation;
 
         let mut tgm_instance = tgm_arc.lock().await;
         if let Some(mut graphs_map_guard) = tgm_instance.get_task_graph_mut(graph_id_str).await {
@@ -134,6 +142,7 @@ impl TaskResultProcessor {
             eprintln!("TaskGraph with ID {} not found for updating timestamp.", graph_id_str);
             return Err(anyhow::anyhow!("TaskGraph not found for update"));
         }
+        TaskResultProcessor::settle_task(&tgm_instance, cancellation, graph_id_str, task_id, true).await?;
 
         if let Some(delegating_agent_id) = tgm_instance.remove_delegated_task_mapping(task_id.to_string()).await { // Assumes tgm_instance methods are async
             println!("Completed task {} was a delegated subtask. Notifying delegating agent {}.", task_id, delegating_agent_id);
@@ -167,18 +176,21 @@ impl TaskResultProcessor {
         Ok(())
     }
 
-    /// Handles task failure, including retry logic.
-    async fn handle_task_failure( // No &mut self
+    /// Handles task failure, including retry logic. Also used by the scheduler for tasks cancelled
+    /// before being assigned, so every failure is persisted and settles the graph the same way.
+    pub(crate) async fn handle_task_failure( // No &mut self
         task_id: &str,
         error: String,
         is_fatal: bool,
         now: DateTime<Utc>,
         graph_id_str: &str,
-        tgm_arc: Arc<Mutex<TaskGraphManager>> // Changed to Arc<Mutex<TaskGraphManager>>
+        tgm_arc: Arc<Mutex<TaskGraphManager>>, // Changed to Arc<Mutex<TaskGraphManager>>
+        cancellation: &TaskCancellationRegistry,
     ) -> Result<()> {
         println!("Task {} failed with error: {}. Fatal: {}", task_id, error, is_fatal);
 
         let tgm_instance = tgm_arc.lock().await;
+        let failed_for_good;
         if let Some(mut graphs_map_guard) = tgm_instance.get_task_graph_mut(graph_id_str).await {
             if let Some(node) = graphs_map_guard.get_mut(graph_id_str).and_then(|g| g.nodes.get_mut(task_id)) {
                 node.updated_at = now;
@@ -194,6 +206,7 @@ impl TaskResultProcessor {
                     println!("Task {} failed. Retrying (attempt {} of {}).", task_id, node.retry_count, max_retries);
                     node.status = TaskStatus::ReadyToExecute;
                 }
+                failed_for_good = node.status == TaskStatus::Failed;
             } else {
                 eprintln!("Task node {} not found in graph {} after acquiring lock.", task_id, graph_id_str);
                 return Err(anyhow::anyhow!("Task node not found for update"));
@@ -203,6 +216,23 @@ impl TaskResultProcessor {
             eprintln!("TaskGraph with ID {} not found for updating timestamp.", graph_id_str);
             return Err(anyhow::anyhow!("TaskGraph not found for update"));
         }
+        TaskResultProcessor::settle_task(&tgm_instance, cancellation, graph_id_str, task_id, failed_for_good).await
+    }
+
+    // Persists a task whose status changed and drops the cancellation tokens no longer needed;
+    // `finished` is false for a task queued for a retry
+    async fn settle_task(
+        tgm_instance: &TaskGraphManager,
+        cancellation: &TaskCancellationRegistry,
+        graph_id: &str,
+        task_id: &str,
+        finished: bool,
+    ) -> Result<()> {
+        cancellation.release_task(task_id, finished);
+        if let Some(graph_status) = tgm_instance.save_task_state(graph_id, task_id).await? {
+            println!("Task graph {} finished with status {:?}.", graph_id, graph_status);
+            cancellation.release_graph(graph_id);
+        }
         Ok(())
     }
 
@@ -213,7 +243,8 @@ impl TaskResultProcessor {
         sub_task_edges: Vec<SubTaskEdgeDefinition>,
         now: DateTime<Utc>,
         graph_id: &str,
-        tgm_arc: Arc<Mutex<TaskGraphManager>> // Changed to Arc<Mutex<TaskGraphManager>>
+        tgm_arc: Arc<Mutex<TaskGraphManager>>, // Changed to Arc<Mutex<TaskGraphManager>>
+        cancellation: &TaskCancellationRegistry,
     ) -> Result<()> {
         println!("Task {} generated subtasks.", original_task_id);
 
@@ -344,6 +375,7 @@ impl TaskResultProcessor {
             eprintln!("Graph {} not found during final update for task {}.", graph_id, original_task_id);
             return Err(anyhow::anyhow!("Graph not found during finalization of subtask generation"));
         }
+        TaskResultProcessor::settle_task(&tgm_instance, cancellation, graph_id, original_task_id, true).await?;
         
         if decomposition_failed {
             return Err(anyhow::anyhow!("Failed to generate subtasks"));
@@ -2,11 +2,14 @@
 
 use crate::agent_manager::AgentManager;
 use crate::communication_bus::CommunicationBus;
-use crate::common_types::{TaskNode, TaskStatus, TaskInput, Deliverable}; // Import Deliverable
+use crate::common_types::{TaskGraphStatus, TaskNode, TaskStatus, TaskInput, Deliverable}; // Import Deliverable
 use uuid::Uuid; // Import Uuid
 use serde_json::Value; // Import Value
 use crate::core_orchestrator::components::task_graph_manager::TaskGraphManager;
+use crate::core_orchestrator::components::task_result_processor::TaskResultProcessor;
+use crate::mcp_manager::cancellation::TaskCancellationRegistry;
 use anyhow::Result;
+use chrono::Utc;
 use std::sync::Arc; // Use Mutex for interior mutability with Arc
 
 /// Responsible for identifying tasks ready for execution and assigning them to agents.
@@ -15,6 +18,7 @@ pub struct TaskScheduler {
     task_graph_manager: Arc<tokio::sync::Mutex<TaskGraphManager>>,
     agent_manager: Arc<tokio::sync::Mutex<AgentManager>>, // AgentManager also needs shared mutable access
     communication_bus: Arc<CommunicationBus>, // CommunicationBus is already Arc
+    cancellation: Arc<TaskCancellationRegistry>, // Tasks of cancelled graphs are not assigned
 }
 
 impl TaskScheduler {
@@ -23,11 +27,13 @@ impl TaskScheduler {
         task_graph_manager: Arc<tokio::sync::Mutex<TaskGraphManager>>,
         agent_manager: Arc<tokio::sync::Mutex<AgentManager>>,
         communication_bus: Arc<CommunicationBus>,
+        cancellation: Arc<TaskCancellationRegistry>,
     ) -> Self {
         TaskScheduler {
             task_graph_manager,
             agent_manager,
             communication_bus,
+            cancellation,
         }
     }
 
@@ -59,10 +65,18 @@ impl TaskScheduler {
             let collected_tasks: Vec<(String, TaskNode)> = (&mut *active_graphs_map_guard)
                 .iter_mut()
                 .filter_map(|(graph_id, graph)| {
+                    // Unfinished graphs are known to the registry so the cancel_graph command finds them
+                    if !matches!(graph.status, TaskGraphStatus::Completed | TaskGraphStatus::Failed) {
+                        self.cancellation.register_graph(graph_id);
+                    }
+                    // Tasks of a cancelled graph that never became ready are collected too, so they fail
+                    // below as cancelled and the graph can finish
+                    let graph_cancelled = self.cancellation.is_graph_cancelled(graph_id);
                     let ready_tasks: Vec<(String, TaskNode)> = graph
                         .nodes
                         .values()
-                        .filter(|node| node.status == TaskStatus::ReadyToExecute)
+                        .filter(|node| node.status == TaskStatus::ReadyToExecute
+                            || (graph_cancelled && matches!(node.status, TaskStatus::Pending | TaskStatus::PendingDependencies)))
                         .map(|node| (graph_id.clone(), node.clone())) // Clone TaskNode to avoid moving out of the guard
                         .collect();
                     if ready_tasks.is_empty() {
@@ -81,6 +95,14 @@ impl TaskScheduler {
         for (graph_id_str, task_node_clone) in tasks_to_schedule.into_iter() { // Use into_iter() to consume owned values
             log::info!("DEBUG: [TaskScheduler::run_scheduling_cycle] - Attempting to schedule task '{}' from graph '{}'.", task_node_clone.id, graph_id_str);
 
+            // The token is created now so the task's LLM calls can be cancelled once it is assigned
+            let task_token = self.cancellation.register_task(&graph_id_str, &task_node_clone.id.to_string());
+            if task_token.is_cancelled() {
+                log::info!("DEBUG: [TaskScheduler::run_scheduling_cycle] - Task '{}' was cancelled before being scheduled. Marking as failed.", task_node_clone.id);
+                self.mark_task_cancelled(&graph_id_str, &task_node_clone.id.to_string()).await;
+                continue;
+            }
+
             let mut task_inputs: Vec<TaskInput> = Vec::new();
             let mut input_mapping_failed = false;
 
@@ -235,6 +257,16 @@ impl TaskScheduler {
         Ok(())
     }
 
+    /// Fails a task that was cancelled before being assigned, through the same path as a task
+    /// failure reported by an agent, so the failure is persisted and settles the graph.
+    async fn mark_task_cancelled(&self, graph_id: &str, task_id: &str) {
+        if let Err(e) = TaskResultProcessor::handle_task_failure(
+            task_id, "Cancelled".to_string(), true, Utc::now(), graph_id, self.task_graph_manager.clone(), &self.cancellation
+        ).await {
+            log::error!("ERROR: [TaskScheduler::mark_task_cancelled] - Failed to mark task '{}' of graph '{}' as cancelled: {}", task_id, graph_id, e);
+        }
+    }
+
     // The assign_task_node_to_agent method from CoreOrchestrator could potentially
     // be moved here or made accessible to the scheduler.
     // pub fn assign_task_node_to_agent(&self, task_node: TaskNode, agent_id: String) -> Result<(), broadcast::error::SendError<Message>> {
@@ -249,4 +281,43 @@ impl TaskScheduler {
     //     self.communication_bus.publish(message);
     //     Ok(()) // Return Ok(()) for success
     // }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common_types::TaskSpecification;
+    use crate::mcp_manager::MCPManager;
+
+    #[tokio::test]
+    async fn test_cancelled_graph_fails_its_pending_tasks_and_is_released() {
+        let db_connection = Arc::new(tokio::sync::Mutex::new(crate::persistence::establish_in_memory_connection().unwrap()));
+        let task_graph_manager = Arc::new(tokio::sync::Mutex::new(TaskGraphManager::new(db_connection).await.unwrap()));
+        let (graph_id, task_id) = {
+            let manager = task_graph_manager.lock().await;
+            let graph_id = manager.create_task_graph("Graph".to_string(), "Test graph".to_string(), "Goal".to_string()).await.unwrap();
+            let task_spec = TaskSpecification { name: "Waiting".to_string(), description: "Never ready".to_string(), ..Default::default() };
+            let task_id = manager.add_task_node_to_graph(&graph_id, task_spec, None, None).await.unwrap();
+            (graph_id, task_id)
+        };
+        let communication_bus = Arc::new(CommunicationBus::new());
+        let agent_manager = Arc::new(tokio::sync::Mutex::new(AgentManager::new(Arc::new(MCPManager::for_test(Vec::new(), Vec::new())), communication_bus.clone())));
+        let cancellation = Arc::new(TaskCancellationRegistry::new());
+        let mut scheduler = TaskScheduler::new(task_graph_manager.clone(), agent_manager, communication_bus, cancellation.clone());
+
+        // The graph is unknown until a scheduling cycle has seen it
+        assert!(!cancellation.cancel_graph(&graph_id));
+        scheduler.run_scheduling_cycle().await.unwrap();
+        assert!(cancellation.cancel_graph(&graph_id));
+        scheduler.run_scheduling_cycle().await.unwrap();
+
+        let manager = task_graph_manager.lock().await;
+        let graphs = manager.get_active_graphs().await;
+        let graph = &graphs[&graph_id];
+        assert_eq!(graph.nodes[&task_id].status, TaskStatus::Failed);
+        assert_eq!(graph.nodes[&task_id].error_message.as_deref(), Some("Cancelled"));
+        assert_eq!(graph.status, TaskGraphStatus::Failed);
+        // Released once finished: the graph's token is forgotten
+        assert!(!cancellation.is_graph_cancelled(&graph_id));
+    }
 }
\ No newline at end of file
@@ -53,6 +53,9 @@ impl CoreOrchestrator {
         ));
         log::debug!("DEBUG: [CoreOrchestrator::new] - TaskGraphManager initialized.");
 
+        // Shared with MCPManager, whose LLM calls watch the tokens of the tasks they are made for
+        let cancellation = Arc::clone(mcp_manager.cancellation_registry());
+
         // Create communication channels
         let (task_results_sender, task_results_receiver) = mpsc::channel(100);
         let bus_receiver = communication_bus.subscribe();
@@ -74,6 +77,7 @@ impl CoreOrchestrator {
             Arc::clone(&task_graph_manager),
             Arc::clone(&agent_manager),
             bus_sender.clone(),
+            Arc::clone(&cancellation),
         );
         log::debug!("DEBUG: [CoreOrchestrator::new] - TaskResultProcessor initialized.");
 
@@ -81,6 +85,7 @@ impl CoreOrchestrator {
             Arc::clone(&task_graph_manager),
             Arc::clone(&agent_manager),
             Arc::clone(&communication_bus),
+            cancellation,
         );
         log::debug!("DEBUG: [CoreOrchestrator::new] - TaskScheduler initialized.");
 
@@ -122,6 +127,7 @@ impl CoreOrchestrator {
     /// Delegates to the MessageProcessor.
     pub fn start_bus_listener(&mut self) {
         log::info!("DEBUG: [CoreOrchestrator::start_bus_listener] - Starting communication bus listener.");
+        self.communication_bus.start_request_forwarding();
         let _ = self.message_processor.start_listening(); // Added let _ = to ignore Result
         log::debug!("DEBUG: [CoreOrchestrator::start_bus_listener] - Communication bus listener started.");
     }
@@ -256,6 +262,9 @@ impl CoreOrchestrator {
     pub async fn run(self) -> Result<(), anyhow::Error> {
         log::info!("DEBUG: [CoreOrchestrator::run] - CoreOrchestrator starting main loops.");
 
+        // Agents send their responses and reports as bus requests
+        self.communication_bus.start_request_forwarding();
+
         // Create shared components for tasks
         let mut message_processor = self.message_processor;
         let mut task_result_processor = self.task_result_processor;
@@ -314,3 +323,144 @@ impl CoreOrchestrator {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::agents::base_agent_components::send_usage_report;
+    use crate::common_types::{MCPOutput, MCPStatus, MCPUsageMetrics, TaskUsage};
+    use crate::external_api_client::{LLMError, LLMRequest, LLMResponse, LLMTokenCounts};
+    use crate::external_api_client::providers::LLMProvider;
+    use crate::agents::{base_agent::Agent, simple_worker::SimpleWorkerAgent};
+    use crate::common_types::{MCPDefinition, TaskGraphStatus, TaskStatus};
+    use async_trait::async_trait;
+
+    /// Provider that never answers.
+    #[derive(Debug)]
+    struct HangingProvider;
+
+    #[async_trait]
+    impl LLMProvider for HangingProvider {
+        fn name(&self) -> String {
+            "Hanging".to_string()
+        }
+
+        async fn generate(&self, _request: LLMRequest) -> Result<LLMResponse, LLMError> {
+            std::future::pending().await
+        }
+    }
+
+    /// Orchestrator listening to the bus and processing task results, as in the app, without scheduling.
+    async fn listening_orchestrator(communication_bus: Arc<CommunicationBus>, mcp_manager: Arc<MCPManager>) -> Arc<Mutex<TaskGraphManager>> {
+        let db_connection = Arc::new(Mutex::new(crate::persistence::establish_in_memory_connection().unwrap()));
+        let mut orchestrator = CoreOrchestrator::new(communication_bus, mcp_manager, db_connection).await.unwrap();
+        orchestrator.start_bus_listener();
+        let task_graph_manager = Arc::clone(&orchestrator.task_graph_manager);
+        let mut task_result_processor = orchestrator.task_result_processor;
+        tokio::spawn(async move { task_result_processor.start_processing().await });
+        task_graph_manager
+    }
+
+    async fn create_task(task_graph_manager: &Mutex<TaskGraphManager>) -> (String, String) {
+        let manager = task_graph_manager.lock().await;
+        let graph_id = manager.create_task_graph("Graph".to_string(), "Test graph".to_string(), "Goal".to_string()).await.unwrap();
+        let task_id = manager.add_task_node_to_graph(&graph_id, TaskSpecification::default(), None, None).await.unwrap();
+        (graph_id, task_id)
+    }
+
+    #[tokio::test]
+    async fn test_usage_reported_by_an_agent_is_recorded() {
+        let communication_bus = Arc::new(CommunicationBus::new());
+        let task_graph_manager = listening_orchestrator(Arc::clone(&communication_bus), Arc::new(MCPManager::for_test(Vec::new(), Vec::new()))).await;
+        let (graph_id, task_id) = create_task(&task_graph_manager).await;
+
+        let usage = MCPUsageMetrics {
+            llm_token_counts: Some(LLMTokenCounts { prompt_tokens: 100, completion_tokens: 20, total_tokens: 120 }),
+            processing_time_ms: None,
+            llm_call_duration_ms: None,
+            llm_model: Some("scripted-model".to_string()),
+            estimated_cost_usd: Some(0.5),
+        };
+        let mcp_output = MCPOutput {
+            request_id: "request-1".to_string(),
+            mcp_id: "draft_v1".to_string(),
+            status: MCPStatus::Success,
+            llm_request_details: None,
+            llm_response_details: None,
+            processed_content: None,
+            raw_completion: None,
+            attempts: Vec::new(),
+            error_message: None,
+            usage_metrics: Some(usage),
+        };
+        send_usage_report(&communication_bus.get_bus_request_sender(), "writer-1", &task_id, &mcp_output).await;
+
+        let expected = TaskUsage { llm_calls: 1, prompt_tokens: 100, completion_tokens: 20, total_tokens: 120, estimated_cost_usd: 0.5 };
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                {
+                    let manager = task_graph_manager.lock().await;
+                    let graphs = manager.get_active_graphs().await;
+                    if graphs[&graph_id].usage == expected {
+                        assert_eq!(graphs[&graph_id].nodes[&task_id].usage, expected);
+                        break;
+                    }
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cancelling_a_running_task_fails_it() {
+        let communication_bus = Arc::new(CommunicationBus::new());
+        let definition: MCPDefinition = serde_json::from_value(serde_json::json!({
+            "id": "hang_v1",
+            "description": "Never answers",
+            "template": "Wait",
+            "logic_module_path": null,
+            "default_llm_provider": "Hanging",
+            "default_llm_model": "hanging-model",
+            "default_llm_parameters": null
+        })).unwrap();
+        let mcp_manager = Arc::new(MCPManager::for_test([definition], vec![Box::new(HangingProvider)]));
+        let task_graph_manager = listening_orchestrator(Arc::clone(&communication_bus), Arc::clone(&mcp_manager)).await;
+        let (graph_id, task_id) = create_task(&task_graph_manager).await;
+
+        // Dispatched as the scheduler does
+        let task = {
+            let manager = task_graph_manager.lock().await;
+            let mut graphs = manager.get_active_graphs_mut().await;
+            let node = graphs.get_mut(&graph_id).unwrap().nodes.get_mut(&task_id).unwrap();
+            node.status = TaskStatus::InProgress;
+            node.mcp_id = Some("hang_v1".to_string());
+            node.clone()
+        };
+        mcp_manager.cancellation_registry().register_task(&graph_id, &task_id);
+        let worker = SimpleWorkerAgent::new("worker-1".to_string(), AgentConfig::default(), communication_bus.get_bus_request_sender(), Arc::clone(&mcp_manager)).await.unwrap();
+        let running = tokio::spawn(async move { worker.process_task(task).await });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!running.is_finished());
+        assert!(mcp_manager.cancellation_registry().cancel_task(&task_id));
+        assert!(running.await.unwrap().is_err());
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                {
+                    let manager = task_graph_manager.lock().await;
+                    let graphs = manager.get_active_graphs().await;
+                    let node = &graphs[&graph_id].nodes[&task_id];
+                    if node.status == TaskStatus::Failed {
+                        // Cancelled tasks are not retried
+                        assert_eq!(node.retry_count, 0);
+                        assert_eq!(graphs[&graph_id].status, TaskGraphStatus::Failed);
+                        break;
+                    }
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }).await.unwrap();
+    }
+}
@@ -0,0 +1,175 @@
+use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+
+use super::{LLMRequest, LLMResponse};
+
+/// Limits of the persistent LLM response cache.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ResponseCacheConfig {
+    // Entries older than this are ignored and eventually evicted; None keeps them until evicted for space
+    #[serde(default = "default_ttl_secs")]
+    pub ttl_secs: Option<u64>,
+    // Least recently used entries are evicted beyond this count
+    #[serde(default = "default_max_entries")]
+    pub max_entries: u64,
+    // Larger responses are not cached
+    #[serde(default = "default_max_response_bytes")]
+    pub max_response_bytes: usize,
+}
+
+fn default_ttl_secs() -> Option<u64> {
+    Some(7 * 24 * 3600)
+}
+
+fn default_max_entries() -> u64 {
+    10_000
+}
+
+fn default_max_response_bytes() -> usize {
+    256 * 1024
+}
+
+impl Default for ResponseCacheConfig {
+    fn default() -> Self {
+        ResponseCacheConfig {
+            ttl_secs: default_ttl_secs(),
+            max_entries: default_max_entries(),
+            max_response_bytes: default_max_response_bytes(),
+        }
+    }
+}
+
+/// Fields of a call that determine its response. Serialized through `serde_json::Value`,
+/// whose maps are sorted, so equal requests always hash to the same key.
+#[derive(Serialize)]
+struct CacheKeyFields<'a> {
+    provider: &'a str,
+    request: &'a LLMRequest,
+}
+
+/// LLM responses stored in the SQLite database, keyed by a hash of provider, model, messages and sampling parameters.
+#[derive(Debug)]
+pub struct ResponseCache {
+    conn: Mutex<Connection>,
+    config: ResponseCacheConfig,
+}
+
+impl ResponseCache {
+    pub fn new(conn: Connection, config: ResponseCacheConfig) -> Result<Self> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS llm_response_cache (
+                cache_key TEXT PRIMARY KEY,
+                provider TEXT NOT NULL,
+                model TEXT NOT NULL,
+                response TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                last_used_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
+        Ok(ResponseCache { conn: Mutex::new(conn), config })
+    }
+
+    /// Canonical key of `request` sent to `provider`. The request must carry the resolved model.
+    pub fn cache_key(provider: &str, request: &LLMRequest) -> Result<String> {
+        let canonical = serde_json::to_value(CacheKeyFields { provider, request })?;
+        Ok(format!("{:x}", Sha256::digest(canonical.to_string().as_bytes())))
+    }
+
+    /// Cached response for `cache_key`, unless it is missing or expired.
+    pub async fn get(&self, cache_key: &str) -> Result<Option<LLMResponse>> {
+        let now = chrono::Utc::now().timestamp();
+        let conn = self.conn.lock().await;
+
+        let cached: Option<(String, i64)> = conn.query_row(
+            "SELECT response, created_at FROM llm_response_cache WHERE cache_key = ?1",
+            params![cache_key],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).optional()?;
+
+        let Some((response_json, created_at)) = cached else {
+            return Ok(None);
+        };
+        if self.is_expired(created_at, now) {
+            conn.execute("DELETE FROM llm_response_cache WHERE cache_key = ?1", params![cache_key])?;
+            return Ok(None);
+        }
+
+        conn.execute("UPDATE llm_response_cache SET last_used_at = ?1 WHERE cache_key = ?2", params![now, cache_key])?;
+        Ok(Some(serde_json::from_str(&response_json)?))
+    }
+
+    /// Stores a response, then evicts expired entries and the least recently used ones over `max_entries`.
+    pub async fn put(&self, cache_key: &str, provider: &str, model: &str, response: &LLMResponse) -> Result<()> {
+        let response_json = serde_json::to_string(response)?;
+        if response_json.len() > self.config.max_response_bytes {
+            return Ok(());
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "INSERT OR REPLACE INTO llm_response_cache (cache_key, provider, model, response, created_at, last_used_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+            params![cache_key, provider, model, response_json, now],
+        )?;
+
+        if let Some(ttl_secs) = self.config.ttl_secs {
+            conn.execute("DELETE FROM llm_response_cache WHERE created_at <= ?1", params![now - ttl_secs as i64])?;
+        }
+        conn.execute(
+            "DELETE FROM llm_response_cache WHERE cache_key NOT IN (
+                SELECT cache_key FROM llm_response_cache ORDER BY last_used_at DESC, rowid DESC LIMIT ?1
+            )",
+            params![self.config.max_entries as i64],
+        )?;
+        Ok(())
+    }
+
+    fn is_expired(&self, created_at: i64, now: i64) -> bool {
+        self.config.ttl_secs.is_some_and(|ttl_secs| now - created_at >= ttl_secs as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::external_api_client::LLMParameters;
+
+    fn request(temperature: Option<f32>) -> LLMRequest {
+        LLMRequest {
+            parameters: LLMParameters { temperature, ..Default::default() },
+            ..LLMRequest::from_prompt("model-a", "Summarize this", None)
+        }
+    }
+
+    fn response(content: &str) -> LLMResponse {
+        LLMResponse { content: content.to_string(), usage: None, served_by: None, cached: false, tool_calls: Vec::new(), finish_reason: None }
+    }
+
+    #[test]
+    fn test_cache_key_covers_provider_and_parameters() {
+        let key = ResponseCache::cache_key("OpenRouter", &request(None)).unwrap();
+        assert_eq!(key, ResponseCache::cache_key("OpenRouter", &request(None)).unwrap());
+        assert_ne!(key, ResponseCache::cache_key("Requesty", &request(None)).unwrap());
+        assert_ne!(key, ResponseCache::cache_key("OpenRouter", &request(Some(0.2))).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_put_get_and_evict_over_max_entries() {
+        let config = ResponseCacheConfig { ttl_secs: None, max_entries: 2, ..Default::default() };
+        let cache = ResponseCache::new(Connection::open_in_memory().unwrap(), config).unwrap();
+
+        cache.put("a", "P", "m", &response("first")).await.unwrap();
+        assert_eq!(cache.get("a").await.unwrap().unwrap().content, "first");
+        assert!(cache.get("missing").await.unwrap().is_none());
+
+        cache.put("b", "P", "m", &response("second")).await.unwrap();
+        cache.put("c", "P", "m", &response("third")).await.unwrap();
+        assert!(cache.get("c").await.unwrap().is_some());
+        assert_eq!(cache.conn.lock().await.query_row("SELECT COUNT(*) FROM llm_response_cache", [], |row| row.get::<_, i64>(0)).unwrap(), 2);
+    }
+}
@@ -0,0 +1,239 @@
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+use super::cache::ResponseCache;
+use super::providers::error::LLMError;
+use super::{LLMRequest, LLMResponse, LLMRole};
+
+/// Environment variable naming a cassette file to record provider traffic to.
+pub const CASSETTE_RECORD_ENV: &str = "HIVE_CASSETTE_RECORD";
+/// Environment variable naming a recorded cassette file to replay instead of calling providers.
+pub const CASSETTE_REPLAY_ENV: &str = "HIVE_CASSETTE_REPLAY";
+/// Environment variable that, set to `1`, lets a recording replace an existing cassette.
+pub const CASSETTE_OVERWRITE_ENV: &str = "HIVE_CASSETTE_OVERWRITE";
+
+/// What a provider call produced, as stored in a cassette.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CassetteOutcome {
+    Response { response: LLMResponse },
+    // Replayed as recorded, so fallback and failure handling are the same as in the recorded run
+    Error { error: LLMError },
+}
+
+/// One provider call: the request as sent to the provider and what came back.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CassetteInteraction {
+    pub provider: String,
+    pub request: LLMRequest,
+    pub outcome: CassetteOutcome,
+    // A streamed call that failed after emitting tokens is not retried on another route
+    #[serde(default)]
+    pub emitted_chunks: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    Record,
+    Replay,
+}
+
+#[derive(Debug)]
+struct ReplayEntry {
+    key: String,
+    interaction: CassetteInteraction,
+    served: bool,
+}
+
+/// Provider traffic recorded to, or replayed from, a JSON Lines file with one interaction per
+/// line. Replaying a cassette recorded during a real run reproduces that run without network
+/// access or API keys.
+#[derive(Debug)]
+pub struct Cassette {
+    mode: CassetteMode,
+    path: PathBuf,
+    // Open for appending while recording
+    file: Mutex<Option<tokio::fs::File>>,
+    replay_entries: Mutex<Vec<ReplayEntry>>,
+}
+
+impl Cassette {
+    /// Starts an empty cassette; each call is appended to it as it completes. A file that
+    /// already holds a recording is only truncated when `overwrite` is set.
+    pub fn record(path: impl AsRef<Path>, overwrite: bool) -> Result<Self> {
+        let path = path.as_ref();
+        if !overwrite && fs::metadata(path).is_ok_and(|metadata| metadata.len() > 0) {
+            return Err(anyhow!("Cassette {:?} already holds a recording; set {}=1 to replace it", path, CASSETTE_OVERWRITE_ENV));
+        }
+        let file = fs::File::create(path)
+            .with_context(|| format!("Failed to create cassette {:?}", path))?;
+        Ok(Cassette {
+            mode: CassetteMode::Record,
+            path: path.to_path_buf(),
+            file: Mutex::new(Some(tokio::fs::File::from_std(file))),
+            replay_entries: Mutex::new(Vec::new()),
+        })
+    }
+
+    pub fn replay(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file_content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read cassette {:?}", path))?;
+
+        // Keys are computed from the stored requests, so a hand-edited request still matches
+        let replay_entries = file_content.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                let interaction: CassetteInteraction = serde_json::from_str(line)
+                    .with_context(|| format!("Invalid cassette {:?} at line {}", path, index + 1))?;
+                Ok(ReplayEntry {
+                    key: Self::request_key(&interaction.provider, &interaction.request)?,
+                    interaction,
+                    served: false,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Cassette {
+            mode: CassetteMode::Replay,
+            path: path.to_path_buf(),
+            file: Mutex::new(None),
+            replay_entries: Mutex::new(replay_entries),
+        })
+    }
+
+    /// Cassette named by `HIVE_CASSETTE_RECORD` or `HIVE_CASSETTE_REPLAY`, if either is set.
+    /// `HIVE_CASSETTE_OVERWRITE=1` allows recording over an existing cassette.
+    pub fn from_env() -> Result<Option<Self>> {
+        match (env::var(CASSETTE_RECORD_ENV), env::var(CASSETTE_REPLAY_ENV)) {
+            (Ok(_), Ok(_)) => Err(anyhow!("{} and {} cannot both be set", CASSETTE_RECORD_ENV, CASSETTE_REPLAY_ENV)),
+            (Ok(path), Err(_)) => {
+                let overwrite = env::var(CASSETTE_OVERWRITE_ENV).is_ok_and(|value| value == "1");
+                Ok(Some(Self::record(path, overwrite)?))
+            }
+            (Err(_), Ok(path)) => Ok(Some(Self::replay(path)?)),
+            (Err(_), Err(_)) => Ok(None),
+        }
+    }
+
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Tool results are left out of the key: tools such as `current_datetime` answer differently
+    // on every run, and the tool calls the model asked for already tell the turns apart
+    fn request_key(provider: &str, request: &LLMRequest) -> Result<String> {
+        let mut request = request.clone();
+        for message in request.messages.iter_mut().filter(|message| message.role == LLMRole::Tool) {
+            message.content.clear();
+        }
+        ResponseCache::cache_key(provider, &request)
+    }
+
+    /// Appends a call to the cassette file.
+    pub async fn record_call(&self, provider: &str, request: &LLMRequest, result: &Result<LLMResponse, LLMError>, emitted_chunks: bool) -> Result<()> {
+        let outcome = match result {
+            Ok(response) => CassetteOutcome::Response { response: response.clone() },
+            Err(e) => CassetteOutcome::Error { error: e.clone() },
+        };
+        let interaction = CassetteInteraction { provider: provider.to_string(), request: request.clone(), outcome, emitted_chunks };
+        let mut line = serde_json::to_string(&interaction)?;
+        line.push('\n');
+
+        // Held while writing so lines of concurrent calls do not interleave
+        let mut file = self.file.lock().await;
+        let file = file.as_mut().ok_or_else(|| anyhow!("Cassette {:?} is not recording", self.path))?;
+        file.write_all(line.as_bytes()).await
+            .and(file.flush().await)
+            .with_context(|| format!("Failed to write cassette {:?}", self.path))
+    }
+
+    /// Recorded outcome of a call and whether it had emitted chunks. Identical requests are served
+    /// in recording order; once all of them were served, the last one is served again.
+    /// A request missing from the cassette fails as an invalid request naming its key.
+    pub async fn replay_call(&self, provider: &str, request: &LLMRequest) -> (Result<LLMResponse, LLMError>, bool) {
+        let invalid_request = |message: String| LLMError::InvalidRequest { provider: provider.to_string(), status: None, message };
+        let key = match Self::request_key(provider, request) {
+            Ok(key) => key,
+            Err(e) => return (Err(invalid_request(e.to_string())), false),
+        };
+
+        let mut replay_entries = self.replay_entries.lock().await;
+        let position = replay_entries.iter().position(|entry| entry.key == key && !entry.served)
+            .or_else(|| replay_entries.iter().rposition(|entry| entry.key == key));
+        let Some(position) = position else {
+            let message = format!("No recorded interaction in cassette for provider '{}', model '{}' (request key {})", provider, request.model, key);
+            return (Err(invalid_request(message)), false);
+        };
+
+        let entry = &mut replay_entries[position];
+        entry.served = true;
+        let result = match &entry.interaction.outcome {
+            CassetteOutcome::Response { response } => Ok(response.clone()),
+            CassetteOutcome::Error { error } => Err(error.clone()),
+        };
+        (result, entry.interaction.emitted_chunks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::external_api_client::providers::scripted_provider::ScriptedProvider;
+    use crate::external_api_client::{ExternalApiClient, LLMCallOptions, LLMMessage, LLMToolCall, RoutingPolicy};
+
+    #[tokio::test]
+    async fn test_recorded_run_replays_without_provider() {
+        let path = env::temp_dir().join(format!("hive_cassette_{}.json", uuid::Uuid::new_v4()));
+        let policy = RoutingPolicy::single("Scripted", None);
+        let request = |prompt: &str| LLMRequest::from_prompt("scripted-model", prompt, None);
+        // Follow-up turn of a tool loop, whose tool result changes from run to run
+        let tool_turn = |now: &str| {
+            let mut request = request("What time is it?");
+            let call = LLMToolCall { id: "call_1".to_string(), name: "current_datetime".to_string(), arguments: "{}".to_string() };
+            request.messages.push(LLMMessage { tool_calls: vec![call], ..LLMMessage::assistant("") });
+            request.messages.push(LLMMessage::tool_result("call_1", now));
+            request
+        };
+
+        let scripted = ScriptedProvider::from_json_str(r#"{ "responses": [
+            { "prompt_regex": "^Hello$", "response": "recorded answer" },
+            { "prompt_regex": "^What time", "response": "It is noon" }
+        ] }"#).unwrap();
+        let recording = ExternalApiClient::with_providers(vec![Box::new(scripted)]).with_cassette(Cassette::record(&path, false).unwrap());
+        recording.call_llm(&policy, request("Hello"), &LLMCallOptions::default()).await.unwrap();
+        recording.call_llm(&policy, tool_turn("2026-01-01T12:00:00Z"), &LLMCallOptions::default()).await.unwrap();
+        assert!(recording.call_llm(&policy, request("Unscripted"), &LLMCallOptions::default()).await.is_err());
+
+        // The replaying provider has no fixtures, so every answer comes from the cassette
+        let empty = ScriptedProvider::from_entries(Vec::new()).unwrap();
+        let replaying = ExternalApiClient::with_providers(vec![Box::new(empty)]).with_cassette(Cassette::replay(&path).unwrap());
+        let response = replaying.call_llm(&policy, request("Hello"), &LLMCallOptions::default()).await.unwrap();
+        assert_eq!(response.content, "recorded answer");
+        let response = replaying.call_llm(&policy, tool_turn("2026-10-17T08:30:00Z"), &LLMCallOptions::default()).await.unwrap();
+        assert_eq!(response.content, "It is noon");
+        let recorded_error = replaying.call_llm(&policy, request("Unscripted"), &LLMCallOptions::default()).await.unwrap_err();
+        assert!(recorded_error.to_string().contains("no response for model"));
+        let unmatched = replaying.call_llm(&policy, request("Never sent"), &LLMCallOptions::default()).await.unwrap_err();
+        assert!(matches!(unmatched, LLMError::InvalidRequest { .. }));
+        assert!(unmatched.to_string().contains("No recorded interaction"));
+
+        // A recording is only replaced when asked to
+        assert!(Cassette::record(&path, false).is_err());
+        assert!(fs::metadata(&path).unwrap().len() > 0);
+        Cassette::record(&path, true).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+use super::providers::LLMModelInfo;
+
+/// How long a provider's model list is reused before it is fetched again.
+pub const MODEL_CATALOG_TTL: Duration = Duration::from_secs(3600);
+
+#[derive(Debug)]
+struct CatalogEntry {
+    fetched_at: Instant,
+    models: Vec<LLMModelInfo>,
+}
+
+/// In-memory cache of the model lists returned by `LLMProvider::list_models`, per provider.
+#[derive(Debug)]
+pub struct ModelCatalog {
+    ttl: Duration,
+    entries: RwLock<HashMap<String, CatalogEntry>>,
+}
+
+impl Default for ModelCatalog {
+    fn default() -> Self {
+        ModelCatalog::new(MODEL_CATALOG_TTL)
+    }
+}
+
+impl ModelCatalog {
+    pub fn new(ttl: Duration) -> Self {
+        ModelCatalog { ttl, entries: RwLock::new(HashMap::new()) }
+    }
+
+    /// Cached models of `provider_name`, if fetched less than `ttl` ago.
+    pub async fn get(&self, provider_name: &str) -> Option<Vec<LLMModelInfo>> {
+        let entries = self.entries.read().await;
+        entries.get(provider_name)
+            .filter(|entry| entry.fetched_at.elapsed() < self.ttl)
+            .map(|entry| entry.models.clone())
+    }
+
+    pub async fn insert(&self, provider_name: &str, models: Vec<LLMModelInfo>) {
+        self.entries.write().await.insert(provider_name.to_string(), CatalogEntry { fetched_at: Instant::now(), models });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_entries_expire_after_ttl() {
+        let model = LLMModelInfo { id: "m".to_string(), name: None, context_length: None, pricing: None, modality: None };
+
+        let catalog = ModelCatalog::default();
+        catalog.insert("OpenRouter", vec![model.clone()]).await;
+        assert_eq!(catalog.get("OpenRouter").await, Some(vec![model.clone()]));
+        assert_eq!(catalog.get("Requesty").await, None);
+
+        let expired = ModelCatalog::new(Duration::ZERO);
+        expired.insert("OpenRouter", vec![model]).await;
+        assert_eq!(expired.get("OpenRouter").await, None);
+    }
+}
@@ -0,0 +1,90 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use super::cache::ResponseCacheConfig;
+use super::providers::retry::RetryConfig;
+use super::rate_limit::RateLimitConfig;
+use super::routing::LLMRoute;
+
+/// Default location of the provider configuration, relative to the working directory.
+pub const DEFAULT_PROVIDERS_CONFIG_PATH: &str = "config/llm_providers.json";
+
+/// Contents of the provider configuration file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExternalApiConfig {
+    #[serde(default)]
+    pub openai_compatible: Vec<OpenAICompatibleProviderConfig>,
+    // Keyed by provider name, e.g. "OpenRouter"
+    #[serde(default)]
+    pub rate_limits: HashMap<String, RateLimitConfig>,
+    // Transient-error retries of the OpenRouter and Requesty providers, keyed by provider name
+    #[serde(default)]
+    pub retry: HashMap<String, RetryConfig>,
+    // Responses are cached in the SQLite database only when this is set
+    #[serde(default)]
+    pub response_cache: Option<ResponseCacheConfig>,
+    // Provider and model used by `ExternalApiClient::embed`; the local Hashing embedder when unset
+    #[serde(default)]
+    pub embeddings: Option<LLMRoute>,
+    // Deadline of each routed LLM call, in seconds; `DEFAULT_REQUEST_TIMEOUT` when unset
+    #[serde(default)]
+    pub request_timeout_secs: Option<u64>,
+}
+
+/// One OpenAI-compatible endpoint (llama.cpp server, Ollama, vLLM, ...) registered under its own name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenAICompatibleProviderConfig {
+    pub name: String,
+    // e.g. "http://localhost:11434/v1"; "/chat/completions" is appended
+    pub base_url: String,
+    #[serde(default)]
+    pub auth: Option<ProviderAuthConfig>,
+    // Model used when a request does not name one
+    #[serde(default)]
+    pub default_model: Option<String>,
+}
+
+/// Header carrying the API key. The key is read from `api_key_env` so it never sits in the config file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderAuthConfig {
+    #[serde(default = "default_auth_header")]
+    pub header: String,
+    // Prepended to the key, e.g. "Bearer "
+    #[serde(default = "default_auth_prefix")]
+    pub value_prefix: String,
+    pub api_key_env: String,
+}
+
+fn default_auth_header() -> String {
+    "Authorization".to_string()
+}
+
+fn default_auth_prefix() -> String {
+    "Bearer ".to_string()
+}
+
+impl ExternalApiConfig {
+    pub fn retry_for(&self, provider_name: &str) -> RetryConfig {
+        self.retry.get(provider_name).cloned().unwrap_or_default()
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file_content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read provider configuration {:?}", path))?;
+        serde_json::from_str(&file_content)
+            .with_context(|| format!("Invalid provider configuration {:?}", path))
+    }
+
+    /// Loads the configuration file if it exists; a missing file means no extra providers.
+    pub fn load_or_default(path: impl AsRef<Path>) -> Result<Self> {
+        if path.as_ref().exists() {
+            Self::from_file(path)
+        } else {
+            Ok(Self::default())
+        }
+    }
+}
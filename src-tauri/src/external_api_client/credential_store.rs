@@ -0,0 +1,275 @@
+use anyhow::{anyhow, Context, Result};
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// File of the credential store, in the app data directory.
+pub const CREDENTIALS_FILE_NAME: &str = "credentials.enc";
+/// Environment variable holding the passphrase that unlocks the credential store at startup.
+pub const CREDENTIALS_PASSPHRASE_ENV: &str = "HIVE_CREDENTIALS_PASSPHRASE";
+/// Environment variable naming a key file that unlocks the credential store at startup.
+pub const CREDENTIALS_KEY_FILE_ENV: &str = "HIVE_CREDENTIALS_KEY_FILE";
+
+const FORMAT_VERSION: u32 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+// A key file goes through Argon2 like a passphrase; it must be long enough to carry at least
+// as much entropy as the derived key
+const MIN_KEY_FILE_LEN: usize = 32;
+
+/// Secret the store's encryption key is derived from.
+#[derive(Debug, Clone)]
+pub enum CredentialSecret {
+    Passphrase(String),
+    // Any file of at least 32 random bytes, e.g. from `head -c 32 /dev/urandom`
+    KeyFile(PathBuf),
+}
+
+impl CredentialSecret {
+    /// Secret named by `HIVE_CREDENTIALS_KEY_FILE` or `HIVE_CREDENTIALS_PASSPHRASE`, if either is set.
+    pub fn from_env() -> Option<Self> {
+        if let Ok(path) = env::var(CREDENTIALS_KEY_FILE_ENV) {
+            return Some(CredentialSecret::KeyFile(PathBuf::from(path)));
+        }
+        env::var(CREDENTIALS_PASSPHRASE_ENV).ok()
+            .filter(|passphrase| !passphrase.is_empty())
+            .map(CredentialSecret::Passphrase)
+    }
+
+    fn bytes(&self) -> Result<Vec<u8>> {
+        match self {
+            CredentialSecret::Passphrase(passphrase) if passphrase.is_empty() => Err(anyhow!("The passphrase is empty")),
+            CredentialSecret::Passphrase(passphrase) => Ok(passphrase.as_bytes().to_vec()),
+            CredentialSecret::KeyFile(path) => {
+                let bytes = fs::read(path).with_context(|| format!("Failed to read key file {:?}", path))?;
+                if bytes.len() < MIN_KEY_FILE_LEN {
+                    return Err(anyhow!("Key file {:?} holds {} bytes, at least {} are needed", path, bytes.len(), MIN_KEY_FILE_LEN));
+                }
+                Ok(bytes)
+            }
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CredentialStoreError {
+    #[error("The credential store is locked: unlock it with its passphrase or key file")]
+    Locked,
+    #[error("Wrong passphrase or key file for the credential store, or the file was modified")]
+    WrongSecret,
+    #[error("The credential store does not exist yet: confirm its creation with the chosen passphrase or key file")]
+    NotCreated,
+}
+
+/// Credential file as stored on disk. Only the salt and nonce are in clear.
+#[derive(Debug, Serialize, Deserialize)]
+struct EncryptedCredentials {
+    version: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+// Key derived from the secret, kept while the store is unlocked so saving does not re-derive it
+struct UnlockedCredentials {
+    key: [u8; 32],
+    salt: [u8; SALT_LEN],
+    api_keys: BTreeMap<String, String>,
+}
+
+/// Provider API keys encrypted with XChaCha20-Poly1305 under a key derived by Argon2id
+/// from a passphrase or key file. The store is locked until `unlock` is given the secret;
+/// the file is rewritten with a fresh nonce on every change.
+pub struct CredentialStore {
+    path: PathBuf,
+    unlocked: Mutex<Option<UnlockedCredentials>>,
+}
+
+impl std::fmt::Debug for CredentialStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Never print the keys
+        f.debug_struct("CredentialStore")
+            .field("path", &self.path)
+            .field("unlocked", &self.is_unlocked())
+            .finish()
+    }
+}
+
+impl CredentialStore {
+    /// Store kept in `path`; nothing is read until it is unlocked.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        CredentialStore { path: path.as_ref().to_path_buf(), unlocked: Mutex::new(None) }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether a credential file was already created.
+    pub fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    pub fn is_unlocked(&self) -> bool {
+        self.unlocked.lock().map(|unlocked| unlocked.is_some()).unwrap_or(false)
+    }
+
+    /// Decrypts the store with `secret` and returns its API keys by provider name. Without a
+    /// credential file, an empty store protected by `secret` is created when `create` is set,
+    /// so a mistyped secret cannot silently start a new store. Runs Argon2, which takes a
+    /// noticeable time: call it from a blocking task in async code.
+    pub fn unlock(&self, secret: &CredentialSecret, create: bool) -> Result<BTreeMap<String, String>> {
+        if !create && !self.exists() {
+            return Err(CredentialStoreError::NotCreated.into());
+        }
+        let secret_bytes = secret.bytes()?;
+        let unlocked = if self.exists() {
+            let file_content = fs::read_to_string(&self.path)
+                .with_context(|| format!("Failed to read credential store {:?}", self.path))?;
+            let encrypted: EncryptedCredentials = serde_json::from_str(&file_content)
+                .with_context(|| format!("Invalid credential store {:?}", self.path))?;
+            if encrypted.version != FORMAT_VERSION {
+                return Err(anyhow!("Unsupported credential store version {} in {:?}", encrypted.version, self.path));
+            }
+            let salt: [u8; SALT_LEN] = decode_fixed(&encrypted.salt, "salt")?;
+            let nonce: [u8; NONCE_LEN] = decode_fixed(&encrypted.nonce, "nonce")?;
+            let key = derive_key(&secret_bytes, &salt)?;
+            let plaintext = XChaCha20Poly1305::new(&key.into())
+                .decrypt(XNonce::from_slice(&nonce), BASE64.decode(&encrypted.ciphertext)?.as_slice())
+                .map_err(|_| CredentialStoreError::WrongSecret)?;
+            let api_keys = serde_json::from_slice(&plaintext).context("Invalid content in credential store")?;
+            UnlockedCredentials { key, salt, api_keys }
+        } else {
+            println!("Creating credential store at {:?}", self.path);
+            let mut salt = [0u8; SALT_LEN];
+            rand::thread_rng().fill_bytes(&mut salt);
+            let unlocked = UnlockedCredentials { key: derive_key(&secret_bytes, &salt)?, salt, api_keys: BTreeMap::new() };
+            self.save(&unlocked)?;
+            unlocked
+        };
+
+        let api_keys = unlocked.api_keys.clone();
+        *self.lock_state() = Some(unlocked);
+        Ok(api_keys)
+    }
+
+    /// Forgets the derived key and the decrypted API keys.
+    pub fn lock(&self) {
+        *self.lock_state() = None;
+    }
+
+    pub fn get(&self, provider: &str) -> Result<Option<String>> {
+        let state = self.lock_state();
+        let unlocked = state.as_ref().ok_or(CredentialStoreError::Locked)?;
+        Ok(unlocked.api_keys.get(provider).cloned())
+    }
+
+    /// Stores the API key of `provider`, replacing any previous one.
+    pub fn set(&self, provider: &str, api_key: &str) -> Result<()> {
+        let mut state = self.lock_state();
+        let unlocked = state.as_mut().ok_or(CredentialStoreError::Locked)?;
+        let previous = unlocked.api_keys.insert(provider.to_string(), api_key.to_string());
+        if let Err(e) = self.save(unlocked) {
+            // Keep memory and file in agreement
+            match previous {
+                Some(previous) => unlocked.api_keys.insert(provider.to_string(), previous),
+                None => unlocked.api_keys.remove(provider),
+            };
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Removes the API key of `provider`; returns whether there was one.
+    pub fn remove(&self, provider: &str) -> Result<bool> {
+        let mut state = self.lock_state();
+        let unlocked = state.as_mut().ok_or(CredentialStoreError::Locked)?;
+        let Some(previous) = unlocked.api_keys.remove(provider) else {
+            return Ok(false);
+        };
+        if let Err(e) = self.save(unlocked) {
+            unlocked.api_keys.insert(provider.to_string(), previous);
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    fn lock_state(&self) -> std::sync::MutexGuard<'_, Option<UnlockedCredentials>> {
+        self.unlocked.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    // Written to a temporary file first, so an interrupted write cannot lose the existing keys
+    fn save(&self, unlocked: &UnlockedCredentials) -> Result<()> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let plaintext = serde_json::to_vec(&unlocked.api_keys)?;
+        let ciphertext = XChaCha20Poly1305::new(&unlocked.key.into())
+            .encrypt(XNonce::from_slice(&nonce), plaintext.as_slice())
+            .map_err(|_| anyhow!("Failed to encrypt the credential store"))?;
+        let encrypted = EncryptedCredentials {
+            version: FORMAT_VERSION,
+            salt: BASE64.encode(unlocked.salt),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        };
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("Failed to create directory {:?}", parent))?;
+        }
+        let temporary_path = self.path.with_extension("enc.tmp");
+        fs::write(&temporary_path, serde_json::to_string_pretty(&encrypted)?)
+            .with_context(|| format!("Failed to write credential store {:?}", temporary_path))?;
+        fs::rename(&temporary_path, &self.path)
+            .with_context(|| format!("Failed to replace credential store {:?}", self.path))
+    }
+}
+
+fn derive_key(secret: &[u8], salt: &[u8]) -> Result<[u8; 32]> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(secret, salt, &mut key)
+        .map_err(|e| anyhow!("Failed to derive the credential store key: {}", e))?;
+    Ok(key)
+}
+
+fn decode_fixed<const N: usize>(encoded: &str, field: &str) -> Result<[u8; N]> {
+    BASE64.decode(encoded)?
+        .try_into()
+        .map_err(|_| anyhow!("Invalid {} in credential store", field))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_survive_reopening_and_need_the_secret() {
+        let path = env::temp_dir().join(format!("hive_credentials_{}.enc", uuid::Uuid::new_v4()));
+        let secret = CredentialSecret::Passphrase("correct horse battery staple".to_string());
+
+        let store = CredentialStore::new(&path);
+        assert!(matches!(store.set("OpenRouter", "sk-or-test").unwrap_err().downcast_ref(), Some(CredentialStoreError::Locked)));
+        assert!(matches!(store.unlock(&secret, false).unwrap_err().downcast_ref(), Some(CredentialStoreError::NotCreated)));
+        assert!(store.unlock(&secret, true).unwrap().is_empty());
+        store.set("OpenRouter", "sk-or-test").unwrap();
+        store.set("Requesty", "rq-test").unwrap();
+        assert!(store.remove("Requesty").unwrap());
+        assert!(!fs::read_to_string(&path).unwrap().contains("sk-or-test"));
+
+        let reopened = CredentialStore::new(&path);
+        let api_keys = reopened.unlock(&secret, false).unwrap();
+        assert_eq!(api_keys, BTreeMap::from([("OpenRouter".to_string(), "sk-or-test".to_string())]));
+        let wrong = reopened.unlock(&CredentialSecret::Passphrase("wrong".to_string()), false).unwrap_err();
+        assert!(matches!(wrong.downcast_ref(), Some(CredentialStoreError::WrongSecret)));
+
+        let _ = fs::remove_file(&path);
+    }
+}
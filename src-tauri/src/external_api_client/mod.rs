@@ -1,55 +1,947 @@
 // Placeholder for external API client module
 // Will include OpenRouter and Requesty clients
 
-use serde::{Serialize, Deserialize};
-
+pub mod cache;
+pub mod cassette;
+pub mod catalog;
+pub mod config;
+pub mod credential_store;
+pub mod pricing;
 pub mod providers;
+pub mod rate_limit;
+pub mod routing;
 
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
+use std::env;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 
 use providers::LLMProvider;
 pub use providers::LLMRequest;
+pub use providers::{LLMEmbeddings, LLMMessage, LLMModelInfo, LLMParameters, LLMResponseFormat, LLMRole, LLMTokenCounts, LLMTool, LLMToolCall};
 pub use providers::LLMResponse;
+use providers::anthropic_provider::AnthropicProvider;
+use providers::hashing_embedder::HashingEmbedder;
+use providers::openai_compatible_provider::OpenAICompatibleProvider;
 use providers::openrouter_provider::OpenRouterProvider;
 use providers::requesty_provider::RequestyProvider;
+use providers::scripted_provider::ScriptedProvider;
+use cache::ResponseCache;
+use cassette::{Cassette, CassetteMode};
+use catalog::ModelCatalog;
+use config::{ExternalApiConfig, DEFAULT_PROVIDERS_CONFIG_PATH};
+use credential_store::{CredentialSecret, CredentialStore, CredentialStoreError};
+use pricing::{PriceTable, DEFAULT_PRICING_PATH};
+pub use routing::{FallbackCondition, LLMRoute, RoutingPolicy};
+pub use providers::error::LLMError;
+use rate_limit::{ProviderRateLimiter, RateLimitConfig};
+
+/// Environment variable pointing at a fixture file for the offline "Scripted" provider.
+pub const SCRIPTED_FIXTURES_ENV: &str = "HIVE_SCRIPTED_FIXTURES";
+
+const STREAM_RELAY_CAPACITY: usize = 64;
+// How many times a call answered with 429 is queued again before the error is returned
+const MAX_RATE_LIMITED_REQUEUES: u32 = 5;
+// Wait after a 429 without Retry-After, multiplied by the requeue count
+const DEFAULT_RATE_LIMIT_PAUSE: Duration = Duration::from_secs(2);
+/// Deadline of each routed call when neither the config nor the call options set one.
+/// It covers the provider's retries and rate-limit waits, not only the HTTP request.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Whether a registered provider can be called, as shown to the frontend.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+pub struct ProviderStatus {
+    pub name: String,
+    pub configured: bool,
+}
+
+/// Per-call options of `call_llm` and `call_llm_stream`.
+#[derive(Debug, Clone, Default)]
+pub struct LLMCallOptions {
+    // Skip the cache lookup; the fresh response still replaces the cached one
+    pub bypass_cache: bool,
+    // Deadline of each route; the client's request timeout when unset
+    pub timeout: Option<Duration>,
+    // Cancelling the token drops the in-flight request and fails the call as Cancelled
+    pub cancellation: Option<CancellationToken>,
+}
+
+/// State of the credential store, as shown to the frontend.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+pub struct CredentialStoreStatus {
+    pub configured: bool,
+    pub exists: bool,
+    pub unlocked: bool,
+}
 
 #[derive(Debug)] // Add Debug trait
 pub struct ExternalApiClient {
     providers: HashMap<String, Box<dyn LLMProvider>>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-pub struct LLMTokenCounts {
-    pub prompt_tokens: u64,
-    pub completion_tokens: u64,
-    pub total_tokens: u64,
+    price_table: PriceTable,
+    rate_limiters: HashMap<String, Arc<ProviderRateLimiter>>,
+    response_cache: Option<ResponseCache>,
+    model_catalog: ModelCatalog,
+    // Used by `embed`; None means the local Hashing embedder
+    embedding_route: Option<LLMRoute>,
+    // Kept apart from `providers`, which are chat providers that calls can be routed to
+    hashing_embedder: HashingEmbedder,
+    // Provider traffic recorded to, or replayed from, a cassette file
+    cassette: Option<Cassette>,
+    // Deadline of each route of a call, unless the call options set one
+    request_timeout: Duration,
+    // Encrypted provider API keys; keys set at runtime are only kept in memory without it
+    credential_store: Option<CredentialStore>,
 }
 
 impl ExternalApiClient {
     pub fn new() -> Result<Self, anyhow::Error> {
         let mut providers: HashMap<String, Box<dyn LLMProvider>> = HashMap::new();
+        let config = ExternalApiConfig::load_or_default(DEFAULT_PROVIDERS_CONFIG_PATH)?;
 
-        // Initialize providers
-        let openrouter = OpenRouterProvider::new()?;
-        let requesty = RequestyProvider::new()?;
+        // Initialize providers. Without an API key a provider is registered as not configured:
+        // its calls fail until a key is set at runtime, and offline runs can use the scripted provider.
+        let openrouter = OpenRouterProvider::new().with_retry_config(config.retry_for("OpenRouter"));
+        let requesty = RequestyProvider::new().with_retry_config(config.retry_for("Requesty"));
+        let anthropic = AnthropicProvider::new().with_retry_config(config.retry_for("Anthropic"));
+        for provider in [Box::new(openrouter) as Box<dyn LLMProvider>, Box::new(requesty), Box::new(anthropic)] {
+            if !provider.is_configured() {
+                eprintln!("Warning: {} provider not configured: no API key set", provider.name());
+            }
+            providers.insert(provider.name(), provider);
+        }
 
-        providers.insert(openrouter.name(), Box::new(openrouter));
-        providers.insert(requesty.name(), Box::new(requesty));
+        // Additional OpenAI-compatible endpoints (local models, self-hosted gateways) from the config file
+        for provider_config in &config.openai_compatible {
+            if providers.contains_key(&provider_config.name) {
+                eprintln!("Warning: Skipping OpenAI-compatible provider '{}': name already registered", provider_config.name);
+                continue;
+            }
+            match OpenAICompatibleProvider::new(provider_config) {
+                Ok(provider) => {
+                    println!("OpenAI-compatible provider '{}' registered at {}", provider_config.name, provider_config.base_url);
+                    providers.insert(provider.name(), Box::new(provider));
+                },
+                Err(e) => eprintln!("Warning: OpenAI-compatible provider '{}' disabled: {}", provider_config.name, e),
+            }
+        }
 
-        Ok(ExternalApiClient { providers })
+        if let Ok(fixture_path) = env::var(SCRIPTED_FIXTURES_ENV) {
+            let scripted = ScriptedProvider::from_file(&fixture_path)?;
+            match providers.entry(scripted.name()) {
+                Entry::Occupied(entry) => {
+                    eprintln!("Warning: Skipping scripted provider '{}' from {}: name already registered", entry.key(), fixture_path);
+                }
+                Entry::Vacant(entry) => {
+                    println!("Scripted LLM provider loaded from: {}", fixture_path);
+                    entry.insert(Box::new(scripted));
+                }
+            }
+        }
+
+        // Without a price table calls are still metered, just not costed
+        let price_table = if Path::new(DEFAULT_PRICING_PATH).exists() {
+            PriceTable::from_file(DEFAULT_PRICING_PATH)?
+        } else {
+            eprintln!("Warning: No price table at {}, LLM costs will not be estimated", DEFAULT_PRICING_PATH);
+            PriceTable::default()
+        };
+
+        let mut client = ExternalApiClient {
+            providers,
+            price_table,
+            rate_limiters: HashMap::new(),
+            response_cache: None,
+            model_catalog: ModelCatalog::default(),
+            embedding_route: None,
+            hashing_embedder: HashingEmbedder::default(),
+            cassette: None,
+            request_timeout: config.request_timeout_secs.map_or(DEFAULT_REQUEST_TIMEOUT, Duration::from_secs),
+            credential_store: None,
+        };
+        if let Some(embedding_route) = config.embeddings {
+            println!("Embeddings served by provider: {}", embedding_route.provider);
+            client = client.with_embedding_route(embedding_route);
+        }
+        if let Some(cassette) = Cassette::from_env()? {
+            match cassette.mode() {
+                CassetteMode::Record => println!("Recording LLM provider traffic to cassette: {:?}", cassette.path()),
+                CassetteMode::Replay => println!("Replaying LLM provider traffic from cassette: {:?}", cassette.path()),
+            }
+            client = client.with_cassette(cassette);
+        }
+        if let Some(cache_config) = config.response_cache {
+            println!("LLM response cache enabled");
+            client = client.with_response_cache(ResponseCache::new(crate::persistence::establish_connection()?, cache_config)?);
+        }
+        Ok(client.with_rate_limits(&config.rate_limits))
+    }
+
+    /// Builds a client from an explicit set of providers, bypassing environment configuration.
+    pub fn with_providers(provider_list: Vec<Box<dyn LLMProvider>>) -> Self {
+        let providers = provider_list
+            .into_iter()
+            .map(|provider| (provider.name(), provider))
+            .collect();
+        ExternalApiClient {
+            providers,
+            price_table: PriceTable::default(),
+            rate_limiters: HashMap::new(),
+            response_cache: None,
+            model_catalog: ModelCatalog::default(),
+            embedding_route: None,
+            hashing_embedder: HashingEmbedder::default(),
+            cassette: None,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            credential_store: None,
+        }
+    }
+
+    /// Keeps provider API keys in `credential_store`. The store is unlocked at once when the
+    /// environment names its passphrase or key file; otherwise `unlock_credentials` loads the keys.
+    pub fn with_credential_store(mut self, credential_store: CredentialStore) -> Self {
+        self.credential_store = Some(credential_store);
+        if let Some(secret) = CredentialSecret::from_env() {
+            // Naming the secret in the environment is deliberate enough to create the store
+            if let Err(e) = self.unlock_credentials(&secret, true) {
+                eprintln!("Warning: Failed to unlock the credential store: {}", e);
+            }
+        }
+        self
+    }
+
+    /// Fails calls whose route gives no complete response within `request_timeout`.
+    pub fn with_request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    /// Records every provider call to `cassette`, or answers from it when it is replayed.
+    /// The response cache is not used while a cassette is set, so every call reaches the cassette.
+    pub fn with_cassette(mut self, cassette: Cassette) -> Self {
+        self.cassette = Some(cassette);
+        self
+    }
+
+    /// Sends `embed` calls to `embedding_route` instead of the local Hashing embedder.
+    pub fn with_embedding_route(mut self, embedding_route: LLMRoute) -> Self {
+        self.embedding_route = Some(embedding_route);
+        self
+    }
+
+    /// Serves repeated calls from `response_cache` instead of calling the provider again.
+    pub fn with_response_cache(mut self, response_cache: ResponseCache) -> Self {
+        self.response_cache = Some(response_cache);
+        self
+    }
+
+    /// Installs per-provider rate limits, keyed by provider name.
+    pub fn with_rate_limits(mut self, rate_limits: &HashMap<String, RateLimitConfig>) -> Self {
+        for (provider_name, limits) in rate_limits {
+            if !self.providers.contains_key(provider_name) {
+                eprintln!("Warning: Rate limits configured for unknown provider '{}'", provider_name);
+            }
+            self.rate_limiters.insert(provider_name.clone(), Arc::new(ProviderRateLimiter::new(limits)));
+        }
+        self
+    }
+
+    /// Replaces the price table used by `estimate_cost`.
+    pub fn with_price_table(mut self, price_table: PriceTable) -> Self {
+        self.price_table = price_table;
+        self
+    }
+
+    /// Estimated cost in USD of a call, if the model has a price in the table.
+    pub fn estimate_cost(&self, model: &str, tokens: &LLMTokenCounts) -> Option<f64> {
+        self.price_table.cost_for(model, tokens)
     }
 
     pub fn get_provider(&self, name: &str) -> Option<&Box<dyn LLMProvider>> {
         self.providers.get(name)
     }
 
-    pub async fn call_llm(&self, provider_name: &str, request: LLMRequest) -> Result<LLMResponse, anyhow::Error> {
-        match self.get_provider(provider_name) {
-            Some(provider) => {
-                println!("Calling LLM provider: {}", provider.name());
-                provider.generate(request).await
+    /// Registered providers and whether each has its credentials, sorted by name.
+    pub fn provider_statuses(&self) -> Vec<ProviderStatus> {
+        let mut statuses: Vec<ProviderStatus> = self.providers.values()
+            .map(|provider| ProviderStatus { name: provider.name(), configured: provider.is_configured() })
+            .collect();
+        statuses.sort_by(|a, b| a.name.cmp(&b.name));
+        statuses
+    }
+
+    pub fn credential_store_status(&self) -> CredentialStoreStatus {
+        CredentialStoreStatus {
+            configured: self.credential_store.is_some(),
+            exists: self.credential_store.as_ref().is_some_and(CredentialStore::exists),
+            unlocked: self.credential_store.as_ref().is_some_and(CredentialStore::is_unlocked),
+        }
+    }
+
+    /// Unlocks the credential store and gives each stored key to its provider, over any key
+    /// from the environment. Returns the providers that received a key. The store is only
+    /// created when `create` is set. Blocks while the key is derived.
+    pub fn unlock_credentials(&self, secret: &CredentialSecret, create: bool) -> Result<Vec<String>, anyhow::Error> {
+        let credential_store = self.credential_store.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("No credential store configured"))?;
+        let mut loaded_providers = Vec::new();
+        for (provider_name, api_key) in credential_store.unlock(secret, create)? {
+            match self.get_provider(&provider_name) {
+                Some(provider) => {
+                    provider.replace_api_key(Some(api_key));
+                    loaded_providers.push(provider_name);
+                }
+                None => eprintln!("Warning: Credential store holds a key for unknown provider '{}'", provider_name),
+            }
+        }
+        println!("Credential store unlocked, API keys loaded for: {:?}", loaded_providers);
+        Ok(loaded_providers)
+    }
+
+    /// Verifies `api_key` with the provider and, if accepted, saves it in the credential store
+    /// and uses it for the provider's later calls. The previous key is kept when the check fails.
+    pub async fn set_provider_api_key(&self, provider_name: &str, api_key: String) -> Result<(), anyhow::Error> {
+        let provider = self.get_provider(provider_name)
+            .ok_or_else(|| anyhow::anyhow!("Provider '{}' not found", provider_name))?;
+        // Checked first so a key is not verified, then lost on restart
+        if self.credential_store.as_ref().is_some_and(|store| !store.is_unlocked()) {
+            return Err(CredentialStoreError::Locked.into());
+        }
+        provider.check_api_key(&api_key).await?;
+        if let Some(credential_store) = &self.credential_store {
+            credential_store.set(provider_name, &api_key)?;
+        }
+        provider.replace_api_key(Some(api_key));
+        println!("API key updated for provider: {}", provider_name);
+        Ok(())
+    }
+
+    /// Replaces the key of a provider that already has one, keeping the old key if the new one is rejected.
+    pub async fn rotate_provider_api_key(&self, provider_name: &str, new_api_key: String) -> Result<(), anyhow::Error> {
+        let provider = self.get_provider(provider_name)
+            .ok_or_else(|| anyhow::anyhow!("Provider '{}' not found", provider_name))?;
+        if provider.current_api_key().is_none() {
+            return Err(anyhow::anyhow!("Provider '{}' has no API key to rotate", provider_name));
+        }
+        self.set_provider_api_key(provider_name, new_api_key).await
+    }
+
+    /// Checks the key the provider currently calls with against the provider.
+    pub async fn test_provider_api_key(&self, provider_name: &str) -> Result<(), anyhow::Error> {
+        let provider = self.get_provider(provider_name)
+            .ok_or_else(|| anyhow::anyhow!("Provider '{}' not found", provider_name))?;
+        let api_key = provider.current_api_key()
+            .ok_or_else(|| LLMError::NotConfigured { provider: provider_name.to_string() })?;
+        provider.check_api_key(&api_key).await
+    }
+
+    /// Removes the key of `provider` from the credential store and stops using it.
+    pub fn delete_provider_api_key(&self, provider_name: &str) -> Result<(), anyhow::Error> {
+        let provider = self.get_provider(provider_name)
+            .ok_or_else(|| anyhow::anyhow!("Provider '{}' not found", provider_name))?;
+        if let Some(credential_store) = &self.credential_store {
+            credential_store.remove(provider_name)?;
+        }
+        provider.replace_api_key(None);
+        println!("API key deleted for provider: {}", provider_name);
+        Ok(())
+    }
+
+    /// Embeds `texts` with the configured embedding route, or the local Hashing embedder when none is configured.
+    pub async fn embed(&self, texts: &[String]) -> Result<LLMEmbeddings, anyhow::Error> {
+        match &self.embedding_route {
+            Some(route) => self.embed_with(&route.provider, texts, route.model.as_deref().unwrap_or_default()).await,
+            None => self.hashing_embedder.embed(texts, "").await,
+        }
+    }
+
+    /// Embeds `texts` with a given provider and model; an empty model uses the provider's default.
+    /// `Hashing` names the local embedder.
+    pub async fn embed_with(&self, provider_name: &str, texts: &[String], model: &str) -> Result<LLMEmbeddings, anyhow::Error> {
+        // Always available, so embeddings work offline
+        if provider_name == self.hashing_embedder.name() {
+            return self.hashing_embedder.embed(texts, model).await;
+        }
+        let provider = self.get_provider(provider_name)
+            .ok_or_else(|| anyhow::anyhow!("Provider '{}' not found", provider_name))?;
+        if !provider.is_configured() {
+            return Err(LLMError::NotConfigured { provider: provider_name.to_string() }.into());
+        }
+        if texts.is_empty() {
+            return Ok(LLMEmbeddings { vectors: Vec::new(), model: model.to_string(), usage: None });
+        }
+        println!("Embedding {} text(s) with provider: {}", texts.len(), provider_name);
+        provider.embed(texts, model).await
+    }
+
+    /// Default model of a provider, for requests that do not name a model.
+    pub fn default_model(&self, provider_name: &str) -> Option<String> {
+        self.get_provider(provider_name).and_then(|provider| provider.default_model())
+    }
+
+    /// Models offered by a provider, fetched once per `catalog::MODEL_CATALOG_TTL`.
+    pub async fn list_models(&self, provider_name: &str) -> Result<Vec<LLMModelInfo>, anyhow::Error> {
+        if let Some(models) = self.model_catalog.get(provider_name).await {
+            return Ok(models);
+        }
+        let provider = self.get_provider(provider_name)
+            .ok_or_else(|| anyhow::anyhow!("Provider '{}' not found", provider_name))?;
+        let models = provider.list_models().await?;
+        self.model_catalog.insert(provider_name, models.clone()).await;
+        Ok(models)
+    }
+
+    /// Context window of `model` as listed in the provider's catalog, if known.
+    pub async fn context_length(&self, provider_name: &str, model: &str) -> Option<u64> {
+        match self.list_models(provider_name).await {
+            Ok(models) => models.into_iter().find(|m| m.id == model).and_then(|m| m.context_length),
+            Err(e) => {
+                eprintln!("Warning: Could not fetch the model catalog of {}: {}. Skipping the context window check.", provider_name, e);
+                None
+            }
+        }
+    }
+
+    /// Checks that `provider_name` exists and offers `model`. Providers without a catalog accept
+    /// any model, and an unreachable catalog only produces a warning so offline setups keep working.
+    pub async fn validate_model(&self, provider_name: &str, model: &str) -> Result<(), anyhow::Error> {
+        match self.list_models(provider_name).await {
+            Ok(models) if !models.is_empty() && !models.iter().any(|m| m.id == model) => Err(anyhow::anyhow!(
+                "Model '{}' is not offered by provider '{}'", model, provider_name
+            )),
+            Ok(_) => Ok(()),
+            Err(e) if self.get_provider(provider_name).is_none() => Err(e),
+            Err(e) => {
+                eprintln!("Warning: Could not fetch the model catalog of {}: {}. Skipping model validation.", provider_name, e);
+                Ok(())
+            }
+        }
+    }
+
+    /// Calls the routes of `policy` in order until one succeeds. A failed route is only
+    /// followed by the next one when the failure matches the policy's fallback conditions.
+    /// The response records the route that served it.
+    pub async fn call_llm(&self, policy: &RoutingPolicy, request: LLMRequest, options: &LLMCallOptions) -> Result<LLMResponse, LLMError> {
+        self.route_call(policy, request, options, None).await
+    }
+
+    /// Like `call_llm`, but forwards partial tokens through `chunk_sender` while the completion is generated.
+    /// Once a route has emitted tokens its failure is returned as is: falling back would duplicate output.
+    pub async fn call_llm_stream(&self, policy: &RoutingPolicy, request: LLMRequest, options: &LLMCallOptions, chunk_sender: mpsc::Sender<String>) -> Result<LLMResponse, LLMError> {
+        self.route_call(policy, request, options, Some(chunk_sender)).await
+    }
+
+    async fn route_call(&self, policy: &RoutingPolicy, request: LLMRequest, options: &LLMCallOptions, chunk_sender: Option<mpsc::Sender<String>>) -> Result<LLMResponse, LLMError> {
+        let mut last_error = None;
+
+        for (index, route) in policy.routes.iter().enumerate() {
+            let has_next_route = index + 1 < policy.routes.len();
+
+            // A route to an unknown provider is a configuration gap, always skip it
+            let Some(provider) = self.get_provider(&route.provider) else {
+                let error = LLMError::InvalidRequest {
+                    provider: route.provider.clone(),
+                    status: None,
+                    message: "Provider not found".to_string(),
+                };
+                if has_next_route {
+                    eprintln!("Warning: {}, trying next route", error);
+                }
+                last_error = Some(error);
+                continue;
+            };
+            // A replayed run needs no credentials
+            if !provider.is_configured() && !self.is_replaying() {
+                let error = LLMError::NotConfigured { provider: route.provider.clone() };
+                if has_next_route {
+                    eprintln!("Warning: {}, trying next route", error);
+                }
+                last_error = Some(error);
+                continue;
+            }
+
+            let mut routed_request = request.clone();
+            if let Some(model) = &route.model {
+                routed_request.model = model.clone();
+            } else if routed_request.model.is_empty() {
+                routed_request.model = provider.default_model().unwrap_or_default();
+            }
+            if routed_request.model.is_empty() {
+                let error = LLMError::InvalidRequest {
+                    provider: route.provider.clone(),
+                    status: None,
+                    message: "No model specified for the route and the provider has no default model".to_string(),
+                };
+                if has_next_route {
+                    eprintln!("Warning: {}, trying next route", error);
+                }
+                last_error = Some(error);
+                continue;
+            }
+            let served_by = LLMRoute::new(route.provider.clone(), Some(routed_request.model.clone()));
+
+            let cache_key = self.response_cache.as_ref()
+                .and_then(|_| ResponseCache::cache_key(&route.provider, &routed_request).ok());
+            if !options.bypass_cache && self.cassette.is_none() {
+                if let Some(mut response) = self.cached_response(cache_key.as_deref()).await {
+                    println!("LLM response served from cache: {} (model: {})", route.provider, routed_request.model);
+                    // The whole completion arrives as a single chunk
+                    if let Some(sender) = &chunk_sender {
+                        let _ = sender.send(response.content.clone()).await;
+                    }
+                    response.served_by = Some(served_by);
+                    return Ok(response);
+                }
+            }
+
+            let deadline = options.timeout.unwrap_or(self.request_timeout);
+            let (result, emitted_chunks) = self.call_route_within(provider.as_ref(), routed_request.clone(), chunk_sender.as_ref(), deadline, options.cancellation.as_ref()).await;
+
+            match result {
+                Ok(mut response) => {
+                    response.served_by = Some(served_by);
+                    if let (Some(cache), Some(key), None) = (&self.response_cache, &cache_key, &self.cassette) {
+                        if let Err(e) = cache.put(key, &route.provider, &routed_request.model, &response).await {
+                            eprintln!("Warning: Failed to cache LLM response: {}", e);
+                        }
+                    }
+                    return Ok(response);
+                }
+                // Nobody is waiting for the answer any more, another route would be wasted
+                Err(e @ LLMError::Cancelled { .. }) => return Err(e),
+                Err(e) if has_next_route && !emitted_chunks && policy.should_fall_back(&e) => {
+                    eprintln!("Warning: LLM route {} failed: {}. Falling back to next route.", route.provider, e);
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| LLMError::InvalidRequest {
+            provider: String::new(),
+            status: None,
+            message: "Routing policy has no route".to_string(),
+        }))
+    }
+
+    /// Cached response for `cache_key`, marked as such. A cache hit costs nothing, so it carries no usage.
+    async fn cached_response(&self, cache_key: Option<&str>) -> Option<LLMResponse> {
+        let (cache, key) = (self.response_cache.as_ref()?, cache_key?);
+        match cache.get(key).await {
+            Ok(Some(response)) => Some(LLMResponse { usage: None, cached: true, ..response }),
+            Ok(None) => None,
+            Err(e) => {
+                eprintln!("Warning: Failed to read LLM response cache: {}", e);
+                None
+            }
+        }
+    }
+
+    fn is_replaying(&self) -> bool {
+        self.cassette.as_ref().is_some_and(|cassette| cassette.mode() == CassetteMode::Replay)
+    }
+
+    /// `call_route` failed as a timeout after `deadline`, or as cancelled once `cancellation` fires.
+    /// Either way the call's future is dropped, which aborts its HTTP request.
+    async fn call_route_within(
+        &self,
+        provider: &dyn LLMProvider,
+        request: LLMRequest,
+        chunk_sender: Option<&mpsc::Sender<String>>,
+        deadline: Duration,
+        cancellation: Option<&CancellationToken>,
+    ) -> (Result<LLMResponse, LLMError>, bool) {
+        let cancelled = async {
+            match cancellation {
+                Some(token) => token.cancelled().await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            // A call cancelled before it starts is not sent at all
+            biased;
+            _ = cancelled => {
+                eprintln!("Warning: Call to {} cancelled", provider.name());
+                // Tokens may already have been streamed, so the caller must not fall back
+                (Err(LLMError::Cancelled { provider: provider.name() }), chunk_sender.is_some())
+            }
+            outcome = tokio::time::timeout(deadline, self.call_route(provider, request, chunk_sender)) => match outcome {
+                Ok(outcome) => outcome,
+                Err(_) => {
+                    let error = LLMError::Transport {
+                        provider: provider.name(),
+                        status: None,
+                        timeout: true,
+                        retry_after: None,
+                        message: format!("No complete response within {:?}", deadline),
+                    };
+                    eprintln!("Warning: {}", error);
+                    // A stream cut by the deadline may have emitted part of the answer: no fallback then
+                    (Err(error), chunk_sender.is_some())
+                }
             },
-            None => Err(anyhow::anyhow!("Provider '{}' not found", provider_name)),
         }
     }
-}
\ No newline at end of file
+
+    /// Calls one provider, or answers from the cassette when one is replayed. Recorded cassettes get every call.
+    async fn call_route(&self, provider: &dyn LLMProvider, request: LLMRequest, chunk_sender: Option<&mpsc::Sender<String>>) -> (Result<LLMResponse, LLMError>, bool) {
+        let Some(cassette) = &self.cassette else {
+            return self.call_provider(provider, request, chunk_sender).await;
+        };
+
+        match cassette.mode() {
+            CassetteMode::Replay => {
+                println!("Replaying LLM provider: {} (model: {})", provider.name(), request.model);
+                let (result, emitted_chunks) = cassette.replay_call(&provider.name(), &request).await;
+                // Like cache hits, a replayed completion arrives as a single chunk
+                if let (Ok(response), Some(sender)) = (&result, chunk_sender) {
+                    let _ = sender.send(response.content.clone()).await;
+                }
+                (result, emitted_chunks)
+            }
+            CassetteMode::Record => {
+                let (result, emitted_chunks) = self.call_provider(provider, request.clone(), chunk_sender).await;
+                if let Err(e) = cassette.record_call(&provider.name(), &request, &result, emitted_chunks).await {
+                    eprintln!("Warning: Failed to record LLM call to cassette: {}", e);
+                }
+                (result, emitted_chunks)
+            }
+        }
+    }
+
+    /// Calls one provider within its rate limits. A `429` answer pauses the provider for the
+    /// `Retry-After` delay and queues the call again instead of failing it.
+    async fn call_provider(&self, provider: &dyn LLMProvider, request: LLMRequest, chunk_sender: Option<&mpsc::Sender<String>>) -> (Result<LLMResponse, LLMError>, bool) {
+        let limiter = self.rate_limiters.get(&provider.name());
+        let estimated_tokens = ProviderRateLimiter::estimate_tokens(&request);
+        let mut requeues = 0;
+
+        loop {
+            let permit = match limiter {
+                Some(limiter) => Some(limiter.acquire(estimated_tokens).await),
+                None => None,
+            };
+
+            let (result, emitted_chunks) = match chunk_sender {
+                Some(sender) => {
+                    println!("Streaming from LLM provider: {} (model: {})", provider.name(), request.model);
+                    Self::stream_from(provider, request.clone(), sender.clone()).await
+                }
+                None => {
+                    println!("Calling LLM provider: {} (model: {})", provider.name(), request.model);
+                    (provider.generate(request.clone()).await, false)
+                }
+            };
+
+            if let (Some(limiter), Some(permit)) = (limiter, &permit) {
+                if let Ok(response) = &result {
+                    let used_tokens = response.usage.as_ref().map_or(estimated_tokens, |usage| usage.total_tokens);
+                    limiter.record_usage(permit, used_tokens).await;
+                }
+            }
+
+            let result = result.and_then(|response| reject_filtered_completion(&provider.name(), response));
+            match &result {
+                Err(LLMError::RateLimited { retry_after, .. }) if !emitted_chunks && requeues < MAX_RATE_LIMITED_REQUEUES => {
+                    requeues += 1;
+                    let delay = retry_after.unwrap_or(DEFAULT_RATE_LIMIT_PAUSE * requeues);
+                    eprintln!("Warning: {} rate limited the call, queueing it again in {:?} ({}/{})", provider.name(), delay, requeues, MAX_RATE_LIMITED_REQUEUES);
+                    match limiter {
+                        // Pausing the limiter also holds back the provider's other queued calls
+                        Some(limiter) => limiter.pause_for(delay).await,
+                        None => tokio::time::sleep(delay).await,
+                    }
+                }
+                _ => return (result, emitted_chunks),
+            }
+        }
+    }
+
+    /// Streams one route through a relay channel, reporting whether any chunk reached `chunk_sender`.
+    async fn stream_from(provider: &dyn LLMProvider, request: LLMRequest, chunk_sender: mpsc::Sender<String>) -> (Result<LLMResponse, LLMError>, bool) {
+        let (relay_sender, mut relay_receiver) = mpsc::channel::<String>(STREAM_RELAY_CAPACITY);
+        let relay = tokio::spawn(async move {
+            let mut emitted_chunks = false;
+            while let Some(chunk) = relay_receiver.recv().await {
+                emitted_chunks = true;
+                let _ = chunk_sender.send(chunk).await;
+            }
+            emitted_chunks
+        });
+
+        // The relay ends once the provider drops its sender, i.e. when the call returns
+        let result = provider.generate_stream(request, relay_sender).await;
+        let emitted_chunks = relay.await.unwrap_or(true);
+        (result, emitted_chunks)
+    }
+}
+
+/// A completion the provider stopped with its content filter, and left empty, is a failure rather than an empty answer.
+fn reject_filtered_completion(provider_name: &str, response: LLMResponse) -> Result<LLMResponse, LLMError> {
+    if response.finish_reason.as_deref() == Some("content_filter") && response.content.trim().is_empty() && response.tool_calls.is_empty() {
+        return Err(LLMError::ContentFiltered {
+            provider: provider_name.to_string(),
+            message: "The completion was withheld by the provider's content filter".to_string(),
+        });
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use providers::ProviderHttpError;
+
+    /// Provider that always fails with the given HTTP status, optionally after streaming a chunk.
+    #[derive(Debug)]
+    struct FailingProvider {
+        status: u16,
+        partial_chunk: Option<String>,
+    }
+
+    #[async_trait]
+    impl LLMProvider for FailingProvider {
+        fn name(&self) -> String {
+            "Failing".to_string()
+        }
+
+        async fn generate(&self, _request: LLMRequest) -> Result<LLMResponse, LLMError> {
+            Err(LLMError::from_http(&ProviderHttpError { provider: self.name(), status: self.status, body: "outage".to_string(), retry_after: None }))
+        }
+
+        async fn generate_stream(&self, request: LLMRequest, chunk_sender: mpsc::Sender<String>) -> Result<LLMResponse, LLMError> {
+            if let Some(chunk) = &self.partial_chunk {
+                let _ = chunk_sender.send(chunk.clone()).await;
+            }
+            self.generate(request).await
+        }
+    }
+
+    /// Provider that never answers.
+    #[derive(Debug)]
+    struct HangingProvider;
+
+    #[async_trait]
+    impl LLMProvider for HangingProvider {
+        fn name(&self) -> String {
+            "Hanging".to_string()
+        }
+
+        async fn generate(&self, _request: LLMRequest) -> Result<LLMResponse, LLMError> {
+            std::future::pending().await
+        }
+
+        async fn generate_stream(&self, request: LLMRequest, _chunk_sender: mpsc::Sender<String>) -> Result<LLMResponse, LLMError> {
+            self.generate(request).await
+        }
+    }
+
+    /// Provider accepting a single API key.
+    #[derive(Debug, Default)]
+    struct KeyedProvider {
+        api_key: std::sync::RwLock<Option<String>>,
+    }
+
+    #[async_trait]
+    impl LLMProvider for KeyedProvider {
+        fn name(&self) -> String {
+            "Keyed".to_string()
+        }
+
+        fn is_configured(&self) -> bool {
+            self.current_api_key().is_some()
+        }
+
+        async fn check_api_key(&self, api_key: &str) -> Result<(), anyhow::Error> {
+            match api_key {
+                "good-key" | "rotated-key" => Ok(()),
+                _ => Err(anyhow::anyhow!("Keyed rejected the API key")),
+            }
+        }
+
+        fn replace_api_key(&self, api_key: Option<String>) {
+            *self.api_key.write().unwrap() = api_key;
+        }
+
+        fn current_api_key(&self) -> Option<String> {
+            self.api_key.read().unwrap().clone()
+        }
+
+        async fn generate(&self, _request: LLMRequest) -> Result<LLMResponse, LLMError> {
+            Err(LLMError::InvalidRequest { provider: self.name(), status: None, message: "not used".to_string() })
+        }
+    }
+
+    /// Provider registered without credentials.
+    #[derive(Debug)]
+    struct UnconfiguredProvider(FailingProvider);
+
+    #[async_trait]
+    impl LLMProvider for UnconfiguredProvider {
+        fn name(&self) -> String {
+            self.0.name()
+        }
+
+        fn is_configured(&self) -> bool {
+            false
+        }
+
+        async fn generate(&self, request: LLMRequest) -> Result<LLMResponse, LLMError> {
+            self.0.generate(request).await
+        }
+
+        async fn generate_stream(&self, request: LLMRequest, chunk_sender: mpsc::Sender<String>) -> Result<LLMResponse, LLMError> {
+            self.0.generate_stream(request, chunk_sender).await
+        }
+    }
+
+    fn client_with(failing: FailingProvider) -> ExternalApiClient {
+        let scripted = ScriptedProvider::from_json_str(r#"{ "responses": [ { "response": "from fallback" } ] }"#).unwrap();
+        ExternalApiClient::with_providers(vec![Box::new(failing), Box::new(scripted)])
+    }
+
+    fn request() -> LLMRequest {
+        LLMRequest::from_prompt("primary-model", "Hello", None)
+    }
+
+    fn policy() -> RoutingPolicy {
+        RoutingPolicy::single("Failing", None)
+            .with_fallbacks(vec![LLMRoute::new("Scripted", Some("fallback-model".to_string()))])
+    }
+
+    #[tokio::test]
+    async fn test_call_llm_falls_back_and_records_route() {
+        let client = client_with(FailingProvider { status: 503, partial_chunk: None });
+        let response = client.call_llm(&policy(), request(), &LLMCallOptions::default()).await.unwrap();
+        assert_eq!(response.content, "from fallback");
+        assert_eq!(response.served_by, Some(LLMRoute::new("Scripted", Some("fallback-model".to_string()))));
+
+        // Client errors are not covered by the default fallback conditions
+        let client = client_with(FailingProvider { status: 400, partial_chunk: None });
+        assert!(client.call_llm(&policy(), request(), &LLMCallOptions::default()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_call_llm_stream_does_not_fall_back_after_emitting() {
+        let (chunk_sender, mut chunk_receiver) = mpsc::channel(8);
+        let client = client_with(FailingProvider { status: 503, partial_chunk: Some("partial".to_string()) });
+        assert!(client.call_llm_stream(&policy(), request(), &LLMCallOptions::default(), chunk_sender).await.is_err());
+        assert_eq!(chunk_receiver.recv().await, Some("partial".to_string()));
+        assert_eq!(chunk_receiver.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_filtered_stream_is_content_filtered() {
+        let body: String = [
+            r#"{"choices":[{"delta":{"role":"assistant","content":""},"finish_reason":null}]}"#,
+            r#"{"choices":[{"delta":{},"finish_reason":"content_filter"}]}"#,
+            "[DONE]",
+        ].iter().map(|payload| format!("data: {}\n\n", payload)).collect();
+        let (url, _server) = providers::test_server::serve_once("/v1", "text/event-stream", body).await;
+        let config: config::OpenAICompatibleProviderConfig = serde_json::from_value(serde_json::json!({
+            "name": "Local",
+            "base_url": url,
+            "default_model": "local-model"
+        })).unwrap();
+        let client = ExternalApiClient::with_providers(vec![Box::new(OpenAICompatibleProvider::new(&config).unwrap())]);
+
+        let (chunk_sender, _chunk_receiver) = mpsc::channel(8);
+        let result = client.call_llm_stream(&RoutingPolicy::single("Local", None), request(), &LLMCallOptions::default(), chunk_sender).await;
+        assert!(matches!(result, Err(LLMError::ContentFiltered { .. })), "{:?}", result);
+    }
+
+    #[tokio::test]
+    async fn test_call_llm_served_from_cache_unless_bypassed() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        let cache = ResponseCache::new(conn, cache::ResponseCacheConfig::default()).unwrap();
+        let client = client_with(FailingProvider { status: 503, partial_chunk: None }).with_response_cache(cache);
+        let policy = RoutingPolicy::single("Scripted", None);
+
+        let first = client.call_llm(&policy, request(), &LLMCallOptions::default()).await.unwrap();
+        assert!(!first.cached);
+        let second = client.call_llm(&policy, request(), &LLMCallOptions::default()).await.unwrap();
+        assert!(second.cached);
+        assert_eq!(second.content, "from fallback");
+        assert_eq!(second.served_by, first.served_by);
+
+        let bypassed = client.call_llm(&policy, request(), &LLMCallOptions { bypass_cache: true, ..Default::default() }).await.unwrap();
+        assert!(!bypassed.cached);
+    }
+
+    #[tokio::test]
+    async fn test_unconfigured_provider_is_skipped() {
+        let unconfigured = UnconfiguredProvider(FailingProvider { status: 503, partial_chunk: None });
+        let client = ExternalApiClient::with_providers(vec![Box::new(unconfigured)]);
+        assert_eq!(client.provider_statuses(), vec![ProviderStatus { name: "Failing".to_string(), configured: false }]);
+
+        // The provider would fail with a 503; not configured means it is never called
+        let error = client.call_llm(&RoutingPolicy::single("Failing", None), request(), &LLMCallOptions::default()).await.unwrap_err();
+        assert_eq!(error, LLMError::NotConfigured { provider: "Failing".to_string() });
+
+        // Key checks and embeddings report the same error
+        let key_error = client.test_provider_api_key("Failing").await.unwrap_err();
+        assert_eq!(key_error.downcast_ref::<LLMError>(), Some(&error));
+        let embed_error = client.embed_with("Failing", &["text".to_string()], "").await.unwrap_err();
+        assert_eq!(embed_error.downcast_ref::<LLMError>(), Some(&error));
+    }
+
+    #[tokio::test]
+    async fn test_hung_provider_times_out_or_is_cancelled() {
+        let scripted = ScriptedProvider::from_json_str(r#"{ "responses": [ { "response": "from fallback" } ] }"#).unwrap();
+        let client = ExternalApiClient::with_providers(vec![Box::new(HangingProvider), Box::new(scripted)])
+            .with_request_timeout(Duration::from_millis(50));
+        let policy = RoutingPolicy::single("Hanging", None)
+            .with_fallbacks(vec![LLMRoute::new("Scripted", Some("fallback-model".to_string()))]);
+
+        // A timeout is a transport failure, so the next route serves the call
+        let response = client.call_llm(&policy, request(), &LLMCallOptions::default()).await.unwrap();
+        assert_eq!(response.content, "from fallback");
+        let error = client.call_llm(&RoutingPolicy::single("Hanging", None), request(), &LLMCallOptions::default()).await.unwrap_err();
+        assert!(matches!(error, LLMError::Transport { timeout: true, .. }));
+
+        // Cancellation wins over the deadline and skips the fallback routes
+        let token = CancellationToken::new();
+        let options = LLMCallOptions { timeout: Some(Duration::from_secs(60)), cancellation: Some(token.clone()), ..Default::default() };
+        let canceller = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            token.cancel();
+        });
+        let error = client.call_llm(&policy, request(), &options).await.unwrap_err();
+        assert_eq!(error, LLMError::Cancelled { provider: "Hanging".to_string() });
+        canceller.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_api_keys_are_kept_in_the_credential_store() {
+        let path = env::temp_dir().join(format!("hive_credentials_{}.enc", uuid::Uuid::new_v4()));
+        let secret = CredentialSecret::Passphrase("passphrase".to_string());
+        let client = ExternalApiClient::with_providers(vec![Box::new(KeyedProvider::default())])
+            .with_credential_store(CredentialStore::new(&path));
+
+        // A locked store refuses keys rather than keeping them only until the next restart
+        assert!(client.set_provider_api_key("Keyed", "good-key".to_string()).await.is_err());
+        client.unlock_credentials(&secret, true).unwrap();
+        assert!(client.rotate_provider_api_key("Keyed", "good-key".to_string()).await.is_err());
+        client.set_provider_api_key("Keyed", "good-key".to_string()).await.unwrap();
+        assert!(client.set_provider_api_key("Keyed", "bad-key".to_string()).await.is_err());
+        client.rotate_provider_api_key("Keyed", "rotated-key".to_string()).await.unwrap();
+        client.test_provider_api_key("Keyed").await.unwrap();
+
+        let restarted = ExternalApiClient::with_providers(vec![Box::new(KeyedProvider::default())])
+            .with_credential_store(CredentialStore::new(&path));
+        assert!(!restarted.get_provider("Keyed").unwrap().is_configured());
+        assert_eq!(restarted.unlock_credentials(&secret, false).unwrap(), vec!["Keyed".to_string()]);
+        assert_eq!(restarted.get_provider("Keyed").unwrap().current_api_key(), Some("rotated-key".to_string()));
+
+        restarted.delete_provider_api_key("Keyed").unwrap();
+        assert!(restarted.test_provider_api_key("Keyed").await.is_err());
+        assert!(CredentialStore::new(&path).unlock(&secret, false).unwrap().is_empty());
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
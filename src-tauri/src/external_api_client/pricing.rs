@@ -0,0 +1,61 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use super::LLMTokenCounts;
+
+/// Default location of the per-model price table, relative to the working directory.
+pub const DEFAULT_PRICING_PATH: &str = "config/llm_pricing.json";
+
+/// Price of a model in USD per million tokens.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ModelPrice {
+    pub prompt_per_million: f64,
+    pub completion_per_million: f64,
+}
+
+/// Per-model prices used to turn token counts into an estimated cost.
+/// Models missing from the table have no cost estimate.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PriceTable {
+    #[serde(default)]
+    pub models: HashMap<String, ModelPrice>,
+}
+
+impl PriceTable {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file_content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read price table {:?}", path))?;
+        serde_json::from_str(&file_content)
+            .with_context(|| format!("Invalid price table {:?}", path))
+    }
+
+    /// Estimated cost in USD of a call to `model` with the given token counts.
+    pub fn cost_for(&self, model: &str, tokens: &LLMTokenCounts) -> Option<f64> {
+        self.models.get(model).map(|price| {
+            (tokens.prompt_tokens as f64 * price.prompt_per_million
+                + tokens.completion_tokens as f64 * price.completion_per_million)
+                / 1_000_000.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cost_for_known_and_unknown_models() {
+        let table: PriceTable = serde_json::from_str(
+            r#"{ "models": { "priced-model": { "prompt_per_million": 2.0, "completion_per_million": 8.0 } } }"#
+        ).unwrap();
+        let tokens = LLMTokenCounts { prompt_tokens: 1_000, completion_tokens: 500, total_tokens: 1_500 };
+
+        let cost = table.cost_for("priced-model", &tokens).unwrap();
+        assert!((cost - 0.006).abs() < 1e-12);
+        assert_eq!(table.cost_for("unpriced-model", &tokens), None);
+    }
+}
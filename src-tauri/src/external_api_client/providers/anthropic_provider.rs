@@ -0,0 +1,615 @@
+use async_trait::async_trait;
+use anyhow::Result;
+use futures_util::StreamExt;
+use reqwest;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::env;
+use std::sync::RwLock;
+use tokio::sync::mpsc;
+
+use super::{http_client, LLMMessage, LLMModelInfo, LLMProvider, LLMRequest, LLMResponse, LLMRole, LLMTokenCounts, LLMTool, LLMToolCall, ProviderHttpError};
+use super::error::LLMError;
+use super::retry::{with_retries, RetryConfig};
+use super::sse::{SseDecoder, StreamError};
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// `max_tokens` is mandatory in the Messages API; this is sent when the request leaves it unset.
+pub const DEFAULT_MAX_TOKENS: u32 = 4096;
+
+#[derive(Debug, Clone, Serialize)]
+struct MessagesRequest {
+    model: String,
+    // System prompt, sent at the top level rather than as a message
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    system: Vec<ContentBlock>,
+    messages: Vec<AnthropicMessage>,
+    max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop_sequences: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<AnthropicTool>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct AnthropicMessage {
+    role: String,
+    content: Vec<ContentBlock>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentBlock {
+    Text {
+        text: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        input: Value,
+    },
+    ToolResult {
+        tool_use_id: String,
+        content: String,
+    },
+    // Block types this client does not use, such as thinking
+    #[serde(other)]
+    Other,
+}
+
+impl ContentBlock {
+    fn text(text: impl Into<String>) -> Self {
+        ContentBlock::Text { text: text.into(), cache_control: None }
+    }
+}
+
+/// Marks the end of a prompt prefix the API may cache between calls.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct CacheControl {
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct AnthropicTool {
+    name: String,
+    description: String,
+    input_schema: Value,
+}
+
+impl From<&LLMTool> for AnthropicTool {
+    fn from(tool: &LLMTool) -> Self {
+        AnthropicTool { name: tool.name.clone(), description: tool.description.clone(), input_schema: tool.parameters.clone() }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct MessagesResponse {
+    content: Vec<ContentBlock>,
+    stop_reason: Option<String>,
+    #[serde(default)]
+    usage: AnthropicUsage,
+}
+
+/// Token usage as reported by the API. Streams report it in parts, so every field is optional.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
+struct AnthropicUsage {
+    input_tokens: Option<u64>,
+    output_tokens: Option<u64>,
+    cache_creation_input_tokens: Option<u64>,
+    cache_read_input_tokens: Option<u64>,
+}
+
+impl AnthropicUsage {
+    /// Overrides the counts reported again in a later stream event.
+    fn update(&mut self, other: AnthropicUsage) {
+        self.input_tokens = other.input_tokens.or(self.input_tokens);
+        self.output_tokens = other.output_tokens.or(self.output_tokens);
+        self.cache_creation_input_tokens = other.cache_creation_input_tokens.or(self.cache_creation_input_tokens);
+        self.cache_read_input_tokens = other.cache_read_input_tokens.or(self.cache_read_input_tokens);
+    }
+}
+
+impl From<AnthropicUsage> for LLMTokenCounts {
+    fn from(usage: AnthropicUsage) -> Self {
+        // Cached prompt tokens are reported apart from `input_tokens` but are still prompt tokens
+        let prompt_tokens = usage.input_tokens.unwrap_or(0)
+            + usage.cache_creation_input_tokens.unwrap_or(0)
+            + usage.cache_read_input_tokens.unwrap_or(0);
+        let completion_tokens = usage.output_tokens.unwrap_or(0);
+        LLMTokenCounts { prompt_tokens, completion_tokens, total_tokens: prompt_tokens + completion_tokens }
+    }
+}
+
+/// Anthropic stop reason in chat completions terms, so callers see the same values for every provider.
+fn finish_reason(stop_reason: &str) -> String {
+    match stop_reason {
+        "end_turn" | "stop_sequence" => "stop",
+        "max_tokens" => "length",
+        "tool_use" => "tool_calls",
+        // Declined by the model's safety classifiers, reported like a filtered completion
+        "refusal" => "content_filter",
+        other => other,
+    }.to_string()
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicModelList {
+    data: Vec<AnthropicModel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicModel {
+    id: String,
+    display_name: Option<String>,
+}
+
+/// Server-sent event of a streamed Messages API call.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StreamEvent {
+    MessageStart { message: StreamMessageStart },
+    ContentBlockStart { index: usize, content_block: ContentBlock },
+    ContentBlockDelta { index: usize, delta: BlockDelta },
+    MessageDelta {
+        delta: MessageDeltaBody,
+        #[serde(default)]
+        usage: AnthropicUsage,
+    },
+    MessageStop,
+    Error { error: Value },
+    // ping and content_block_stop carry nothing needed here
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamMessageStart {
+    #[serde(default)]
+    usage: AnthropicUsage,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BlockDelta {
+    TextDelta { text: String },
+    // Piece of the JSON input of a tool_use block
+    InputJsonDelta { partial_json: String },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessageDeltaBody {
+    stop_reason: Option<String>,
+}
+
+/// Response assembled from the events of a stream.
+#[derive(Debug, Default)]
+struct MessageStream {
+    content: String,
+    tool_calls: Vec<LLMToolCall>,
+    // Content block index of each tool call, to route `input_json_delta` fragments
+    tool_call_blocks: HashMap<usize, usize>,
+    stop_reason: Option<String>,
+    usage: AnthropicUsage,
+}
+
+impl MessageStream {
+    /// Applies one event payload, forwarding text deltas through `chunk_sender`.
+    /// Returns false once the message is complete.
+    async fn apply(&mut self, payload: &str, chunk_sender: &mpsc::Sender<String>) -> Result<bool> {
+        match serde_json::from_str::<StreamEvent>(payload)? {
+            StreamEvent::MessageStart { message } => self.usage.update(message.usage),
+            StreamEvent::ContentBlockStart { index, content_block: ContentBlock::ToolUse { id, name, .. } } => {
+                // The input arrives through `input_json_delta` events; the one given here is empty
+                self.tool_call_blocks.insert(index, self.tool_calls.len());
+                self.tool_calls.push(LLMToolCall { id, name, arguments: String::new() });
+            }
+            StreamEvent::ContentBlockStart { .. } => {}
+            StreamEvent::ContentBlockDelta { delta: BlockDelta::TextDelta { text }, .. } => {
+                if !text.is_empty() {
+                    self.content.push_str(&text);
+                    let _ = chunk_sender.send(text).await;
+                }
+            }
+            StreamEvent::ContentBlockDelta { index, delta: BlockDelta::InputJsonDelta { partial_json } } => {
+                if let Some(call) = self.tool_call_blocks.get(&index).and_then(|position| self.tool_calls.get_mut(*position)) {
+                    call.arguments.push_str(&partial_json);
+                }
+            }
+            StreamEvent::ContentBlockDelta { .. } => {}
+            StreamEvent::MessageDelta { delta, usage } => {
+                self.stop_reason = delta.stop_reason.or(self.stop_reason.take());
+                self.usage.update(usage);
+            }
+            StreamEvent::MessageStop => return Ok(false),
+            StreamEvent::Error { error } => return Err(StreamError { message: error.to_string() }.into()),
+            StreamEvent::Other => {}
+        }
+        Ok(true)
+    }
+
+    fn into_response(mut self) -> LLMResponse {
+        for call in &mut self.tool_calls {
+            // Tools without parameters get no input deltas at all
+            if call.arguments.is_empty() {
+                call.arguments = "{}".to_string();
+            }
+        }
+        LLMResponse {
+            content: self.content,
+            usage: Some(self.usage.into()),
+            served_by: None,
+            cached: false,
+            tool_calls: self.tool_calls,
+            finish_reason: self.stop_reason.as_deref().map(finish_reason),
+        }
+    }
+}
+
+/// Provider calling the Anthropic Messages API directly, without going through OpenRouter.
+#[derive(Debug)]
+pub struct AnthropicProvider {
+    // None until a key comes from the environment, the credential store or `replace_api_key`
+    api_key: RwLock<Option<String>>,
+    client: reqwest::Client,
+    api_endpoint: String,
+    retry_config: RetryConfig,
+}
+
+impl Default for AnthropicProvider {
+    fn default() -> Self {
+        AnthropicProvider::new()
+    }
+}
+
+impl AnthropicProvider {
+    /// Registers the provider even without `ANTHROPIC_API_KEY`; it is then not configured until a key is set.
+    pub fn new() -> Self {
+        let api_key = env::var("ANTHROPIC_API_KEY").ok().filter(|key| !key.trim().is_empty());
+        let api_endpoint = env::var("ANTHROPIC_API_ENDPOINT")
+            .unwrap_or_else(|_| "https://api.anthropic.com/v1/messages".to_string());
+
+        let client = http_client();
+        AnthropicProvider { api_key: RwLock::new(api_key), client, api_endpoint, retry_config: RetryConfig::default() }
+    }
+
+    fn api_key(&self) -> Result<String, LLMError> {
+        self.api_key.read().ok()
+            .and_then(|api_key| api_key.clone())
+            .ok_or_else(|| LLMError::NotConfigured { provider: "Anthropic".to_string() })
+    }
+
+    pub fn with_retry_config(mut self, retry_config: RetryConfig) -> Self {
+        self.retry_config = retry_config;
+        self
+    }
+
+    fn models_endpoint(&self) -> String {
+        format!("{}/models", self.api_endpoint.trim_end_matches("/messages"))
+    }
+
+    fn build_request(request: LLMRequest, stream: bool) -> MessagesRequest {
+        let mut system = Vec::new();
+        let mut messages: Vec<AnthropicMessage> = Vec::new();
+
+        for message in request.messages {
+            let (role, blocks) = match message.role {
+                LLMRole::System => {
+                    system.push(ContentBlock::text(message.content));
+                    continue;
+                }
+                LLMRole::User => ("user", vec![ContentBlock::text(message.content)]),
+                LLMRole::Assistant => ("assistant", Self::assistant_blocks(message)),
+                // Tool results are sent back as blocks of a user turn
+                LLMRole::Tool => ("user", vec![ContentBlock::ToolResult {
+                    tool_use_id: message.tool_call_id.unwrap_or_default(),
+                    content: message.content,
+                }]),
+            };
+            if blocks.is_empty() {
+                continue;
+            }
+            // Roles must alternate, so consecutive turns of the same role (several tool results,
+            // or tool results followed by a prompt) are merged into one message
+            match messages.last_mut() {
+                Some(last) if last.role == role => last.content.extend(blocks),
+                _ => messages.push(AnthropicMessage { role: role.to_string(), content: blocks }),
+            }
+        }
+
+        // The system prompt is usually the stable part of the prompt, so it ends the cached prefix.
+        // Prompts shorter than the model's caching minimum are simply not cached.
+        if let Some(ContentBlock::Text { cache_control, .. }) = system.last_mut() {
+            *cache_control = Some(CacheControl { kind: "ephemeral".to_string() });
+        }
+
+        MessagesRequest {
+            model: request.model,
+            system,
+            messages,
+            max_tokens: request.parameters.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+            temperature: request.parameters.temperature,
+            top_p: request.parameters.top_p,
+            stop_sequences: request.parameters.stop,
+            tools: request.tools.iter().map(AnthropicTool::from).collect(),
+            stream,
+        }
+    }
+
+    fn assistant_blocks(message: LLMMessage) -> Vec<ContentBlock> {
+        let mut blocks = Vec::new();
+        // Empty text blocks are rejected by the API
+        if !message.content.is_empty() {
+            blocks.push(ContentBlock::text(message.content));
+        }
+        for call in message.tool_calls {
+            // Arguments the model generated as invalid JSON cannot be replayed as an input object
+            let input = serde_json::from_str(&call.arguments).unwrap_or_else(|_| json!({}));
+            blocks.push(ContentBlock::ToolUse { id: call.id, name: call.name, input });
+        }
+        blocks
+    }
+
+    fn authorized(&self, request_builder: reqwest::RequestBuilder, api_key: &str) -> reqwest::RequestBuilder {
+        request_builder
+            .header("x-api-key", api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+    }
+
+    async fn send(&self, messages_request: &MessagesRequest) -> Result<reqwest::Response, anyhow::Error> {
+        let response = self.authorized(self.client.post(&self.api_endpoint), &self.api_key()?)
+            .header("Content-Type", "application/json")
+            .json(messages_request)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(ProviderHttpError::from_response("Anthropic".to_string(), response).await?.into());
+        }
+
+        Ok(response)
+    }
+
+    async fn fetch_models(&self, api_key: &str) -> Result<AnthropicModelList, anyhow::Error> {
+        let response = self.authorized(self.client.get(self.models_endpoint()), api_key)
+            .query(&[("limit", "1000")])
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(ProviderHttpError::from_response("Anthropic".to_string(), response).await?.into());
+        }
+        let body = response.text().await?;
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    // Fails as truncated when the stream closes before `message_stop`
+    async fn read_stream(response: reqwest::Response, chunk_sender: &mpsc::Sender<String>) -> Result<LLMResponse> {
+        let mut decoder = SseDecoder::new();
+        let mut stream = MessageStream::default();
+        let mut byte_stream = response.bytes_stream();
+        let mut stopped = false;
+        'stream: while let Some(bytes) = byte_stream.next().await {
+            let bytes = bytes?;
+            for payload in decoder.push(&bytes) {
+                if !stream.apply(&payload, chunk_sender).await? {
+                    stopped = true;
+                    break 'stream;
+                }
+            }
+        }
+        if !stopped {
+            if let Some(payload) = decoder.finish() {
+                stopped = !stream.apply(&payload, chunk_sender).await?;
+            }
+        }
+
+        if !stopped {
+            return Err(LLMError::MalformedResponse {
+                provider: "Anthropic".to_string(),
+                truncated: true,
+                message: "Stream closed before message_stop".to_string(),
+            }.into());
+        }
+        Ok(stream.into_response())
+    }
+}
+
+#[async_trait]
+impl LLMProvider for AnthropicProvider {
+    fn name(&self) -> String {
+        "Anthropic".to_string()
+    }
+
+    fn is_configured(&self) -> bool {
+        self.api_key().is_ok()
+    }
+
+    async fn check_api_key(&self, api_key: &str) -> Result<(), anyhow::Error> {
+        // Listing models requires authentication, which makes it a cheap key check
+        self.fetch_models(api_key).await.map(|_| ())
+    }
+
+    fn replace_api_key(&self, api_key: Option<String>) {
+        if let Ok(mut current) = self.api_key.write() {
+            *current = api_key;
+        }
+    }
+
+    fn current_api_key(&self) -> Option<String> {
+        self.api_key().ok()
+    }
+
+    async fn generate(&self, request: LLMRequest) -> Result<LLMResponse, LLMError> {
+        let messages_request = &Self::build_request(request, false);
+        // The body is read inside the retried block so a response cut off mid-JSON is retried too
+        let messages_response: MessagesResponse = with_retries(&self.retry_config, "Anthropic", || async move {
+            let response = self.send(messages_request).await?;
+            let body = response.text().await?;
+            Ok(serde_json::from_str(&body)?)
+        }).await?;
+
+        let mut content = String::new();
+        let mut tool_calls = Vec::new();
+        for block in messages_response.content {
+            match block {
+                ContentBlock::Text { text, .. } => content.push_str(&text),
+                ContentBlock::ToolUse { id, name, input } => tool_calls.push(LLMToolCall { id, name, arguments: input.to_string() }),
+                ContentBlock::ToolResult { .. } | ContentBlock::Other => {}
+            }
+        }
+
+        Ok(LLMResponse {
+            content,
+            usage: Some(messages_response.usage.into()),
+            served_by: None,
+            cached: false,
+            tool_calls,
+            finish_reason: messages_response.stop_reason.as_deref().map(finish_reason),
+        })
+    }
+
+    async fn generate_stream(&self, request: LLMRequest, chunk_sender: mpsc::Sender<String>) -> Result<LLMResponse, LLMError> {
+        let messages_request = &Self::build_request(request, true);
+        // Only establishing the stream is retried; chunks may already have been forwarded afterwards
+        let response = with_retries(&self.retry_config, "Anthropic", || self.send(messages_request)).await?;
+        Self::read_stream(response, &chunk_sender).await
+            .map_err(|e| LLMError::from_anyhow("Anthropic", e))
+    }
+
+    async fn list_models(&self) -> Result<Vec<LLMModelInfo>, anyhow::Error> {
+        let api_key = self.api_key()?;
+        let model_list = with_retries(&self.retry_config, "Anthropic", || self.fetch_models(&api_key)).await?;
+        Ok(model_list.data.into_iter()
+            .map(|model| LLMModelInfo { id: model.id, name: model.display_name, context_length: None, pricing: None, modality: None })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::external_api_client::providers::test_server::serve_once;
+    use crate::external_api_client::LLMParameters;
+
+    fn provider(api_endpoint: String) -> AnthropicProvider {
+        AnthropicProvider { api_key: RwLock::new(Some("test-key".to_string())), api_endpoint, ..AnthropicProvider::new() }
+    }
+
+    #[test]
+    fn test_stop_reasons_use_chat_completions_terms() {
+        assert_eq!(finish_reason("end_turn"), "stop");
+        assert_eq!(finish_reason("max_tokens"), "length");
+        assert_eq!(finish_reason("tool_use"), "tool_calls");
+        assert_eq!(finish_reason("refusal"), "content_filter");
+        assert_eq!(finish_reason("pause_turn"), "pause_turn");
+    }
+
+    #[test]
+    fn test_build_request_maps_system_tools_and_parameters() {
+        let call = LLMToolCall { id: "toolu_1".to_string(), name: "current_datetime".to_string(), arguments: "{}".to_string() };
+        let request = LLMRequest {
+            model: "claude-3-5-haiku-latest".to_string(),
+            messages: vec![
+                LLMMessage::system("Be brief."),
+                LLMMessage::user("What time is it?"),
+                LLMMessage::assistant_tool_calls("", vec![call]),
+                LLMMessage::tool_result("toolu_1", "12:00"),
+                LLMMessage::user("Answer now."),
+            ],
+            parameters: LLMParameters { stop: Some(vec!["END".to_string()]), seed: Some(7), ..Default::default() },
+            tools: Vec::new(),
+        };
+
+        let body = serde_json::to_value(AnthropicProvider::build_request(request, false)).unwrap();
+        assert_eq!(body["system"], json!([{ "type": "text", "text": "Be brief.", "cache_control": { "type": "ephemeral" } }]));
+        assert_eq!(body["messages"], json!([
+            { "role": "user", "content": [{ "type": "text", "text": "What time is it?" }] },
+            { "role": "assistant", "content": [{ "type": "tool_use", "id": "toolu_1", "name": "current_datetime", "input": {} }] },
+            { "role": "user", "content": [
+                { "type": "tool_result", "tool_use_id": "toolu_1", "content": "12:00" },
+                { "type": "text", "text": "Answer now." }
+            ] }
+        ]));
+        assert_eq!(body["max_tokens"], json!(DEFAULT_MAX_TOKENS));
+        assert_eq!(body["stop_sequences"], json!(["END"]));
+        // Parameters the API does not support are not sent
+        assert!(body.get("seed").is_none());
+        assert!(body.get("stream").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_generate_against_local_stand_in() {
+        let (url, server) = serve_once("/v1/messages", "application/json", json!({
+            "id": "msg_1",
+            "type": "message",
+            "role": "assistant",
+            "content": [
+                { "type": "text", "text": "Let me check." },
+                { "type": "tool_use", "id": "toolu_1", "name": "current_datetime", "input": {} }
+            ],
+            "stop_reason": "tool_use",
+            "usage": { "input_tokens": 20, "output_tokens": 5, "cache_read_input_tokens": 100 }
+        }).to_string()).await;
+
+        let response = provider(url).generate(LLMRequest::from_prompt("claude-3-5-haiku-latest", "Hello", None)).await.unwrap();
+        assert_eq!(response.content, "Let me check.");
+        assert_eq!(response.tool_calls, vec![LLMToolCall { id: "toolu_1".to_string(), name: "current_datetime".to_string(), arguments: "{}".to_string() }]);
+        assert_eq!(response.finish_reason.as_deref(), Some("tool_calls"));
+        assert_eq!(response.usage, Some(LLMTokenCounts { prompt_tokens: 120, completion_tokens: 5, total_tokens: 125 }));
+
+        let received = server.await.unwrap().to_ascii_lowercase();
+        assert!(received.contains("x-api-key: test-key"));
+        assert!(received.contains(&format!("anthropic-version: {}", ANTHROPIC_VERSION)));
+    }
+
+    #[tokio::test]
+    async fn test_generate_stream_against_local_stand_in() {
+        let events = [
+            json!({ "type": "message_start", "message": { "usage": { "input_tokens": 12, "output_tokens": 1 } } }),
+            json!({ "type": "content_block_start", "index": 0, "content_block": { "type": "text", "text": "" } }),
+            json!({ "type": "ping" }),
+            json!({ "type": "content_block_delta", "index": 0, "delta": { "type": "text_delta", "text": "Hel" } }),
+            json!({ "type": "content_block_delta", "index": 0, "delta": { "type": "text_delta", "text": "lo" } }),
+            json!({ "type": "content_block_stop", "index": 0 }),
+            json!({ "type": "message_delta", "delta": { "stop_reason": "max_tokens" }, "usage": { "output_tokens": 2 } }),
+            json!({ "type": "message_stop" }),
+        ];
+        let body = events.iter().map(|event| format!("event: {}\ndata: {}\n\n", event["type"].as_str().unwrap(), event)).collect();
+        let (url, _server) = serve_once("/v1/messages", "text/event-stream", body).await;
+
+        let (chunk_sender, mut chunk_receiver) = mpsc::channel(8);
+        let response = provider(url).generate_stream(LLMRequest::from_prompt("claude-3-5-haiku-latest", "Hello", None), chunk_sender).await.unwrap();
+        assert_eq!(response.content, "Hello");
+        assert_eq!(response.finish_reason.as_deref(), Some("length"));
+        assert_eq!(response.usage, Some(LLMTokenCounts { prompt_tokens: 12, completion_tokens: 2, total_tokens: 14 }));
+        assert_eq!(chunk_receiver.recv().await, Some("Hel".to_string()));
+        assert_eq!(chunk_receiver.recv().await, Some("lo".to_string()));
+        assert_eq!(chunk_receiver.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_stream_closed_before_message_stop_is_truncated() {
+        let events = [
+            json!({ "type": "message_start", "message": { "usage": { "input_tokens": 12, "output_tokens": 1 } } }),
+            json!({ "type": "content_block_delta", "index": 0, "delta": { "type": "text_delta", "text": "Half a sen" } }),
+        ];
+        let body = events.iter().map(|event| format!("event: {}\ndata: {}\n\n", event["type"].as_str().unwrap(), event)).collect();
+        let (url, _server) = serve_once("/v1/messages", "text/event-stream", body).await;
+
+        let (chunk_sender, _chunk_receiver) = mpsc::channel(8);
+        let result = provider(url).generate_stream(LLMRequest::from_prompt("claude-3-5-haiku-latest", "Hello", None), chunk_sender).await;
+        assert!(matches!(result, Err(LLMError::MalformedResponse { truncated: true, .. })), "{:?}", result);
+    }
+}
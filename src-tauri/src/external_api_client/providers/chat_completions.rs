@@ -0,0 +1,243 @@
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use super::error::LLMError;
+use super::retry::{with_retries, RetryConfig};
+use super::sse::{collect_chat_completion_stream, StreamOptions};
+use super::{LLMMessage, LLMParameters, LLMRequest, LLMResponse, LLMTokenCounts, LLMTool, LLMToolCall, ProviderHttpError};
+
+/// Body of a chat completions request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatCompletionRequest {
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<StreamOptions>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<ChatTool>,
+    #[serde(flatten)]
+    pub parameters: LLMParameters,
+}
+
+impl ChatCompletionRequest {
+    pub fn new(request: LLMRequest, stream: bool) -> Self {
+        ChatCompletionRequest {
+            messages: request.messages.iter().map(ChatMessage::from).collect(),
+            tools: request.tools.iter().map(ChatTool::from).collect(),
+            model: request.model,
+            stream,
+            // Ask for a final usage chunk so streamed calls are accounted like the others
+            stream_options: stream.then_some(StreamOptions { include_usage: true }),
+            parameters: request.parameters,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ChatCompletionResponse {
+    choices: Vec<ChatCompletionChoice>,
+    usage: Option<LLMTokenCounts>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ChatCompletionChoice {
+    message: ChatMessage,
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
+/// A chat completions endpoint of an OpenAI-style provider, with how to authenticate and retry.
+pub struct ChatCompletionsEndpoint<'a> {
+    pub client: &'a reqwest::Client,
+    pub provider: &'a str,
+    pub url: &'a str,
+    // Header name and full header value
+    pub auth_header: Option<(&'a str, &'a str)>,
+    pub retry_config: &'a RetryConfig,
+}
+
+impl ChatCompletionsEndpoint<'_> {
+    pub async fn generate(&self, request: ChatCompletionRequest) -> Result<LLMResponse, LLMError> {
+        let request = &request;
+        // The body is read inside the retried block so a response cut off mid-JSON is retried too
+        let response: ChatCompletionResponse = with_retries(self.retry_config, self.provider, || async move {
+            let body = self.send(request).await?.text().await?;
+            Ok(serde_json::from_str(&body)?)
+        }).await?;
+
+        let Some(choice) = response.choices.into_iter().next() else {
+            return Err(LLMError::MalformedResponse {
+                provider: self.provider.to_string(),
+                truncated: false,
+                message: "API returned no choices".to_string(),
+            });
+        };
+        let (content, tool_calls) = choice.message.into_content_and_tool_calls();
+        Ok(LLMResponse {
+            content,
+            usage: response.usage,
+            served_by: None,
+            cached: false,
+            tool_calls,
+            finish_reason: choice.finish_reason,
+        })
+    }
+
+    pub async fn generate_stream(&self, request: ChatCompletionRequest, chunk_sender: &mpsc::Sender<String>) -> Result<LLMResponse, LLMError> {
+        let request = &request;
+        // Only establishing the stream is retried; chunks may already have been forwarded afterwards
+        let response = with_retries(self.retry_config, self.provider, || self.send(request)).await?;
+        let completion = collect_chat_completion_stream(self.provider, response, chunk_sender).await
+            .map_err(|e| LLMError::from_anyhow(self.provider, e))?;
+
+        Ok(LLMResponse {
+            content: completion.content,
+            usage: completion.usage,
+            served_by: None,
+            cached: false,
+            tool_calls: completion.tool_calls,
+            finish_reason: completion.finish_reason,
+        })
+    }
+
+    async fn send(&self, request: &ChatCompletionRequest) -> Result<reqwest::Response, anyhow::Error> {
+        let mut request_builder = self.client.post(self.url)
+            .header("Content-Type", "application/json")
+            .json(request);
+        if let Some((header, value)) = self.auth_header {
+            request_builder = request_builder.header(header, value);
+        }
+
+        let response = request_builder.send().await?;
+        if !response.status().is_success() {
+            return Err(ProviderHttpError::from_response(self.provider.to_string(), response).await?.into());
+        }
+        Ok(response)
+    }
+}
+
+/// Message in the OpenAI chat completions format, shared by the OpenAI-style providers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: String,
+    // Null in assistant messages that only call tools
+    #[serde(default)]
+    pub content: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ChatToolCall>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatToolCall {
+    pub id: String,
+    #[serde(rename = "type", default = "function_type")]
+    pub kind: String,
+    pub function: ChatFunctionCall,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatFunctionCall {
+    pub name: String,
+    // JSON-encoded arguments
+    #[serde(default)]
+    pub arguments: String,
+}
+
+/// Entry of the `tools` array of a request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatTool {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub function: ChatFunctionDefinition,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatFunctionDefinition {
+    pub name: String,
+    pub description: String,
+    pub parameters: serde_json::Value,
+}
+
+fn function_type() -> String {
+    "function".to_string()
+}
+
+impl From<&LLMMessage> for ChatMessage {
+    fn from(message: &LLMMessage) -> Self {
+        let tool_calls = (!message.tool_calls.is_empty())
+            .then(|| message.tool_calls.iter().map(ChatToolCall::from).collect());
+        // An assistant turn made only of tool calls carries no text
+        let content = if tool_calls.is_some() && message.content.is_empty() {
+            None
+        } else {
+            Some(message.content.clone())
+        };
+        ChatMessage {
+            role: message.role.as_str().to_string(),
+            content,
+            tool_calls,
+            tool_call_id: message.tool_call_id.clone(),
+        }
+    }
+}
+
+impl ChatMessage {
+    /// Text and tool calls of an assistant message from a response.
+    pub fn into_content_and_tool_calls(self) -> (String, Vec<LLMToolCall>) {
+        let tool_calls = self.tool_calls.unwrap_or_default()
+            .into_iter()
+            .map(|call| LLMToolCall { id: call.id, name: call.function.name, arguments: call.function.arguments })
+            .collect();
+        (self.content.unwrap_or_default(), tool_calls)
+    }
+}
+
+impl From<&LLMToolCall> for ChatToolCall {
+    fn from(call: &LLMToolCall) -> Self {
+        ChatToolCall {
+            id: call.id.clone(),
+            kind: function_type(),
+            function: ChatFunctionCall { name: call.name.clone(), arguments: call.arguments.clone() },
+        }
+    }
+}
+
+impl From<&LLMTool> for ChatTool {
+    fn from(tool: &LLMTool) -> Self {
+        ChatTool {
+            kind: function_type(),
+            function: ChatFunctionDefinition {
+                name: tool.name.clone(),
+                description: tool.description.clone(),
+                parameters: tool.parameters.clone(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_tool_call_round_trip() {
+        let call = LLMToolCall { id: "call_1".to_string(), name: "current_datetime".to_string(), arguments: "{}".to_string() };
+        let assistant = serde_json::to_value(ChatMessage::from(&LLMMessage::assistant_tool_calls("", vec![call.clone()]))).unwrap();
+        assert_eq!(assistant, json!({
+            "role": "assistant",
+            "content": null,
+            "tool_calls": [ { "id": "call_1", "type": "function", "function": { "name": "current_datetime", "arguments": "{}" } } ]
+        }));
+
+        let result = serde_json::to_value(ChatMessage::from(&LLMMessage::tool_result("call_1", "2024-01-01T00:00:00Z"))).unwrap();
+        assert_eq!(result, json!({ "role": "tool", "content": "2024-01-01T00:00:00Z", "tool_call_id": "call_1" }));
+
+        let response_message: ChatMessage = serde_json::from_value(assistant).unwrap();
+        assert_eq!(response_message.into_content_and_tool_calls(), (String::new(), vec![call]));
+    }
+}
@@ -0,0 +1,116 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use super::{LLMEmbeddings, LLMTokenCounts, ProviderHttpError};
+
+/// Body of an OpenAI-compatible `/embeddings` request.
+#[derive(Debug, Clone, Serialize)]
+struct EmbeddingsRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingsResponse {
+    data: Vec<EmbeddingData>,
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    usage: Option<EmbeddingsUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingData {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+// Embedding calls only consume prompt tokens
+#[derive(Debug, Deserialize)]
+struct EmbeddingsUsage {
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    total_tokens: u64,
+}
+
+impl EmbeddingsResponse {
+    /// Vectors in the order of the input texts; the API may return them in any order.
+    fn into_embeddings(mut self, requested_model: &str, text_count: usize) -> Result<LLMEmbeddings> {
+        if self.data.len() != text_count {
+            return Err(anyhow!("Embeddings API returned {} vectors for {} texts", self.data.len(), text_count));
+        }
+        self.data.sort_by_key(|data| data.index);
+        Ok(LLMEmbeddings {
+            vectors: self.data.into_iter().map(|data| data.embedding).collect(),
+            model: self.model.unwrap_or_else(|| requested_model.to_string()),
+            usage: self.usage.map(|usage| LLMTokenCounts {
+                prompt_tokens: usage.prompt_tokens,
+                completion_tokens: 0,
+                total_tokens: usage.total_tokens,
+            }),
+        })
+    }
+}
+
+/// Posts `texts` to an OpenAI-compatible `/embeddings` endpoint. `auth_header` is a header name and its full value.
+pub async fn request_embeddings(
+    client: &reqwest::Client,
+    provider_name: &str,
+    url: &str,
+    auth_header: Option<(&str, &str)>,
+    texts: &[String],
+    model: &str,
+) -> Result<LLMEmbeddings> {
+    let mut request_builder = client.post(url)
+        .header("Content-Type", "application/json")
+        .json(&EmbeddingsRequest { model, input: texts });
+    if let Some((header, value)) = auth_header {
+        request_builder = request_builder.header(header, value);
+    }
+
+    let response = request_builder.send().await?;
+    if !response.status().is_success() {
+        return Err(ProviderHttpError::from_response(provider_name.to_string(), response).await?.into());
+    }
+    let body = response.text().await?;
+    let embeddings_response: EmbeddingsResponse = serde_json::from_str(&body)?;
+    embeddings_response.into_embeddings(model, texts.len())
+}
+
+/// Cosine similarity of two vectors, 0.0 when either is null or their lengths differ.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_vectors_follow_input_order() {
+        let response: EmbeddingsResponse = serde_json::from_value(serde_json::json!({
+            "object": "list",
+            "data": [
+                { "object": "embedding", "index": 1, "embedding": [0.0, 1.0] },
+                { "object": "embedding", "index": 0, "embedding": [1.0, 0.0] }
+            ],
+            "usage": { "prompt_tokens": 6, "total_tokens": 6 }
+        })).unwrap();
+
+        let embeddings = response.into_embeddings("text-embedding-3-small", 2).unwrap();
+        assert_eq!(embeddings.vectors, vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+        assert_eq!(embeddings.model, "text-embedding-3-small");
+        assert_eq!(embeddings.usage.map(|usage| usage.prompt_tokens), Some(6));
+        assert_eq!(cosine_similarity(&embeddings.vectors[0], &embeddings.vectors[1]), 0.0);
+    }
+}
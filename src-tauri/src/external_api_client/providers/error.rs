@@ -0,0 +1,168 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use super::sse::StreamError;
+use super::ProviderHttpError;
+
+/// Why a provider failed to generate a completion. Callers use the variant to decide
+/// whether to retry, fall back to another route or give up on the task.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, thiserror::Error)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LLMError {
+    #[error("{provider} rejected the credentials (status {status}): {message}")]
+    Auth { provider: String, status: u16, message: String },
+    #[error("Provider '{provider}' is not configured: set its API key")]
+    NotConfigured { provider: String },
+    #[error("{provider} rate limit reached: {message}")]
+    RateLimited { provider: String, retry_after: Option<Duration>, message: String },
+    #[error("Prompt does not fit in the context window of the {provider} model: {message}")]
+    ContextLength { provider: String, message: String },
+    #[error("{provider} does not know the requested model: {message}")]
+    ModelNotFound { provider: String, message: String },
+    #[error("{provider} blocked the request or the completion by content filtering: {message}")]
+    ContentFiltered { provider: String, message: String },
+    // Network failures, timeouts, 5xx and errors reported in the middle of a stream
+    #[error("{provider} call failed{}: {message}", status.map(|status| format!(" (status {})", status)).unwrap_or_default())]
+    Transport { provider: String, status: Option<u16>, timeout: bool, retry_after: Option<Duration>, message: String },
+    // `truncated` when the body was cut off mid-JSON, which a retry usually fixes
+    #[error("{provider} returned a malformed response: {message}")]
+    MalformedResponse { provider: String, truncated: bool, message: String },
+    // The task the call was made for was cancelled; the in-flight request was dropped
+    #[error("Call to {provider} was cancelled")]
+    Cancelled { provider: String },
+    // Other 4xx and requests that cannot be sent as built (unknown provider, missing model...)
+    #[error("{provider} rejected the request{}: {message}", status.map(|status| format!(" (status {})", status)).unwrap_or_default())]
+    InvalidRequest { provider: String, status: Option<u16>, message: String },
+}
+
+impl LLMError {
+    /// Classifies an error raised while talking to `provider`. Errors that are already
+    /// an `LLMError` are kept; anything unrecognized is treated as a malformed response.
+    pub fn from_anyhow(provider: &str, error: anyhow::Error) -> Self {
+        let error = match error.downcast::<LLMError>() {
+            Ok(llm_error) => return llm_error,
+            Err(error) => error,
+        };
+        if let Some(http_error) = error.downcast_ref::<ProviderHttpError>() {
+            return Self::from_http(http_error);
+        }
+        if let Some(stream_error) = error.downcast_ref::<StreamError>() {
+            return LLMError::Transport { provider: provider.to_string(), status: None, timeout: false, retry_after: None, message: stream_error.message.clone() };
+        }
+        if let Some(reqwest_error) = error.downcast_ref::<reqwest::Error>() {
+            if reqwest_error.is_decode() {
+                return LLMError::MalformedResponse { provider: provider.to_string(), truncated: false, message: reqwest_error.to_string() };
+            }
+            return LLMError::Transport {
+                provider: provider.to_string(),
+                status: reqwest_error.status().map(|status| status.as_u16()),
+                timeout: reqwest_error.is_timeout(),
+                retry_after: None,
+                message: reqwest_error.to_string(),
+            };
+        }
+        if let Some(json_error) = error.downcast_ref::<serde_json::Error>() {
+            return LLMError::MalformedResponse { provider: provider.to_string(), truncated: json_error.is_eof(), message: json_error.to_string() };
+        }
+        LLMError::MalformedResponse { provider: provider.to_string(), truncated: false, message: error.to_string() }
+    }
+
+    /// Classifies a non-success HTTP response from its status and, for the statuses
+    /// providers reuse for several causes, its body.
+    pub fn from_http(http_error: &ProviderHttpError) -> Self {
+        let provider = http_error.provider.clone();
+        let message = http_error.body.clone();
+        let body = http_error.body.to_lowercase();
+        let mentions_content_filter = ["moderation", "flagged", "content_filter", "content policy", "safety"]
+            .iter()
+            .any(|marker| body.contains(marker));
+        let mentions_context_length = ["context length", "context_length", "context window", "maximum context", "too many tokens", "prompt is too long"]
+            .iter()
+            .any(|marker| body.contains(marker));
+        let mentions_unknown_model = body.contains("model") && ["not found", "not_found", "does not exist", "invalid model", "no endpoints found", "not a valid model"]
+            .iter()
+            .any(|marker| body.contains(marker));
+
+        match http_error.status {
+            // OpenRouter answers 403 when its moderation flags the input
+            401..=403 if mentions_content_filter => LLMError::ContentFiltered { provider, message },
+            // 402 is "insufficient credits", which only the account owner can fix
+            401..=403 => LLMError::Auth { provider, status: http_error.status, message },
+            429 => LLMError::RateLimited { provider, retry_after: http_error.retry_after, message },
+            404 => LLMError::ModelNotFound { provider, message },
+            413 => LLMError::ContextLength { provider, message },
+            400 | 422 if mentions_context_length => LLMError::ContextLength { provider, message },
+            400 | 422 if mentions_unknown_model => LLMError::ModelNotFound { provider, message },
+            400 | 422 if mentions_content_filter => LLMError::ContentFiltered { provider, message },
+            408 => LLMError::Transport { provider, status: Some(408), timeout: true, retry_after: http_error.retry_after, message },
+            status if status >= 500 => LLMError::Transport { provider, status: Some(status), timeout: false, retry_after: http_error.retry_after, message },
+            status => LLMError::InvalidRequest { provider, status: Some(status), message },
+        }
+    }
+
+    pub fn provider(&self) -> &str {
+        match self {
+            LLMError::Auth { provider, .. }
+            | LLMError::NotConfigured { provider }
+            | LLMError::RateLimited { provider, .. }
+            | LLMError::ContextLength { provider, .. }
+            | LLMError::ModelNotFound { provider, .. }
+            | LLMError::ContentFiltered { provider, .. }
+            | LLMError::Transport { provider, .. }
+            | LLMError::MalformedResponse { provider, .. }
+            | LLMError::Cancelled { provider }
+            | LLMError::InvalidRequest { provider, .. } => provider,
+        }
+    }
+
+    /// Whether the same call may succeed if repeated right away: network and server failures,
+    /// and responses cut off mid-body. Rate limits are not retried here; the client pauses the
+    /// provider and queues the call again, honouring `Retry-After`.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, LLMError::Transport { .. } | LLMError::MalformedResponse { truncated: true, .. })
+    }
+
+    /// Delay the provider asked for before the next attempt, from its `Retry-After` header.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            LLMError::RateLimited { retry_after, .. } | LLMError::Transport { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn http_error(status: u16, body: &str) -> LLMError {
+        LLMError::from_http(&ProviderHttpError { provider: "Test".to_string(), status, body: body.to_string(), retry_after: None })
+    }
+
+    #[test]
+    fn test_http_errors_are_classified() {
+        assert!(matches!(http_error(401, r#"{"error":{"message":"Invalid API key"}}"#), LLMError::Auth { status: 401, .. }));
+        assert!(matches!(http_error(403, r#"{"error":{"message":"Input was flagged by moderation"}}"#), LLMError::ContentFiltered { .. }));
+        assert!(matches!(http_error(429, "slow down"), LLMError::RateLimited { .. }));
+        assert!(matches!(http_error(404, ""), LLMError::ModelNotFound { .. }));
+        assert!(matches!(http_error(400, r#"{"error":{"message":"The model `gpt-9` does not exist"}}"#), LLMError::ModelNotFound { .. }));
+        assert!(matches!(http_error(400, "This model's maximum context length is 8192 tokens"), LLMError::ContextLength { .. }));
+        assert!(matches!(http_error(400, "temperature must be between 0 and 2"), LLMError::InvalidRequest { status: Some(400), .. }));
+        assert!(matches!(http_error(408, ""), LLMError::Transport { timeout: true, .. }));
+        assert!(matches!(http_error(503, "overloaded"), LLMError::Transport { status: Some(503), .. }));
+
+        assert!(http_error(503, "").is_retryable());
+        // Requeued by the rate limiter instead
+        assert!(!http_error(429, "").is_retryable());
+        assert!(!http_error(401, "").is_retryable());
+        assert!(!http_error(400, "").is_retryable());
+    }
+
+    #[test]
+    fn test_truncated_body_is_retryable() {
+        let truncated = serde_json::from_str::<serde_json::Value>(r#"{"choices": [{"mess"#).unwrap_err();
+        assert!(LLMError::from_anyhow("Test", truncated.into()).is_retryable());
+        let malformed = serde_json::from_str::<serde_json::Value>(r#"{"choices": oops}"#).unwrap_err();
+        assert!(!LLMError::from_anyhow("Test", malformed.into()).is_retryable());
+    }
+}
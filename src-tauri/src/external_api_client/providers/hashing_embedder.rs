@@ -0,0 +1,106 @@
+use async_trait::async_trait;
+use anyhow::Result;
+
+use super::error::LLMError;
+use super::{LLMEmbeddings, LLMProvider, LLMRequest, LLMResponse};
+
+/// Number of dimensions of the vectors produced by `HashingEmbedder::default()`.
+pub const DEFAULT_HASHING_DIMENSIONS: usize = 256;
+
+/// Offline embedder using the hashing trick: word unigrams and bigrams are hashed into a
+/// fixed number of signed buckets, then the vector is L2-normalized. The vectors only capture
+/// shared vocabulary, not meaning, but they are deterministic and need no network or model,
+/// which makes them usable as a fallback and in tests. It cannot generate completions.
+#[derive(Debug, Clone)]
+pub struct HashingEmbedder {
+    dimensions: usize,
+}
+
+impl Default for HashingEmbedder {
+    fn default() -> Self {
+        HashingEmbedder::new(DEFAULT_HASHING_DIMENSIONS)
+    }
+}
+
+impl HashingEmbedder {
+    pub fn new(dimensions: usize) -> Self {
+        HashingEmbedder { dimensions: dimensions.max(1) }
+    }
+
+    /// Model name reported with the vectors, so vectors of different sizes are not compared.
+    pub fn model_name(&self) -> String {
+        format!("hashing-{}", self.dimensions)
+    }
+
+    pub fn embed_text(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0f32; self.dimensions];
+        let words: Vec<String> = text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(|word| word.to_lowercase())
+            .collect();
+
+        let bigrams = words.windows(2).map(|pair| format!("{} {}", pair[0], pair[1]));
+        for feature in words.iter().cloned().chain(bigrams) {
+            let hash = fnv1a(feature.as_bytes());
+            let bucket = (hash % self.dimensions as u64) as usize;
+            // A bit of the hash picks the sign, so colliding features tend to cancel out
+            let sign = if (hash >> 63) == 0 { 1.0 } else { -1.0 };
+            vector[bucket] += sign;
+        }
+
+        let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|x| *x /= norm);
+        }
+        vector
+    }
+}
+
+// 64-bit FNV-1a, stable across platforms and Rust versions unlike `DefaultHasher`
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+#[async_trait]
+impl LLMProvider for HashingEmbedder {
+    fn name(&self) -> String {
+        "Hashing".to_string()
+    }
+
+    async fn generate(&self, _request: LLMRequest) -> Result<LLMResponse, LLMError> {
+        Err(LLMError::InvalidRequest {
+            provider: self.name(),
+            status: None,
+            message: "The Hashing provider only computes embeddings".to_string(),
+        })
+    }
+
+    async fn embed(&self, texts: &[String], _model: &str) -> Result<LLMEmbeddings, anyhow::Error> {
+        Ok(LLMEmbeddings {
+            vectors: texts.iter().map(|text| self.embed_text(text)).collect(),
+            model: self.model_name(),
+            usage: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::external_api_client::providers::embeddings::cosine_similarity;
+
+    #[test]
+    fn test_embeddings_are_deterministic_and_normalized() {
+        let embedder = HashingEmbedder::default();
+        let vector = embedder.embed_text("Rust async runtimes");
+        assert_eq!(vector.len(), DEFAULT_HASHING_DIMENSIONS);
+        assert_eq!(vector, embedder.embed_text("rust ASYNC runtimes!"));
+        assert!((vector.iter().map(|x| x * x).sum::<f32>() - 1.0).abs() < 1e-5);
+        assert!(embedder.embed_text("").iter().all(|x| *x == 0.0));
+
+        let related = embedder.embed_text("comparing async runtimes in Rust");
+        let unrelated = embedder.embed_text("a recipe for lemon cake");
+        assert!(cosine_similarity(&vector, &related) > cosine_similarity(&vector, &unrelated));
+    }
+}
@@ -1,25 +1,331 @@
 use async_trait::async_trait;
+use std::time::Duration;
+use tokio::sync::mpsc;
 
+use super::pricing::ModelPrice;
+use super::rate_limit::parse_retry_after;
+use super::routing::LLMRoute;
+use error::LLMError;
+
+pub mod anthropic_provider;
+pub mod chat_completions;
+pub mod embeddings;
+pub mod error;
+pub mod hashing_embedder;
+pub mod openai_compatible_provider;
 pub mod openrouter_provider;
 pub mod requesty_provider;
+pub mod retry;
+pub mod scripted_provider;
+pub mod sse;
+#[cfg(test)]
+pub(crate) mod test_server;
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq)]
 pub struct LLMRequest {
     pub model: String,
-    pub prompt: String,
-    // Autres paramètres comme temperature, max_tokens, etc.
-    pub system_prompt: Option<String>,
+    // Conversation sent to the model, oldest message first
+    pub messages: Vec<LLMMessage>,
+    #[serde(default)]
+    pub parameters: LLMParameters,
+    // Functions the model may ask to call instead of answering directly
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<LLMTool>,
+}
+
+impl LLMRequest {
+    /// Request made of an optional system prompt followed by a single user prompt.
+    pub fn from_prompt(model: impl Into<String>, prompt: impl Into<String>, system_prompt: Option<String>) -> Self {
+        let mut messages = Vec::new();
+        if let Some(system_prompt) = system_prompt {
+            messages.push(LLMMessage::system(system_prompt));
+        }
+        messages.push(LLMMessage::user(prompt));
+        LLMRequest { model: model.into(), messages, parameters: LLMParameters::default(), tools: Vec::new() }
+    }
+
+    /// Content of the last user message, or an empty string when there is none.
+    pub fn last_user_prompt(&self) -> &str {
+        self.messages.iter().rev()
+            .find(|message| message.role == LLMRole::User)
+            .map_or("", |message| message.content.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LLMRole {
+    System,
+    User,
+    Assistant,
+    // Result of a tool call, answering the assistant message that requested it
+    Tool,
+}
+
+impl LLMRole {
+    /// Role name in the OpenAI chat completions API.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LLMRole::System => "system",
+            LLMRole::User => "user",
+            LLMRole::Assistant => "assistant",
+            LLMRole::Tool => "tool",
+        }
+    }
+}
+
+/// One role-tagged message of a chat conversation.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+pub struct LLMMessage {
+    pub role: LLMRole,
+    pub content: String,
+    // Calls requested by an assistant message
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<LLMToolCall>,
+    // Call answered by a tool message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+impl LLMMessage {
+    fn new(role: LLMRole, content: impl Into<String>) -> Self {
+        LLMMessage { role, content: content.into(), tool_calls: Vec::new(), tool_call_id: None }
+    }
+
+    pub fn system(content: impl Into<String>) -> Self {
+        Self::new(LLMRole::System, content)
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self::new(LLMRole::User, content)
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self::new(LLMRole::Assistant, content)
+    }
+
+    /// Assistant turn that requested tool calls, to be followed by one `tool_result` per call.
+    pub fn assistant_tool_calls(content: impl Into<String>, tool_calls: Vec<LLMToolCall>) -> Self {
+        LLMMessage { tool_calls, ..Self::new(LLMRole::Assistant, content) }
+    }
+
+    pub fn tool_result(tool_call_id: impl Into<String>, content: impl Into<String>) -> Self {
+        LLMMessage { tool_call_id: Some(tool_call_id.into()), ..Self::new(LLMRole::Tool, content) }
+    }
+}
+
+/// Local function offered to the model. `parameters` is the JSON Schema of its arguments.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+pub struct LLMTool {
+    pub name: String,
+    pub description: String,
+    pub parameters: serde_json::Value,
+}
+
+/// Tool call requested by the model. `arguments` is the JSON text as generated, which may not parse.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+pub struct LLMToolCall {
+    pub id: String,
+    pub name: String,
+    pub arguments: String,
+}
+
+/// Sampling parameters forwarded to the provider. Unset fields are omitted from the
+/// request body so the provider's own defaults apply.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct LLMParameters {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<LLMResponseFormat>,
+}
+
+/// Output format requested from the model, serialized in the OpenAI `response_format` shape.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LLMResponseFormat {
+    Text,
+    JsonObject,
+    JsonSchema { json_schema: serde_json::Value },
+}
+
+impl LLMParameters {
+    /// Returns these parameters with every field set in `overrides` replacing the current value.
+    pub fn merged_with(&self, overrides: &LLMParameters) -> LLMParameters {
+        LLMParameters {
+            temperature: overrides.temperature.or(self.temperature),
+            top_p: overrides.top_p.or(self.top_p),
+            max_tokens: overrides.max_tokens.or(self.max_tokens),
+            stop: overrides.stop.clone().or_else(|| self.stop.clone()),
+            seed: overrides.seed.or(self.seed),
+            response_format: overrides.response_format.clone().or_else(|| self.response_format.clone()),
+        }
+    }
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
 pub struct LLMResponse {
     pub content: String,
-    // Autres informations comme usage des tokens, etc.
+    // Token usage as reported by the provider, when it reports any
+    #[serde(default)]
+    pub usage: Option<LLMTokenCounts>,
+    // Provider and model that actually produced this response, set by `ExternalApiClient`
+    #[serde(default)]
+    pub served_by: Option<LLMRoute>,
+    // Served from the response cache rather than by a provider call
+    #[serde(default)]
+    pub cached: bool,
+    // Tools the model asked to call; their results go back in a follow-up request
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<LLMToolCall>,
+    // Why the generation stopped, in chat completions terms ("stop", "length", "tool_calls"), when reported
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<String>,
+}
+
+/// Vectors returned by `LLMProvider::embed`, one per input text and in the same order.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct LLMEmbeddings {
+    pub vectors: Vec<Vec<f32>>,
+    // Model that produced the vectors; vectors of different models must not be compared
+    pub model: String,
+    #[serde(default)]
+    pub usage: Option<LLMTokenCounts>,
+}
+
+/// One model offered by a provider, as listed in its catalog.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct LLMModelInfo {
+    pub id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub context_length: Option<u64>,
+    #[serde(default)]
+    pub pricing: Option<ModelPrice>,
+    // Input and output modalities, e.g. "text+image->text"
+    #[serde(default)]
+    pub modality: Option<String>,
+}
+
+// Bounds on reaching a provider and on a response going silent. The whole call is bounded
+// by the deadline of `LLMCallOptions`, which leaves room for long streamed completions
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const READ_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// HTTP client shared by a provider's calls, failing connections and reads that hang.
+pub(crate) fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .read_timeout(READ_TIMEOUT)
+        .build()
+        .unwrap_or_else(|e| {
+            eprintln!("Warning: Failed to build the HTTP client with timeouts: {}. Using the default client.", e);
+            reqwest::Client::new()
+        })
+}
+
+/// Sends an authenticated GET to `url` with `api_key`, failing unless the provider accepts the key.
+/// Used as a cheap check before storing a new key.
+pub(crate) async fn check_bearer_key(client: &reqwest::Client, provider: &str, url: &str, api_key: &str) -> Result<(), anyhow::Error> {
+    let response = client.get(url)
+        .header("Authorization", format!("Bearer {}", api_key))
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        return Err(ProviderHttpError::from_response(provider.to_string(), response).await?.into());
+    }
+    Ok(())
+}
+
+/// Non-success HTTP status returned by a provider API.
+#[derive(Debug, thiserror::Error)]
+#[error("{provider} API error: Status {status}, Body: {body}")]
+pub struct ProviderHttpError {
+    pub provider: String,
+    pub status: u16,
+    pub body: String,
+    // From the `Retry-After` header, mostly sent with 429 and 503
+    pub retry_after: Option<Duration>,
+}
+
+impl ProviderHttpError {
+    /// Builds the error from a non-success response, consuming its body.
+    pub async fn from_response(provider: String, response: reqwest::Response) -> Result<Self, reqwest::Error> {
+        let status = response.status().as_u16();
+        let retry_after = response.headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_retry_after);
+        let body = response.text().await?;
+        Ok(ProviderHttpError { provider, status, body, retry_after })
+    }
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+pub struct LLMTokenCounts {
+    #[serde(default)]
+    pub prompt_tokens: u64,
+    #[serde(default)]
+    pub completion_tokens: u64,
+    #[serde(default)]
+    pub total_tokens: u64,
 }
 
 #[async_trait]
 pub trait LLMProvider: Send + Sync + std::fmt::Debug {
     fn name(&self) -> String;
-    async fn generate(&self, request: LLMRequest) -> Result<LLMResponse, anyhow::Error>;
-    // Potentiellement d'autres méthodes comme list_models, etc.
+
+    /// Whether the provider has the credentials it needs. Providers without them stay
+    /// registered so a key can be set at runtime, and fail calls with `LLMError::NotConfigured`.
+    fn is_configured(&self) -> bool {
+        true
+    }
+
+    /// Checks `api_key` with a cheap authenticated request, without using it for calls.
+    async fn check_api_key(&self, _api_key: &str) -> Result<(), anyhow::Error> {
+        Err(anyhow::anyhow!("Provider '{}' does not use an API key", self.name()))
+    }
+
+    /// Uses `api_key` for all later calls without checking it; None leaves the provider not configured.
+    fn replace_api_key(&self, _api_key: Option<String>) {}
+
+    /// Key the provider currently calls with, if it uses one.
+    fn current_api_key(&self) -> Option<String> {
+        None
+    }
+
+    /// Model used when a request leaves `model` empty, if the provider has one configured.
+    fn default_model(&self) -> Option<String> {
+        None
+    }
+
+    async fn generate(&self, request: LLMRequest) -> Result<LLMResponse, LLMError>;
+
+    /// Generates a completion while sending partial tokens through `chunk_sender` as they arrive.
+    /// Providers without native streaming send the whole completion as a single chunk.
+    async fn generate_stream(&self, request: LLMRequest, chunk_sender: mpsc::Sender<String>) -> Result<LLMResponse, LLMError> {
+        let response = self.generate(request).await?;
+        let _ = chunk_sender.send(response.content.clone()).await;
+        Ok(response)
+    }
+
+    /// Embeds `texts` with `model`, one vector per text. Providers without an embeddings API fail.
+    async fn embed(&self, _texts: &[String], _model: &str) -> Result<LLMEmbeddings, anyhow::Error> {
+        Err(anyhow::anyhow!("Provider '{}' does not support embeddings", self.name()))
+    }
+
+    /// Models offered by the provider. An empty list means the provider does not publish
+    /// a catalog, in which case any model name is accepted.
+    async fn list_models(&self) -> Result<Vec<LLMModelInfo>, anyhow::Error> {
+        Ok(Vec::new())
+    }
 }
\ No newline at end of file
@@ -0,0 +1,130 @@
+use async_trait::async_trait;
+use anyhow::{Result, anyhow};
+use reqwest;
+use std::env;
+use tokio::sync::mpsc;
+
+use super::error::LLMError;
+use super::{http_client, LLMEmbeddings, LLMProvider, LLMRequest, LLMResponse};
+use super::chat_completions::{ChatCompletionRequest, ChatCompletionsEndpoint};
+use super::embeddings::request_embeddings;
+use super::retry::RetryConfig;
+use crate::external_api_client::config::OpenAICompatibleProviderConfig;
+
+/// Provider for any server exposing the OpenAI chat completions API,
+/// such as a llama.cpp server, Ollama or vLLM. Several instances can be
+/// registered side by side, each under the name given in its configuration.
+#[derive(Debug)]
+pub struct OpenAICompatibleProvider {
+    name: String,
+    api_endpoint: String,
+    // Header name and full header value
+    auth_header: Option<(String, String)>,
+    default_model: Option<String>,
+    client: reqwest::Client,
+    retry_config: RetryConfig,
+}
+
+impl OpenAICompatibleProvider {
+    pub fn new(config: &OpenAICompatibleProviderConfig) -> Result<Self, anyhow::Error> {
+        if config.name.trim().is_empty() {
+            return Err(anyhow!("OpenAI-compatible provider configured without a name"));
+        }
+
+        let auth_header = match &config.auth {
+            Some(auth) => {
+                let api_key = env::var(&auth.api_key_env)
+                    .map_err(|_| anyhow!("{} not found in environment variables (provider '{}')", auth.api_key_env, config.name))?;
+                Some((auth.header.clone(), format!("{}{}", auth.value_prefix, api_key)))
+            }
+            None => None, // Local servers usually run without authentication
+        };
+
+        let api_endpoint = format!("{}/chat/completions", config.base_url.trim_end_matches('/'));
+        let client = http_client();
+        Ok(OpenAICompatibleProvider {
+            name: config.name.clone(),
+            api_endpoint,
+            auth_header,
+            default_model: config.default_model.clone(),
+            client,
+            // Calls are not retried: a local server that fails usually keeps failing
+            retry_config: RetryConfig { max_attempts: 1, ..RetryConfig::default() },
+        })
+    }
+
+    fn build_request(&self, request: LLMRequest, stream: bool) -> Result<ChatCompletionRequest, LLMError> {
+        let mut chat_request = ChatCompletionRequest::new(request, stream);
+        if chat_request.model.is_empty() {
+            chat_request.model = self.default_model.clone()
+                .ok_or_else(|| LLMError::InvalidRequest {
+                    provider: self.name.clone(),
+                    status: None,
+                    message: "No model in request and no default model configured".to_string(),
+                })?;
+        }
+        Ok(chat_request)
+    }
+
+    fn chat_completions(&self) -> ChatCompletionsEndpoint<'_> {
+        ChatCompletionsEndpoint {
+            client: &self.client,
+            provider: &self.name,
+            url: &self.api_endpoint,
+            auth_header: self.auth_header.as_ref().map(|(header, value)| (header.as_str(), value.as_str())),
+            retry_config: &self.retry_config,
+        }
+    }
+}
+
+#[async_trait]
+impl LLMProvider for OpenAICompatibleProvider {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn default_model(&self) -> Option<String> {
+        self.default_model.clone()
+    }
+
+    async fn generate(&self, request: LLMRequest) -> Result<LLMResponse, LLMError> {
+        self.chat_completions().generate(self.build_request(request, false)?).await
+    }
+
+    async fn generate_stream(&self, request: LLMRequest, chunk_sender: mpsc::Sender<String>) -> Result<LLMResponse, LLMError> {
+        self.chat_completions().generate_stream(self.build_request(request, true)?, &chunk_sender).await
+    }
+
+    async fn embed(&self, texts: &[String], model: &str) -> Result<LLMEmbeddings, anyhow::Error> {
+        // default_model is a chat model, which the embeddings endpoint would reject
+        if model.is_empty() {
+            return Err(anyhow!("No embedding model given for provider '{}'", self.name));
+        }
+        // The embeddings endpoint sits next to the chat completions one
+        let url = format!("{}/embeddings", self.api_endpoint.trim_end_matches("/chat/completions"));
+        let auth_header = self.auth_header.as_ref().map(|(header, value)| (header.as_str(), value.as_str()));
+        request_embeddings(&self.client, &self.name, &url, auth_header, texts, model).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_and_default_model_from_config() {
+        let config: OpenAICompatibleProviderConfig = serde_json::from_value(serde_json::json!({
+            "name": "LocalLlama",
+            "base_url": "http://localhost:8080/v1/",
+            "default_model": "llama-3-8b-instruct"
+        })).unwrap();
+        let provider = OpenAICompatibleProvider::new(&config).unwrap();
+        assert_eq!(provider.name(), "LocalLlama");
+        assert_eq!(provider.api_endpoint, "http://localhost:8080/v1/chat/completions");
+        assert!(provider.auth_header.is_none());
+
+        let request = LLMRequest::from_prompt("", "Hi", None);
+        let body = provider.build_request(request, false).unwrap();
+        assert_eq!(body.model, "llama-3-8b-instruct");
+    }
+}
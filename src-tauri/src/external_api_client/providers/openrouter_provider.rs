@@ -1,48 +1,138 @@
 use async_trait::async_trait;
-use anyhow::{Result, anyhow};
+use anyhow::Result;
 use reqwest;
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use std::env;
+use std::sync::RwLock;
+use tokio::sync::mpsc;
 
-use super::{LLMProvider, LLMRequest, LLMResponse};
+use super::{check_bearer_key, http_client, LLMEmbeddings, LLMModelInfo, LLMProvider, LLMRequest, LLMResponse, ProviderHttpError};
+use super::error::LLMError;
+use super::retry::{with_retries, RetryConfig};
+use super::chat_completions::{ChatCompletionRequest, ChatCompletionsEndpoint};
+use super::embeddings::request_embeddings;
+use crate::external_api_client::pricing::ModelPrice;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct OpenRouterRequest {
-    model: String,
-    messages: Vec<OpenRouterMessage>,
+const OPENROUTER_CHAT_COMPLETIONS_URL: &str = "https://openrouter.ai/api/v1/chat/completions";
+
+#[derive(Debug, Deserialize)]
+struct OpenRouterModelList {
+    data: Vec<OpenRouterModel>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct OpenRouterMessage {
-    role: String,
-    content: String,
+#[derive(Debug, Deserialize)]
+struct OpenRouterModel {
+    id: String,
+    name: Option<String>,
+    context_length: Option<u64>,
+    architecture: Option<OpenRouterArchitecture>,
+    pricing: Option<OpenRouterPricing>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct OpenRouterResponse {
-    choices: Vec<OpenRouterChoice>,
-    // Include other fields if needed, like 'usage'
+#[derive(Debug, Deserialize)]
+struct OpenRouterArchitecture {
+    modality: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct OpenRouterChoice {
-    message: OpenRouterMessage,
-    // Include other fields if needed, like 'finish_reason'
+// Prices are decimal strings in USD per token
+#[derive(Debug, Deserialize)]
+struct OpenRouterPricing {
+    prompt: Option<String>,
+    completion: Option<String>,
+}
+
+impl From<OpenRouterModel> for LLMModelInfo {
+    fn from(model: OpenRouterModel) -> Self {
+        let per_million = |price: &Option<String>| {
+            price.as_deref()
+                .and_then(|p| p.parse::<f64>().ok())
+                .filter(|p| *p >= 0.0) // Dynamically priced routers report -1
+                .map(|p| p * 1_000_000.0)
+        };
+        let pricing = model.pricing.and_then(|pricing| {
+            Some(ModelPrice {
+                prompt_per_million: per_million(&pricing.prompt)?,
+                completion_per_million: per_million(&pricing.completion)?,
+            })
+        });
+        LLMModelInfo {
+            id: model.id,
+            name: model.name,
+            context_length: model.context_length,
+            pricing,
+            modality: model.architecture.and_then(|architecture| architecture.modality),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct OpenRouterProvider {
-    api_key: String,
+    // None until a key comes from the environment, the credential store or `replace_api_key`
+    api_key: RwLock<Option<String>>,
     client: reqwest::Client,
+    api_endpoint: String,
+    retry_config: RetryConfig,
+}
+
+impl Default for OpenRouterProvider {
+    fn default() -> Self {
+        OpenRouterProvider::new()
+    }
 }
 
 impl OpenRouterProvider {
-    pub fn new() -> Result<Self, anyhow::Error> {
-        dotenv::dotenv().ok(); // Load environment variables from .env file
-        let api_key = env::var("OPENROUTER_API_KEY")
-            .map_err(|_| anyhow!("OPENROUTER_API_KEY not found in environment variables"))?;
-        let client = reqwest::Client::new();
-        Ok(OpenRouterProvider { api_key, client })
+    /// Registers the provider even without `OPENROUTER_API_KEY`; it is then not configured until a key is set.
+    pub fn new() -> Self {
+        let api_key = env::var("OPENROUTER_API_KEY").ok().filter(|key| !key.trim().is_empty());
+        // Overridable to go through a proxy or a self-hosted gateway
+        let api_endpoint = env::var("OPENROUTER_API_ENDPOINT")
+            .unwrap_or_else(|_| OPENROUTER_CHAT_COMPLETIONS_URL.to_string());
+        let client = http_client();
+        OpenRouterProvider { api_key: RwLock::new(api_key), client, api_endpoint, retry_config: RetryConfig::default() }
+    }
+
+    fn api_key(&self) -> Result<String, LLMError> {
+        self.api_key.read().ok()
+            .and_then(|api_key| api_key.clone())
+            .ok_or_else(|| LLMError::NotConfigured { provider: "OpenRouter".to_string() })
+    }
+
+    pub fn with_retry_config(mut self, retry_config: RetryConfig) -> Self {
+        self.retry_config = retry_config;
+        self
+    }
+
+    fn build_request(request: LLMRequest, stream: bool) -> ChatCompletionRequest {
+        ChatCompletionRequest::new(request, stream)
+    }
+
+    fn chat_completions<'a>(&'a self, authorization: &'a str) -> ChatCompletionsEndpoint<'a> {
+        ChatCompletionsEndpoint {
+            client: &self.client,
+            provider: "OpenRouter",
+            url: &self.api_endpoint,
+            auth_header: Some(("Authorization", authorization)),
+            retry_config: &self.retry_config,
+        }
+    }
+
+    fn authorization(&self) -> Result<String, LLMError> {
+        let api_key = self.api_key()?;
+        Ok(format!("Bearer {}", api_key))
+    }
+
+    // The model list sits next to the chat completions endpoint, e.g. https://openrouter.ai/api/v1/models
+    fn models_endpoint(&self) -> String {
+        format!("{}/models", self.api_endpoint.trim_end_matches("/chat/completions"))
+    }
+
+    fn embeddings_endpoint(&self) -> String {
+        format!("{}/embeddings", self.api_endpoint.trim_end_matches("/chat/completions"))
+    }
+
+    // Describes the calling key, so it only succeeds with a valid one
+    fn key_check_endpoint(&self) -> String {
+        format!("{}/key", self.api_endpoint.trim_end_matches("/chat/completions"))
     }
 }
 
@@ -52,48 +142,116 @@ impl LLMProvider for OpenRouterProvider {
         "OpenRouter".to_string()
     }
 
-    async fn generate(&self, request: LLMRequest) -> Result<LLMResponse, anyhow::Error> {
-        let mut messages = Vec::new();
+    fn is_configured(&self) -> bool {
+        self.api_key().is_ok()
+    }
 
-        if let Some(system_prompt) = &request.system_prompt {
-            messages.push(OpenRouterMessage {
-                role: "system".to_string(),
-                content: system_prompt.clone(),
-            });
+    async fn check_api_key(&self, api_key: &str) -> Result<(), anyhow::Error> {
+        check_bearer_key(&self.client, "OpenRouter", &self.key_check_endpoint(), api_key).await
+    }
+
+    fn replace_api_key(&self, api_key: Option<String>) {
+        if let Ok(mut current) = self.api_key.write() {
+            *current = api_key;
         }
+    }
 
-        messages.push(OpenRouterMessage {
-            role: "user".to_string(),
-            content: request.prompt.clone(),
-        });
+    fn current_api_key(&self) -> Option<String> {
+        self.api_key().ok()
+    }
 
-        let openrouter_request = OpenRouterRequest {
-            model: request.model,
-            messages,
-        };
+    async fn generate(&self, request: LLMRequest) -> Result<LLMResponse, LLMError> {
+        let authorization = self.authorization()?;
+        self.chat_completions(&authorization).generate(Self::build_request(request, false)).await
+    }
 
-        let response = self.client.post("https://openrouter.ai/api/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&openrouter_request)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await?;
-            return Err(anyhow!("OpenRouter API error: Status {}, Body: {}", status, text));
+    async fn generate_stream(&self, request: LLMRequest, chunk_sender: mpsc::Sender<String>) -> Result<LLMResponse, LLMError> {
+        let authorization = self.authorization()?;
+        self.chat_completions(&authorization).generate_stream(Self::build_request(request, true), &chunk_sender).await
+    }
+
+    async fn embed(&self, texts: &[String], model: &str) -> Result<LLMEmbeddings, anyhow::Error> {
+        // OpenRouter has no default embedding model to fall back on
+        if model.is_empty() {
+            return Err(anyhow::anyhow!("No embedding model given for provider 'OpenRouter'"));
         }
+        let authorization = format!("Bearer {}", self.api_key()?);
+        let url = self.embeddings_endpoint();
+        let embeddings = with_retries(&self.retry_config, "OpenRouter", || {
+            request_embeddings(&self.client, "OpenRouter", &url, Some(("Authorization", authorization.as_str())), texts, model)
+        }).await?;
+        Ok(embeddings)
+    }
 
-        let openrouter_response: OpenRouterResponse = response.json().await?;
+    async fn list_models(&self) -> Result<Vec<LLMModelInfo>, anyhow::Error> {
+        let model_list: OpenRouterModelList = with_retries(&self.retry_config, "OpenRouter", || async {
+            // The model list is public, so it is available before a key is set
+            let mut request_builder = self.client.get(self.models_endpoint());
+            if let Ok(api_key) = self.api_key() {
+                request_builder = request_builder.header("Authorization", format!("Bearer {}", api_key));
+            }
+            let response = request_builder.send().await?;
+            if !response.status().is_success() {
+                return Err(ProviderHttpError::from_response("OpenRouter".to_string(), response).await?.into());
+            }
+            let body = response.text().await?;
+            Ok(serde_json::from_str(&body)?)
+        }).await?;
 
-        if let Some(choice) = openrouter_response.choices.into_iter().next() {
-            Ok(LLMResponse {
-                content: choice.message.content,
-                // Map other fields if necessary
-            })
-        } else {
-            Err(anyhow!("OpenRouter API returned no choices"))
-        }
+        Ok(model_list.data.into_iter().map(LLMModelInfo::from).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::external_api_client::{LLMParameters, LLMResponseFormat};
+
+    #[test]
+    fn test_build_request_serializes_sampling_parameters() {
+        let request = LLMRequest {
+            parameters: LLMParameters {
+                temperature: Some(0.25),
+                stop: Some(vec!["END".to_string()]),
+                seed: Some(7),
+                response_format: Some(LLMResponseFormat::JsonObject),
+                ..Default::default()
+            },
+            ..LLMRequest::from_prompt("test-model", "Hello", None)
+        };
+
+        let body = serde_json::to_value(OpenRouterProvider::build_request(request, false)).unwrap();
+        assert_eq!(body["temperature"], serde_json::json!(0.25));
+        assert_eq!(body["stop"], serde_json::json!(["END"]));
+        assert_eq!(body["seed"], serde_json::json!(7));
+        assert_eq!(body["response_format"], serde_json::json!({ "type": "json_object" }));
+        // Unset parameters and a disabled stream are left out so provider defaults apply
+        assert!(body.get("top_p").is_none());
+        assert!(body.get("max_tokens").is_none());
+        assert!(body.get("stream").is_none());
+    }
+
+    #[test]
+    fn test_model_list_conversion() {
+        let model_list: OpenRouterModelList = serde_json::from_value(serde_json::json!({
+            "data": [
+                {
+                    "id": "openai/gpt-4o",
+                    "name": "OpenAI: GPT-4o",
+                    "context_length": 128000,
+                    "architecture": { "modality": "text+image->text" },
+                    "pricing": { "prompt": "0.0000025", "completion": "0.00001" }
+                },
+                { "id": "openrouter/auto", "pricing": { "prompt": "-1", "completion": "-1" } }
+            ]
+        })).unwrap();
+        let models: Vec<LLMModelInfo> = model_list.data.into_iter().map(LLMModelInfo::from).collect();
+
+        assert_eq!(models[0].context_length, Some(128000));
+        assert_eq!(models[0].modality.as_deref(), Some("text+image->text"));
+        let pricing = models[0].pricing.as_ref().unwrap();
+        assert!((pricing.prompt_per_million - 2.5).abs() < 1e-9);
+        assert!((pricing.completion_per_million - 10.0).abs() < 1e-9);
+        assert_eq!(models[1].pricing, None);
     }
-}
\ No newline at end of file
+}
@@ -1,54 +1,76 @@
 use async_trait::async_trait;
-use anyhow::{Result, anyhow};
+use anyhow::Result;
 use reqwest;
-use serde::{Deserialize, Serialize};
 use std::env;
+use std::sync::RwLock;
+use tokio::sync::mpsc;
 
-use super::{LLMProvider, LLMRequest, LLMResponse};
-
-// Assuming Requesty uses a similar chat completion structure to OpenAI/OpenRouter
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct RequestyRequest {
-    model: String,
-    messages: Vec<RequestyMessage>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct RequestyMessage {
-    role: String,
-    content: String,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct RequestyResponse {
-    choices: Vec<RequestyChoice>,
-    // Include other fields if needed, like 'usage'
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct RequestyChoice {
-    message: RequestyMessage,
-    // Include other fields if needed, like 'finish_reason'
-}
+use super::{check_bearer_key, http_client, LLMProvider, LLMRequest, LLMResponse};
+use super::error::LLMError;
+use super::retry::RetryConfig;
+use super::chat_completions::{ChatCompletionRequest, ChatCompletionsEndpoint};
 
+// Requesty exposes an OpenAI-style chat completions API
 #[derive(Debug)]
 pub struct RequestyProvider {
-    api_key: String,
+    // None until a key comes from the environment, the credential store or `replace_api_key`
+    api_key: RwLock<Option<String>>,
     client: reqwest::Client,
     api_endpoint: String, // Added for flexibility
+    retry_config: RetryConfig,
+}
+
+impl Default for RequestyProvider {
+    fn default() -> Self {
+        RequestyProvider::new()
+    }
 }
 
 impl RequestyProvider {
-    pub fn new() -> Result<Self, anyhow::Error> {
-        dotenv::dotenv().ok(); // Load environment variables from .env file
-        let api_key = env::var("REQUESTY_API_KEY")
-            .map_err(|_| anyhow!("REQUESTY_API_KEY not found in environment variables"))?;
+    /// Registers the provider even without `REQUESTY_API_KEY`; it is then not configured until a key is set.
+    pub fn new() -> Self {
+        let api_key = env::var("REQUESTY_API_KEY").ok().filter(|key| !key.trim().is_empty());
         // Assuming a default endpoint, but could also load from env var
         let api_endpoint = env::var("REQUESTY_API_ENDPOINT")
             .unwrap_or_else(|_| "https://api.requesty.com/v1/chat/completions".to_string()); // Replace with actual endpoint if known
 
-        let client = reqwest::Client::new();
-        Ok(RequestyProvider { api_key, client, api_endpoint })
+        let client = http_client();
+        RequestyProvider { api_key: RwLock::new(api_key), client, api_endpoint, retry_config: RetryConfig::default() }
+    }
+
+    fn api_key(&self) -> Result<String, LLMError> {
+        self.api_key.read().ok()
+            .and_then(|api_key| api_key.clone())
+            .ok_or_else(|| LLMError::NotConfigured { provider: "Requesty".to_string() })
+    }
+
+    pub fn with_retry_config(mut self, retry_config: RetryConfig) -> Self {
+        self.retry_config = retry_config;
+        self
+    }
+
+    // The model list requires authentication, which makes it a cheap key check
+    fn key_check_endpoint(&self) -> String {
+        format!("{}/models", self.api_endpoint.trim_end_matches("/chat/completions"))
+    }
+
+    fn build_request(request: LLMRequest, stream: bool) -> ChatCompletionRequest {
+        ChatCompletionRequest::new(request, stream)
+    }
+
+    fn chat_completions<'a>(&'a self, authorization: &'a str) -> ChatCompletionsEndpoint<'a> {
+        ChatCompletionsEndpoint {
+            client: &self.client,
+            provider: "Requesty",
+            url: &self.api_endpoint,
+            auth_header: Some(("Authorization", authorization)),
+            retry_config: &self.retry_config,
+        }
+    }
+
+    fn authorization(&self) -> Result<String, LLMError> {
+        let api_key = self.api_key()?;
+        Ok(format!("Bearer {}", api_key))
     }
 }
 
@@ -58,48 +80,31 @@ impl LLMProvider for RequestyProvider {
         "Requesty".to_string()
     }
 
-    async fn generate(&self, request: LLMRequest) -> Result<LLMResponse, anyhow::Error> {
-        let mut messages = Vec::new();
+    fn is_configured(&self) -> bool {
+        self.api_key().is_ok()
+    }
+
+    async fn check_api_key(&self, api_key: &str) -> Result<(), anyhow::Error> {
+        check_bearer_key(&self.client, "Requesty", &self.key_check_endpoint(), api_key).await
+    }
 
-        if let Some(system_prompt) = &request.system_prompt {
-            messages.push(RequestyMessage {
-                role: "system".to_string(),
-                content: system_prompt.clone(),
-            });
+    fn replace_api_key(&self, api_key: Option<String>) {
+        if let Ok(mut current) = self.api_key.write() {
+            *current = api_key;
         }
+    }
 
-        messages.push(RequestyMessage {
-            role: "user".to_string(),
-            content: request.prompt.clone(),
-        });
-
-        let requesty_request = RequestyRequest {
-            model: request.model,
-            messages,
-        };
-
-        let response = self.client.post(&self.api_endpoint)
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&requesty_request)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await?;
-            return Err(anyhow!("Requesty API error: Status {}, Body: {}", status, text));
-        }
+    fn current_api_key(&self) -> Option<String> {
+        self.api_key().ok()
+    }
 
-        let requesty_response: RequestyResponse = response.json().await?;
+    async fn generate(&self, request: LLMRequest) -> Result<LLMResponse, LLMError> {
+        let authorization = self.authorization()?;
+        self.chat_completions(&authorization).generate(Self::build_request(request, false)).await
+    }
 
-        if let Some(choice) = requesty_response.choices.into_iter().next() {
-            Ok(LLMResponse {
-                content: choice.message.content,
-                // Map other fields if necessary
-            })
-        } else {
-            Err(anyhow!("Requesty API returned no choices"))
-        }
+    async fn generate_stream(&self, request: LLMRequest, chunk_sender: mpsc::Sender<String>) -> Result<LLMResponse, LLMError> {
+        let authorization = self.authorization()?;
+        self.chat_completions(&authorization).generate_stream(Self::build_request(request, true), &chunk_sender).await
     }
-}
\ No newline at end of file
+}
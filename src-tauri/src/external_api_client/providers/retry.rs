@@ -0,0 +1,109 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::time::Duration;
+
+use super::error::LLMError;
+
+/// Retry settings for transient provider failures.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RetryConfig {
+    // Total attempts, including the first one
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    500
+}
+
+fn default_max_backoff_ms() -> u64 {
+    8_000
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            max_attempts: default_max_attempts(),
+            initial_backoff_ms: default_initial_backoff_ms(),
+            max_backoff_ms: default_max_backoff_ms(),
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Delay before retry number `retry` (starting at 1): a random duration up to the
+    /// exponential backoff ceiling ("full jitter"), so concurrent callers do not retry in lockstep.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let ceiling = self.initial_backoff_ms
+            .saturating_mul(1u64 << retry.saturating_sub(1).min(20))
+            .min(self.max_backoff_ms);
+        Duration::from_millis(rand::thread_rng().gen_range(0..=ceiling))
+    }
+}
+
+/// Runs `operation` until it succeeds, fails with a non-retryable error or runs out of attempts.
+/// Failures are classified as `LLMError`s, so a rejected key fails at once while a 503 is retried.
+/// A `Retry-After` sent by the provider takes precedence over the computed backoff.
+pub async fn with_retries<T, F, Fut>(config: &RetryConfig, provider_name: &str, mut operation: F) -> Result<T, LLMError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<T>>,
+{
+    let mut attempt = 1;
+    loop {
+        match operation().await.map_err(|e| LLMError::from_anyhow(provider_name, e)) {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < config.max_attempts && e.is_retryable() => {
+                let delay = e.retry_after().unwrap_or_else(|| config.backoff(attempt));
+                eprintln!(
+                    "Warning: {} call failed (attempt {}/{}): {}. Retrying in {:?}",
+                    provider_name, attempt, config.max_attempts, e, delay
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::external_api_client::providers::ProviderHttpError;
+
+    fn http_error(status: u16) -> anyhow::Error {
+        ProviderHttpError { provider: "Test".to_string(), status, body: String::new(), retry_after: None }.into()
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let config = RetryConfig { max_attempts: 10, initial_backoff_ms: 100, max_backoff_ms: 1_000 };
+        for retry in 1..10 {
+            let ceiling = (100u64 << (retry - 1)).min(1_000);
+            assert!(config.backoff(retry) <= Duration::from_millis(ceiling));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_with_retries_stops_on_non_retryable_error() {
+        let config = RetryConfig { max_attempts: 3, initial_backoff_ms: 1, max_backoff_ms: 1 };
+        let mut calls = 0;
+        let result: Result<(), LLMError> = with_retries(&config, "Test", || {
+            calls += 1;
+            let status = if calls == 1 { 503 } else { 401 };
+            async move { Err(http_error(status)) }
+        }).await;
+        assert!(matches!(result, Err(LLMError::Auth { status: 401, .. })));
+        assert_eq!(calls, 2);
+    }
+}
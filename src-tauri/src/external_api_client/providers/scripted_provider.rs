@@ -0,0 +1,166 @@
+use async_trait::async_trait;
+use anyhow::{Result, anyhow, Context};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+
+use super::error::LLMError;
+use super::{LLMProvider, LLMRequest, LLMResponse};
+
+/// One scripted answer as written in a fixture file.
+/// An entry matches when every matcher it declares matches; an entry with no
+/// matcher at all acts as a catch-all.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptedFixtureEntry {
+    pub model: Option<String>,
+    pub prompt_regex: Option<String>,
+    pub prompt_sha256: Option<String>,
+    pub response: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptedFixtureFile {
+    pub responses: Vec<ScriptedFixtureEntry>,
+}
+
+#[derive(Debug)]
+struct CompiledEntry {
+    model: Option<String>,
+    prompt_regex: Option<Regex>,
+    prompt_sha256: Option<String>,
+    response: String,
+}
+
+/// Offline provider answering from a local fixture file instead of a remote API.
+/// Entries are tried in file order and the first match wins.
+#[derive(Debug)]
+pub struct ScriptedProvider {
+    entries: Vec<CompiledEntry>,
+}
+
+impl ScriptedProvider {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, anyhow::Error> {
+        let path = path.as_ref();
+        let file_content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read scripted fixture file {:?}", path))?;
+        Self::from_json_str(&file_content)
+            .with_context(|| format!("Invalid scripted fixture file {:?}", path))
+    }
+
+    pub fn from_json_str(json: &str) -> Result<Self, anyhow::Error> {
+        let fixture: ScriptedFixtureFile = serde_json::from_str(json)?;
+        Self::from_entries(fixture.responses)
+    }
+
+    pub fn from_entries(entries: Vec<ScriptedFixtureEntry>) -> Result<Self, anyhow::Error> {
+        let entries = entries
+            .into_iter()
+            .map(|entry| {
+                let prompt_regex = entry.prompt_regex
+                    .as_deref()
+                    .map(Regex::new)
+                    .transpose()
+                    .map_err(|e| anyhow!("Invalid prompt_regex in scripted fixture: {}", e))?;
+                Ok(CompiledEntry {
+                    model: entry.model,
+                    prompt_regex,
+                    prompt_sha256: entry.prompt_sha256.map(|h| h.to_lowercase()),
+                    response: entry.response,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(ScriptedProvider { entries })
+    }
+
+    /// Hex-encoded SHA-256 of a rendered prompt, as expected in `prompt_sha256`.
+    pub fn prompt_hash(prompt: &str) -> String {
+        format!("{:x}", Sha256::digest(prompt.as_bytes()))
+    }
+}
+
+impl CompiledEntry {
+    fn matches(&self, model: &str, prompt: &str, prompt_hash: &str) -> bool {
+        if self.model.as_deref().is_some_and(|m| m != model) {
+            return false;
+        }
+        if self.prompt_sha256.as_deref().is_some_and(|h| h != prompt_hash) {
+            return false;
+        }
+        if self.prompt_regex.as_ref().is_some_and(|re| !re.is_match(prompt)) {
+            return false;
+        }
+        true
+    }
+}
+
+#[async_trait]
+impl LLMProvider for ScriptedProvider {
+    fn name(&self) -> String {
+        "Scripted".to_string()
+    }
+
+    async fn generate(&self, request: LLMRequest) -> Result<LLMResponse, LLMError> {
+        // Fixtures match on the latest user turn, which is the rendered prompt for single-prompt requests
+        let prompt = request.last_user_prompt();
+        let prompt_hash = Self::prompt_hash(prompt);
+
+        match self.entries.iter().find(|entry| entry.matches(&request.model, prompt, &prompt_hash)) {
+            Some(entry) => Ok(LLMResponse {
+                content: entry.response.clone(),
+                usage: None,
+                served_by: None,
+                cached: false,
+                tool_calls: Vec::new(),
+                finish_reason: None,
+            }),
+            None => Err(LLMError::InvalidRequest {
+                provider: self.name(),
+                status: None,
+                message: format!("Scripted provider has no response for model '{}' and prompt sha256 {}", request.model, prompt_hash),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(model: &str, prompt: &str) -> LLMRequest {
+        LLMRequest::from_prompt(model, prompt, None)
+    }
+
+    #[tokio::test]
+    async fn test_scripted_provider_matching() {
+        let hashed_prompt = "exact prompt";
+        let fixture = serde_json::json!({
+            "responses": [
+                { "model": "planner-model", "prompt_regex": "Objective: .*", "response": "planner" },
+                { "prompt_sha256": ScriptedProvider::prompt_hash(hashed_prompt), "response": "hashed" },
+                { "model": "fallback-model", "response": "fallback" }
+            ]
+        });
+        let provider = ScriptedProvider::from_json_str(&fixture.to_string()).unwrap();
+
+        let planner = provider.generate(request("planner-model", "Objective: ship it")).await.unwrap();
+        assert_eq!(planner.content, "planner");
+
+        let hashed = provider.generate(request("any-model", hashed_prompt)).await.unwrap();
+        assert_eq!(hashed.content, "hashed");
+
+        let fallback = provider.generate(request("fallback-model", "anything")).await.unwrap();
+        assert_eq!(fallback.content, "fallback");
+
+        // The regex matches but the model does not, and nothing else applies.
+        let missing = provider.generate(request("other-model", "Objective: ship it")).await;
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn test_scripted_provider_rejects_invalid_regex() {
+        let fixture = r#"{ "responses": [ { "prompt_regex": "(", "response": "x" } ] }"#;
+        assert!(ScriptedProvider::from_json_str(fixture).is_err());
+    }
+}
@@ -0,0 +1,314 @@
+use anyhow::Result;
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use super::error::LLMError;
+use super::{LLMTokenCounts, LLMToolCall};
+
+/// Incremental decoder for `text/event-stream` bodies.
+/// Bytes are pushed as they arrive from the network; the `data:` payload of each
+/// event is returned once the blank line terminating that event has been seen.
+#[derive(Debug, Default)]
+pub struct SseDecoder {
+    buffer: Vec<u8>,
+    data_lines: Vec<String>,
+}
+
+impl SseDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds raw bytes and returns the payloads of all events completed by them.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+
+        while let Some(newline_pos) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line_bytes: Vec<u8> = self.buffer.drain(..=newline_pos).collect();
+            let line = String::from_utf8_lossy(&line_bytes);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if let Some(event) = self.process_line(line) {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    /// Flushes an event left unterminated when the stream closed.
+    pub fn finish(&mut self) -> Option<String> {
+        if !self.buffer.is_empty() {
+            let line_bytes = std::mem::take(&mut self.buffer);
+            let line = String::from_utf8_lossy(&line_bytes).trim_end_matches('\r').to_string();
+            if let Some(event) = self.process_line(&line) {
+                return Some(event);
+            }
+        }
+        self.dispatch()
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<String> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(':') {
+            // Comment line, used by providers as keep-alive.
+            return None;
+        }
+        if let Some(value) = line.strip_prefix("data:") {
+            self.data_lines.push(value.strip_prefix(' ').unwrap_or(value).to_string());
+        }
+        // Other fields (event, id, retry) are not needed by the chat completion streams.
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<String> {
+        if self.data_lines.is_empty() {
+            return None;
+        }
+        let data = self.data_lines.join("\n");
+        self.data_lines.clear();
+        Some(data)
+    }
+}
+
+/// `stream_options` of an OpenAI-compatible request; `include_usage` asks for a final
+/// chunk carrying the token usage of the whole completion.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamOptions {
+    pub include_usage: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletionChunk {
+    #[serde(default)]
+    choices: Vec<ChatCompletionChunkChoice>,
+    usage: Option<LLMTokenCounts>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletionChunkChoice {
+    #[serde(default)]
+    delta: ChatCompletionDelta,
+    // Set on the last chunk of the choice, e.g. "stop", "length" or "content_filter"
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ChatCompletionDelta {
+    content: Option<String>,
+    tool_calls: Option<Vec<ToolCallDelta>>,
+}
+
+/// Fragment of a streamed tool call. The first fragment of a call carries its id and name,
+/// the following ones pieces of its arguments; `index` tells which call a fragment belongs to.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct ToolCallDelta {
+    pub index: usize,
+    pub id: Option<String>,
+    pub function: Option<FunctionCallDelta>,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct FunctionCallDelta {
+    pub name: Option<String>,
+    pub arguments: Option<String>,
+}
+
+/// What a single OpenAI-style chat completion stream payload carried.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChatStreamEvent {
+    // A chunk may carry text, tool call fragments, the finish reason and the usage at once;
+    // the usage comes with the last choice or alone in a final chunk, depending on the provider
+    Delta { content: String, tool_calls: Vec<ToolCallDelta>, finish_reason: Option<String>, usage: Option<LLMTokenCounts> },
+    Done,
+}
+
+/// Error event sent by the provider in the middle of a stream, e.g. when it became overloaded.
+#[derive(Debug, thiserror::Error)]
+#[error("Stream returned an error: {message}")]
+pub struct StreamError {
+    pub message: String,
+}
+
+/// Parses one `data:` payload of an OpenAI-compatible chat completion stream.
+pub fn parse_chat_completion_chunk(payload: &str) -> Result<ChatStreamEvent> {
+    if payload.trim() == "[DONE]" {
+        return Ok(ChatStreamEvent::Done);
+    }
+    let value: serde_json::Value = serde_json::from_str(payload)?;
+    if let Some(error) = value.get("error") {
+        return Err(StreamError { message: error.to_string() }.into());
+    }
+    let chunk: ChatCompletionChunk = serde_json::from_value(value)?;
+    let finish_reason = chunk.choices.iter().find_map(|choice| choice.finish_reason.clone());
+    let mut content = String::new();
+    let mut tool_calls = Vec::new();
+    for choice in chunk.choices {
+        content.push_str(choice.delta.content.as_deref().unwrap_or_default());
+        tool_calls.extend(choice.delta.tool_calls.unwrap_or_default());
+    }
+    Ok(ChatStreamEvent::Delta { content, tool_calls, finish_reason, usage: chunk.usage })
+}
+
+/// Full text, requested tool calls, final usage report and finish reason of a streamed completion.
+#[derive(Debug, Default)]
+pub struct StreamedCompletion {
+    pub content: String,
+    pub usage: Option<LLMTokenCounts>,
+    pub tool_calls: Vec<LLMToolCall>,
+    pub finish_reason: Option<String>,
+}
+
+impl StreamedCompletion {
+    /// Applies one stream event, forwarding text deltas through `chunk_sender`.
+    /// Returns false once the stream signalled its end.
+    async fn apply(&mut self, event: ChatStreamEvent, chunk_sender: &mpsc::Sender<String>) -> bool {
+        match event {
+            ChatStreamEvent::Delta { content, tool_calls, finish_reason, usage } => {
+                if !content.is_empty() {
+                    self.content.push_str(&content);
+                    let _ = chunk_sender.send(content).await;
+                }
+                self.finish_reason = finish_reason.or(self.finish_reason.take());
+                self.usage = usage.or(self.usage.take());
+                for delta in tool_calls {
+                    if self.tool_calls.len() <= delta.index {
+                        self.tool_calls.resize_with(delta.index + 1, || LLMToolCall {
+                            id: String::new(),
+                            name: String::new(),
+                            arguments: String::new(),
+                        });
+                    }
+                    let call = &mut self.tool_calls[delta.index];
+                    if let Some(id) = delta.id {
+                        call.id = id;
+                    }
+                    if let Some(function) = delta.function {
+                        call.name.push_str(function.name.as_deref().unwrap_or_default());
+                        call.arguments.push_str(function.arguments.as_deref().unwrap_or_default());
+                    }
+                }
+            }
+            ChatStreamEvent::Done => return false,
+        }
+        true
+    }
+}
+
+/// Reads an OpenAI-compatible streaming response to the end, forwarding every
+/// non-empty token delta through `chunk_sender` and returning the full text
+/// along with any tool calls. A dropped receiver does not abort the generation.
+/// A stream of `provider` that closes before `[DONE]` or a finish reason fails as truncated.
+pub async fn collect_chat_completion_stream(
+    provider: &str,
+    response: reqwest::Response,
+    chunk_sender: &mpsc::Sender<String>,
+) -> Result<StreamedCompletion> {
+    let mut decoder = SseDecoder::new();
+    let mut completion = StreamedCompletion::default();
+    let mut byte_stream = response.bytes_stream();
+    let mut done = false;
+
+    'stream: while let Some(bytes) = byte_stream.next().await {
+        let bytes = bytes?;
+        for payload in decoder.push(&bytes) {
+            if !completion.apply(parse_chat_completion_chunk(&payload)?, chunk_sender).await {
+                done = true;
+                break 'stream;
+            }
+        }
+    }
+
+    if !done {
+        if let Some(payload) = decoder.finish() {
+            done = !completion.apply(parse_chat_completion_chunk(&payload)?, chunk_sender).await;
+        }
+    }
+
+    if !done && completion.finish_reason.is_none() {
+        return Err(LLMError::MalformedResponse {
+            provider: provider.to_string(),
+            truncated: true,
+            message: "Stream closed before the completion finished".to_string(),
+        }.into());
+    }
+    Ok(completion)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sse_decoder_handles_split_chunks_and_comments() {
+        let mut decoder = SseDecoder::new();
+        assert!(decoder.push(b": OPENROUTER PROCESSING\n\n").is_empty());
+        assert!(decoder.push(b"data: {\"a\"").is_empty());
+        assert_eq!(decoder.push(b":1}\r\n\r\ndata: [DO"), vec!["{\"a\":1}".to_string()]);
+        assert_eq!(decoder.push(b"NE]\n\n"), vec!["[DONE]".to_string()]);
+        assert_eq!(decoder.finish(), None);
+    }
+
+    #[test]
+    fn test_sse_decoder_joins_multiline_data_and_flushes() {
+        let mut decoder = SseDecoder::new();
+        assert!(decoder.push(b"event: message\ndata: first\ndata: second\n").is_empty());
+        assert_eq!(decoder.finish(), Some("first\nsecond".to_string()));
+    }
+
+    #[test]
+    fn test_parse_chat_completion_chunk() {
+        let payload = r#"{"choices":[{"delta":{"content":"Hel"},"finish_reason":null}]}"#;
+        assert_eq!(parse_chat_completion_chunk(payload).unwrap(), ChatStreamEvent::Delta { content: "Hel".to_string(), tool_calls: Vec::new(), finish_reason: None, usage: None });
+        let role_only = r#"{"choices":[{"delta":{"role":"assistant"}}]}"#;
+        assert_eq!(parse_chat_completion_chunk(role_only).unwrap(), ChatStreamEvent::Delta { content: String::new(), tool_calls: Vec::new(), finish_reason: None, usage: None });
+        let last = r#"{"choices":[{"delta":{"content":"lo"},"finish_reason":"length"}]}"#;
+        assert_eq!(parse_chat_completion_chunk(last).unwrap(), ChatStreamEvent::Delta { content: "lo".to_string(), tool_calls: Vec::new(), finish_reason: Some("length".to_string()), usage: None });
+        assert_eq!(parse_chat_completion_chunk("[DONE]").unwrap(), ChatStreamEvent::Done);
+        let counts = LLMTokenCounts { prompt_tokens: 12, completion_tokens: 3, total_tokens: 15 };
+        let usage = r#"{"choices":[],"usage":{"prompt_tokens":12,"completion_tokens":3,"total_tokens":15}}"#;
+        assert_eq!(
+            parse_chat_completion_chunk(usage).unwrap(),
+            ChatStreamEvent::Delta { content: String::new(), tool_calls: Vec::new(), finish_reason: None, usage: Some(counts.clone()) }
+        );
+        let last_with_usage = r#"{"choices":[{"delta":{"content":"!"},"finish_reason":"stop"}],"usage":{"prompt_tokens":12,"completion_tokens":3,"total_tokens":15}}"#;
+        assert_eq!(
+            parse_chat_completion_chunk(last_with_usage).unwrap(),
+            ChatStreamEvent::Delta { content: "!".to_string(), tool_calls: Vec::new(), finish_reason: Some("stop".to_string()), usage: Some(counts) }
+        );
+        assert!(parse_chat_completion_chunk(r#"{"error":{"message":"overloaded"}}"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_streamed_tool_call_fragments_are_assembled() {
+        let (chunk_sender, _chunk_receiver) = mpsc::channel(8);
+        let mut completion = StreamedCompletion::default();
+        for payload in [
+            r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"id":"call_1","type":"function","function":{"name":"lookup","arguments":""}}]}}]}"#,
+            r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"q\":"}}]}}]}"#,
+            r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"\"rust\"}"}}]},"finish_reason":"tool_calls"}]}"#,
+        ] {
+            assert!(completion.apply(parse_chat_completion_chunk(payload).unwrap(), &chunk_sender).await);
+        }
+        assert_eq!(completion.tool_calls, vec![LLMToolCall {
+            id: "call_1".to_string(),
+            name: "lookup".to_string(),
+            arguments: r#"{"q":"rust"}"#.to_string(),
+        }]);
+        assert!(completion.content.is_empty());
+        assert_eq!(completion.finish_reason.as_deref(), Some("tool_calls"));
+    }
+
+    #[tokio::test]
+    async fn test_stream_closed_before_the_end_is_truncated() {
+        let body = "data: {\"choices\":[{\"delta\":{\"content\":\"Half a sen\"}}]}\n\n".to_string();
+        let (url, _server) = crate::external_api_client::providers::test_server::serve_once("/v1", "text/event-stream", body).await;
+        let response = reqwest::get(&url).await.unwrap();
+
+        let (chunk_sender, _chunk_receiver) = mpsc::channel(8);
+        let error = collect_chat_completion_stream("Local", response, &chunk_sender).await.unwrap_err();
+        assert!(matches!(LLMError::from_anyhow("Local", error), LLMError::MalformedResponse { truncated: true, .. }));
+    }
+}
@@ -0,0 +1,195 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
+
+use super::LLMRequest;
+
+/// Limits applied to one provider. Unset limits are not enforced.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RateLimitConfig {
+    #[serde(default)]
+    pub requests_per_minute: Option<u32>,
+    #[serde(default)]
+    pub tokens_per_minute: Option<u32>,
+    #[serde(default)]
+    pub max_in_flight: Option<usize>,
+}
+
+/// Classic token bucket refilled continuously up to its capacity.
+/// The balance may go negative when a call used more tokens than reserved; later calls then wait longer.
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    available: f64,
+    refill_per_sec: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn per_minute(limit: u32, now: Instant) -> Self {
+        TokenBucket {
+            capacity: limit as f64,
+            available: limit as f64,
+            refill_per_sec: limit as f64 / 60.0,
+            last_refill: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.available = (self.available + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+    }
+
+    /// Takes `amount` if available, otherwise returns how long to wait before it will be.
+    /// Amounts larger than the capacity are clamped so they can eventually go through.
+    fn try_take(&mut self, amount: f64, now: Instant) -> Result<(), Duration> {
+        self.refill(now);
+        let amount = amount.min(self.capacity);
+        if self.available >= amount {
+            self.available -= amount;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((amount - self.available) / self.refill_per_sec))
+        }
+    }
+
+    /// Charges (or refunds, when negative) the difference between reserved and actual usage.
+    fn adjust(&mut self, delta: f64, now: Instant) {
+        self.refill(now);
+        self.available = (self.available - delta).min(self.capacity);
+    }
+}
+
+/// Held for the duration of a provider call; releases the in-flight slot when dropped.
+#[derive(Debug)]
+pub struct RateLimitPermit {
+    _in_flight: Option<OwnedSemaphorePermit>,
+    reserved_tokens: u64,
+}
+
+/// Per-provider limiter: request and token buckets, an in-flight cap, and a pause
+/// set when the provider answers `429 Too Many Requests`.
+#[derive(Debug)]
+pub struct ProviderRateLimiter {
+    requests: Option<Mutex<TokenBucket>>,
+    tokens: Option<Mutex<TokenBucket>>,
+    in_flight: Option<Arc<Semaphore>>,
+    paused_until: Mutex<Option<Instant>>,
+}
+
+impl ProviderRateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        let now = Instant::now();
+        ProviderRateLimiter {
+            requests: config.requests_per_minute.filter(|l| *l > 0).map(|l| Mutex::new(TokenBucket::per_minute(l, now))),
+            tokens: config.tokens_per_minute.filter(|l| *l > 0).map(|l| Mutex::new(TokenBucket::per_minute(l, now))),
+            in_flight: config.max_in_flight.filter(|l| *l > 0).map(|l| Arc::new(Semaphore::new(l))),
+            paused_until: Mutex::new(None),
+        }
+    }
+
+    /// Rough token cost of a request, reserved before the call: about four characters
+    /// per prompt token plus the completion budget.
+    pub fn estimate_tokens(request: &LLMRequest) -> u64 {
+        let prompt_chars: usize = request.messages.iter().map(|message| message.content.len()).sum();
+        (prompt_chars / 4) as u64 + request.parameters.max_tokens.unwrap_or(0) as u64
+    }
+
+    /// Waits until a call reserving `estimated_tokens` may be sent to the provider.
+    pub async fn acquire(&self, estimated_tokens: u64) -> RateLimitPermit {
+        let in_flight = match &self.in_flight {
+            // The semaphore is never closed, so acquiring cannot fail
+            Some(semaphore) => semaphore.clone().acquire_owned().await.ok(),
+            None => None,
+        };
+
+        self.wait_while_paused().await;
+        if let Some(requests) = &self.requests {
+            Self::take(requests, 1.0).await;
+        }
+        if let Some(tokens) = &self.tokens {
+            Self::take(tokens, estimated_tokens as f64).await;
+        }
+
+        RateLimitPermit { _in_flight: in_flight, reserved_tokens: estimated_tokens }
+    }
+
+    /// Settles the token bucket once the provider reported how many tokens the call used.
+    pub async fn record_usage(&self, permit: &RateLimitPermit, actual_tokens: u64) {
+        if let Some(tokens) = &self.tokens {
+            let delta = actual_tokens as f64 - permit.reserved_tokens as f64;
+            tokens.lock().await.adjust(delta, Instant::now());
+        }
+    }
+
+    /// Holds back every call to this provider for `delay`, e.g. after a `429` with `Retry-After`.
+    pub async fn pause_for(&self, delay: Duration) {
+        let resume_at = Instant::now() + delay;
+        let mut paused_until = self.paused_until.lock().await;
+        if paused_until.map_or(true, |current| current < resume_at) {
+            *paused_until = Some(resume_at);
+        }
+    }
+
+    async fn wait_while_paused(&self) {
+        loop {
+            let paused_until = *self.paused_until.lock().await;
+            match paused_until {
+                Some(resume_at) if resume_at > Instant::now() => tokio::time::sleep_until(resume_at.into()).await,
+                _ => return,
+            }
+        }
+    }
+
+    async fn take(bucket: &Mutex<TokenBucket>, amount: f64) {
+        loop {
+            let wait = bucket.lock().await.try_take(amount, Instant::now());
+            match wait {
+                Ok(()) => return,
+                Err(delay) => tokio::time::sleep(delay).await,
+            }
+        }
+    }
+}
+
+/// Parses a `Retry-After` header, given either in seconds or as an HTTP date.
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let retry_at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let delay = retry_at.with_timezone(&chrono::Utc) - chrono::Utc::now();
+    // A date in the past means "retry now"
+    Some(delay.to_std().unwrap_or(Duration::ZERO))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket_waits_for_refill() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::per_minute(60, start); // one per second
+
+        assert!(bucket.try_take(60.0, start).is_ok());
+        let wait = bucket.try_take(2.0, start).unwrap_err();
+        assert!((wait.as_secs_f64() - 2.0).abs() < 1e-6);
+        assert!(bucket.try_take(2.0, start + Duration::from_secs(2)).is_ok());
+
+        // A call that used more than it reserved puts the bucket in debt
+        bucket.adjust(3.0, start + Duration::from_secs(2));
+        let wait = bucket.try_take(1.0, start + Duration::from_secs(2)).unwrap_err();
+        assert!((wait.as_secs_f64() - 4.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("7"), Some(Duration::from_secs(7)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+}
@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+
+use super::providers::error::LLMError;
+
+/// One provider+model pair a call can be routed to.
+/// A route without a model uses the model of the request, or the provider's default model.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LLMRoute {
+    pub provider: String,
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+impl LLMRoute {
+    pub fn new(provider: impl Into<String>, model: Option<String>) -> Self {
+        LLMRoute { provider: provider.into(), model }
+    }
+}
+
+/// Failures after which the next route of a policy is tried.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum FallbackCondition {
+    ServerError,     // HTTP 5xx
+    Timeout,         // Request timed out, or HTTP 408
+    RateLimited,     // HTTP 429
+    ConnectionError, // Provider unreachable
+    AnyError,
+}
+
+/// Ordered list of routes for an LLM call. The first route is tried first; a failure
+/// matching one of `fallback_on` moves on to the next route, any other failure is returned.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RoutingPolicy {
+    pub routes: Vec<LLMRoute>,
+    #[serde(default = "default_fallback_conditions")]
+    pub fallback_on: Vec<FallbackCondition>,
+}
+
+fn default_fallback_conditions() -> Vec<FallbackCondition> {
+    vec![FallbackCondition::ServerError, FallbackCondition::Timeout, FallbackCondition::ConnectionError]
+}
+
+impl RoutingPolicy {
+    /// Policy with a single route and no fallback.
+    pub fn single(provider: impl Into<String>, model: Option<String>) -> Self {
+        RoutingPolicy {
+            routes: vec![LLMRoute::new(provider, model)],
+            fallback_on: default_fallback_conditions(),
+        }
+    }
+
+    /// Appends routes tried after the existing ones.
+    pub fn with_fallbacks(mut self, fallback_routes: impl IntoIterator<Item = LLMRoute>) -> Self {
+        self.routes.extend(fallback_routes);
+        self
+    }
+
+    /// Whether `error` from one route allows trying the next one.
+    pub fn should_fall_back(&self, error: &LLMError) -> bool {
+        self.fallback_on.iter().any(|condition| matches_condition(*condition, error))
+    }
+}
+
+fn matches_condition(condition: FallbackCondition, error: &LLMError) -> bool {
+    match (condition, error) {
+        (FallbackCondition::AnyError, _) => true,
+        (FallbackCondition::RateLimited, LLMError::RateLimited { .. }) => true,
+        (FallbackCondition::Timeout, LLMError::Transport { timeout, .. }) => *timeout,
+        (FallbackCondition::ServerError, LLMError::Transport { status, .. }) => status.is_some_and(|status| status >= 500),
+        // No status and no timeout: the provider could not be reached, or the connection dropped
+        (FallbackCondition::ConnectionError, LLMError::Transport { status, timeout, .. }) => status.is_none() && !timeout,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::external_api_client::providers::ProviderHttpError;
+
+    fn http_error(status: u16) -> LLMError {
+        LLMError::from_http(&ProviderHttpError { provider: "Test".to_string(), status, body: String::new(), retry_after: None })
+    }
+
+    fn malformed() -> LLMError {
+        LLMError::MalformedResponse { provider: "Test".to_string(), truncated: false, message: "malformed response".to_string() }
+    }
+
+    #[test]
+    fn test_default_policy_falls_back_on_server_errors_only() {
+        let policy = RoutingPolicy::single("OpenRouter", None);
+        assert!(policy.should_fall_back(&http_error(502)));
+        assert!(policy.should_fall_back(&http_error(408)));
+        assert!(!policy.should_fall_back(&http_error(401)));
+        assert!(!policy.should_fall_back(&http_error(429)));
+        assert!(!policy.should_fall_back(&malformed()));
+
+        let any_error = RoutingPolicy { fallback_on: vec![FallbackCondition::AnyError], ..policy };
+        assert!(any_error.should_fall_back(&malformed()));
+    }
+}
@@ -9,21 +9,27 @@ pub mod common_types;
 pub mod mcp_manager;
 pub mod persistence; // Add persistence module
 
-use tauri::{async_runtime::spawn, Manager};
+use tauri::{async_runtime::spawn, Emitter, Manager};
 use std::sync::Arc; // Changed to std::sync::Arc
 use tokio::sync::Mutex; // Changed to tokio::sync::Mutex
 use crate::core_orchestrator::CoreOrchestrator;
 use crate::communication_bus::CommunicationBus; // Import CommunicationBus
 use crate::external_api_client::ExternalApiClient; // Import ExternalApiClient
-use crate::mcp_manager::MCPManager; // Import MCPManager
-use crate::common_types::{AgentConfig, AgentRole, TaskNode, TaskStatus}; // Re-added TaskNode and TaskStatus as they are used later
+use crate::external_api_client::credential_store::{CredentialStore, CREDENTIALS_FILE_NAME};
+use crate::mcp_manager::{MCPManager, MCP_DEFINITIONS_DIR}; // Import MCPManager
+use crate::mcp_manager::cancellation::TaskCancellationRegistry;
+use crate::mcp_manager::definitions::DefinitionsReloaded;
+use crate::common_types::{AgentConfig, AgentRole, TaskNode, TaskStatus, MessageContent}; // Re-added TaskNode and TaskStatus as they are used later
 use uuid::Uuid;
 use anyhow::anyhow; // Add this line
 
 // Define the AppState struct to hold the orchestrator and DB connection
 pub struct AppState {
+    pub external_api_client: Arc<ExternalApiClient>,
     pub orchestrator: Arc<Mutex<CoreOrchestrator>>,
     pub db_connection: Arc<Mutex<rusqlite::Connection>>, // Add DB connection to state
+    // Held apart from the orchestrator, whose lock is taken by long-running commands
+    pub task_cancellation: Arc<TaskCancellationRegistry>,
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -36,11 +42,14 @@ pub fn run() {
             let communication_bus = Arc::new(CommunicationBus::new());
             log::info!("Communication bus initialized.");
 
+            // Provider API keys are kept encrypted in the app data directory
+            let app_data_dir = app.path().app_data_dir()?;
             let external_api_client = ExternalApiClient::new()
                 .map_err(|e| {
                     log::error!("Failed to create ExternalApiClient: {}", e);
                     e
-                })?;
+                })?
+                .with_credential_store(CredentialStore::new(app_data_dir.join(CREDENTIALS_FILE_NAME)));
             log::info!("External API client created.");
 
             let external_api_client_arc = Arc::new(external_api_client);
@@ -66,6 +75,31 @@ pub fn run() {
             })?);
             log::info!("MCPManager created.");
 
+            // Definition files that do not load are reported like on a hot reload rather than stopping the app
+            let initial_definitions = tauri::async_runtime::block_on(mcp_manager_arc.load_definitions(MCP_DEFINITIONS_DIR))
+                .map_err(|e| {
+                    log::error!("Failed to load MCP definitions: {}", e);
+                    e
+                })?;
+            log::info!("MCP definitions loaded.");
+
+            // Reload MCP definitions edited on disk and report each reload, including files that do not parse
+            let definitions_manager = Arc::clone(&mcp_manager_arc);
+            let definitions_app_handle = app.handle().clone();
+            spawn(async move {
+                report_definitions_reloaded(&definitions_app_handle, &initial_definitions);
+                let mut reloads = match definitions_manager.watch_definitions(MCP_DEFINITIONS_DIR) {
+                    Ok(reloads) => reloads,
+                    Err(e) => {
+                        log::error!("Failed to watch MCP definitions: {}", e);
+                        return;
+                    }
+                };
+                while let Some(reloaded) = reloads.recv().await {
+                    report_definitions_reloaded(&definitions_app_handle, &reloaded);
+                }
+            });
+
             let core_orchestrator = tauri::async_runtime::block_on(async {
                 CoreOrchestrator::new(
                     Arc::clone(&communication_bus),
@@ -83,8 +117,10 @@ pub fn run() {
 
             // Manage the state
             app.manage(AppState {
+                external_api_client: external_api_client_arc.clone(),
                 orchestrator: core_orchestrator_arc.clone(),
                 db_connection: db_connection_arc.clone(),
+                task_cancellation: Arc::clone(mcp_manager_arc.cancellation_registry()),
             });
             log::info!("App state managed.");
 
@@ -107,6 +143,28 @@ pub fn run() {
                 Ok(())
             });
 
+            // Forward streamed LLM fragments to the frontend
+            let mut fragment_receiver = communication_bus.subscribe_fragments();
+            let fragment_app_handle = app.handle().clone();
+            spawn(async move {
+                loop {
+                    match fragment_receiver.recv().await {
+                        Ok(message) => {
+                            if let MessageContent::DataFragment { task_id, data } = message.content {
+                                let payload = serde_json::json!({ "task_id": task_id, "data": data });
+                                if let Err(e) = fragment_app_handle.emit("task-data-fragment", payload) {
+                                    log::error!("Failed to emit task-data-fragment event: {}", e);
+                                }
+                            }
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
+                            log::warn!("Data fragment forwarder lagged behind by {} messages.", n);
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+            });
+
             app.handle().plugin(tauri_plugin_fs::init())?;
             log::info!("Tauri FS plugin initialized.");
 
@@ -121,9 +179,29 @@ pub fn run() {
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
-            crate::tauri_bindings::execute_agent_task
+            crate::tauri_bindings::execute_agent_task,
+            crate::tauri_bindings::list_llm_models,
+            crate::tauri_bindings::list_llm_providers,
+            crate::tauri_bindings::set_provider_api_key,
+            crate::tauri_bindings::rotate_provider_api_key,
+            crate::tauri_bindings::test_provider_api_key,
+            crate::tauri_bindings::delete_provider_api_key,
+            crate::tauri_bindings::credential_store_status,
+            crate::tauri_bindings::unlock_credential_store,
+            crate::tauri_bindings::cancel_task,
+            crate::tauri_bindings::cancel_graph
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
     log::info!("Tauri application run complete.");
 }
+
+// Logs the definition files that did not load and tells the frontend what changed
+fn report_definitions_reloaded(app_handle: &tauri::AppHandle, reloaded: &DefinitionsReloaded) {
+    for error in &reloaded.errors {
+        log::warn!("MCP definition {} was not loaded: {}", error.path, error.message);
+    }
+    if let Err(e) = app_handle.emit("mcp-definitions-reloaded", reloaded) {
+        log::error!("Failed to emit mcp-definitions-reloaded event: {}", e);
+    }
+}
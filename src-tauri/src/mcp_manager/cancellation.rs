@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+/// Cancellation tokens of running tasks and graphs. A task's token is a child of its graph's,
+/// so cancelling a graph cancels every task scheduled from it. The LLM calls made for a task
+/// watch its token and abort their in-flight HTTP request when it is cancelled.
+#[derive(Debug, Default)]
+pub struct TaskCancellationRegistry {
+    graphs: Mutex<HashMap<String, CancellationToken>>,
+    tasks: Mutex<HashMap<String, TaskToken>>,
+}
+
+#[derive(Debug)]
+struct TaskToken {
+    // None for tasks not scheduled by the orchestrator
+    graph_id: Option<String>,
+    token: CancellationToken,
+}
+
+impl TaskCancellationRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remembers a graph that has not finished, so it can be cancelled before any of its tasks is scheduled.
+    pub fn register_graph(&self, graph_id: &str) {
+        self.graph_token(graph_id);
+    }
+
+    /// Token of a task about to be scheduled from `graph_id`. A task cancelled while it ran and
+    /// then queued for a retry keeps its cancelled token, which the scheduler checks before assigning it.
+    pub fn register_task(&self, graph_id: &str, task_id: &str) -> CancellationToken {
+        let graph_token = self.graph_token(graph_id);
+        let mut tasks = self.tasks.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        match tasks.get(task_id) {
+            Some(task) if task.token.is_cancelled() => task.token.clone(),
+            _ => {
+                let token = graph_token.child_token();
+                tasks.insert(task_id.to_string(), TaskToken { graph_id: Some(graph_id.to_string()), token: token.clone() });
+                token
+            }
+        }
+    }
+
+    /// Token of a scheduled task. A task the scheduler did not register, or already released,
+    /// e.g. an MCP invoked directly, gets a fresh token that is not remembered: nothing would release it.
+    pub fn task_token(&self, task_id: &str) -> CancellationToken {
+        let tasks = self.tasks.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        match tasks.get(task_id) {
+            Some(task) => task.token.clone(),
+            None => CancellationToken::new(),
+        }
+    }
+
+    /// Cancels a running task. Returns false when no task with this id is running.
+    pub fn cancel_task(&self, task_id: &str) -> bool {
+        let tasks = self.tasks.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        match tasks.get(task_id) {
+            Some(task) => {
+                println!("Cancelling task {}", task_id);
+                task.token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cancels the running tasks of the graph and keeps its tasks from being scheduled.
+    /// Returns false when the graph is not known to the scheduler or has finished.
+    pub fn cancel_graph(&self, graph_id: &str) -> bool {
+        let graphs = self.graphs.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        match graphs.get(graph_id) {
+            Some(token) => {
+                println!("Cancelling task graph {}", graph_id);
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_graph_cancelled(&self, graph_id: &str) -> bool {
+        let graphs = self.graphs.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        graphs.get(graph_id).is_some_and(CancellationToken::is_cancelled)
+    }
+
+    /// Forgets the token of a task whose attempt ended. A cancelled token is kept when the task
+    /// is queued for a retry, so it is not scheduled again; pass `finished` once the task has
+    /// completed or failed for good.
+    pub fn release_task(&self, task_id: &str, finished: bool) {
+        let mut tasks = self.tasks.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if finished || tasks.get(task_id).is_some_and(|task| !task.token.is_cancelled()) {
+            tasks.remove(task_id);
+        }
+    }
+
+    /// Forgets a finished graph and every task token scheduled from it.
+    pub fn release_graph(&self, graph_id: &str) {
+        self.tasks.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .retain(|_, task| task.graph_id.as_deref() != Some(graph_id));
+        self.graphs.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .remove(graph_id);
+    }
+
+    fn graph_token(&self, graph_id: &str) -> CancellationToken {
+        let mut graphs = self.graphs.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        graphs.entry(graph_id.to_string()).or_default().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancelling_graph_cancels_its_tasks() {
+        let registry = TaskCancellationRegistry::new();
+        let first = registry.register_task("graph-1", "task-1");
+        let other_graph = registry.register_task("graph-2", "task-2");
+
+        assert!(registry.cancel_graph("graph-1"));
+        assert!(first.is_cancelled());
+        assert!(registry.task_token("task-1").is_cancelled());
+        assert!(!other_graph.is_cancelled());
+        assert!(registry.register_task("graph-1", "task-3").is_cancelled());
+
+        // A cancelled task queued for a retry stays cancelled until it has failed for good
+        let retried = registry.register_task("graph-2", "task-4");
+        assert!(registry.cancel_task("task-4"));
+        registry.release_task("task-4", false);
+        assert!(registry.register_task("graph-2", "task-4").is_cancelled());
+        assert!(retried.is_cancelled());
+        registry.release_task("task-4", true);
+        assert!(!registry.register_task("graph-2", "task-4").is_cancelled());
+
+        // Unknown tasks are not remembered, and finished graphs are forgotten
+        assert!(!registry.cancel_task("task-5"));
+        assert!(!registry.task_token("task-5").is_cancelled());
+        assert!(!registry.tasks.lock().unwrap().contains_key("task-5"));
+        registry.release_graph("graph-1");
+        assert!(!registry.is_graph_cancelled("graph-1"));
+        assert!(!registry.tasks.lock().unwrap().contains_key("task-1"));
+        assert!(registry.tasks.lock().unwrap().contains_key("task-2"));
+
+        // Unknown and finished graphs are not remembered when cancelled
+        assert!(!registry.cancel_graph("graph-1"));
+        assert!(!registry.cancel_graph("graph-3"));
+        assert!(!registry.graphs.lock().unwrap().contains_key("graph-1"));
+        assert!(!registry.graphs.lock().unwrap().contains_key("graph-3"));
+        registry.register_graph("graph-3");
+        assert!(registry.cancel_graph("graph-3"));
+        assert!(registry.is_graph_cancelled("graph-3"));
+    }
+}
@@ -0,0 +1,346 @@
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+
+use super::MCPManager;
+use crate::common_types::{MCPContextOverrides, MCPDefinition, MCPInput};
+pub use crate::common_types::{ContextOverflowPolicy, ContextOverflowStrategy};
+use crate::external_api_client::{LLMRequest, LLMTokenCounts, RoutingPolicy};
+
+/// MCP used by the `Summarize` strategy.
+pub const SUMMARIZE_MCP_ID: &str = "SummarizeTextV1";
+
+// Every model in use has at least this many tokens of context, so smaller prompts skip the catalog lookup
+const MIN_GUARDED_PROMPT_TOKENS: u64 = 4096;
+
+// Per-message formatting tokens added by chat templates
+const TOKENS_PER_MESSAGE: u64 = 4;
+
+const TRUNCATION_MARKER: &str = "\n[... truncated to fit the context window ...]";
+
+/// Rendered prompt that does not fit in the model's context window, even after applying the overflow strategy.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("Prompt of MCP {mcp_id} needs about {estimated_tokens} tokens but model '{model}' has a context window of {context_length}")]
+pub struct ContextLengthExceeded {
+    pub mcp_id: String,
+    pub model: String,
+    pub estimated_tokens: u64,
+    pub context_length: u64,
+}
+
+/// Rough token count of a text: about four characters per token for English prose and code.
+pub fn estimate_text_tokens(text: &str) -> u64 {
+    (text.chars().count() as u64).div_ceil(4)
+}
+
+/// Tokens a request needs: its messages, tool definitions and the room left for the completion.
+pub fn estimate_request_tokens(request: &LLMRequest, reserved_completion_tokens: u32) -> u64 {
+    let message_tokens: u64 = request.messages.iter()
+        .map(|message| estimate_text_tokens(&message.content) + TOKENS_PER_MESSAGE)
+        .sum();
+    let tool_tokens: u64 = request.tools.iter()
+        .map(|tool| estimate_text_tokens(&serde_json::to_string(tool).unwrap_or_default()))
+        .sum();
+    message_tokens + tool_tokens + request.parameters.max_tokens.unwrap_or(reserved_completion_tokens) as u64
+}
+
+/// `text` cut to about `max_tokens`, on a character boundary, with a marker telling the model it was cut.
+pub fn truncate_to_tokens(text: &str, max_tokens: u64) -> String {
+    let max_chars = (max_tokens * 4) as usize;
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let kept: String = text.chars().take(max_chars.saturating_sub(TRUNCATION_MARKER.len())).collect();
+    format!("{}{}", kept, TRUNCATION_MARKER)
+}
+
+impl MCPManager {
+    /// Makes the prompt of `llm_request` (its last message) fit the context window of the first route's model,
+    /// re-rendering the template with shortened input fields as the definition's policy allows.
+    /// Returns the token usage of summarization calls, which the caller adds to its own.
+    pub(super) async fn guard_context_window(
+        &self,
+        mcp_definition: &MCPDefinition,
+        input: &MCPInput,
+        routing_policy: &RoutingPolicy,
+        llm_request: &mut LLMRequest,
+    ) -> Result<Option<LLMTokenCounts>> {
+        let policy = mcp_definition.context_overflow.clone().unwrap_or_default();
+        let estimated_tokens = estimate_request_tokens(llm_request, policy.reserved_completion_tokens);
+        if estimated_tokens <= MIN_GUARDED_PROMPT_TOKENS {
+            return Ok(None);
+        }
+        let Some((model, context_length)) = self.context_window(&policy, routing_policy, llm_request).await else {
+            return Ok(None);
+        };
+        if estimated_tokens <= context_length {
+            return Ok(None);
+        }
+
+        let exceeded = |estimated_tokens| ContextLengthExceeded {
+            mcp_id: mcp_definition.id.clone(),
+            model: model.clone(),
+            estimated_tokens,
+            context_length,
+        };
+        if policy.strategy == ContextOverflowStrategy::Fail || policy.fields.is_empty() {
+            return Err(exceeded(estimated_tokens).into());
+        }
+
+        println!("MCP {} prompt needs ~{} tokens, over the {} of {}: applying {:?}", mcp_definition.id, estimated_tokens, context_length, model, policy.strategy);
+        let mut data = input.data.clone();
+        let mut summarization_usage = None;
+        let overflow = estimated_tokens - context_length;
+
+        // The largest fields are shortened first; each gives up as much as is still needed
+        let mut fields: Vec<(String, u64)> = policy.fields.iter()
+            .filter_map(|field| data.get(field).and_then(Value::as_str).map(|text| (field.clone(), estimate_text_tokens(text))))
+            .collect();
+        fields.sort_by_key(|(_, field_tokens)| std::cmp::Reverse(*field_tokens));
+        let mut remaining_overflow = overflow;
+        for (field, field_tokens) in fields {
+            if remaining_overflow == 0 {
+                break;
+            }
+            // Keep a margin for the marker and the estimate's error
+            let target_tokens = field_tokens.saturating_sub(remaining_overflow + remaining_overflow / 10 + 16);
+            let text = data[&field].as_str().unwrap_or_default().to_string();
+            let shortened = match policy.strategy {
+                ContextOverflowStrategy::Summarize => {
+                    let (summary, usage) = self.summarize_for_context(input, &text, target_tokens).await?;
+                    if let Some(usage) = usage {
+                        let total: &mut LLMTokenCounts = summarization_usage.get_or_insert_with(LLMTokenCounts::default);
+                        total.prompt_tokens += usage.prompt_tokens;
+                        total.completion_tokens += usage.completion_tokens;
+                        total.total_tokens += usage.total_tokens;
+                    }
+                    summary
+                }
+                _ => truncate_to_tokens(&text, target_tokens),
+            };
+            remaining_overflow = remaining_overflow.saturating_sub(field_tokens.saturating_sub(estimate_text_tokens(&shortened)));
+            data[&field] = json!(shortened);
+        }
+
+        // Native MCPs rebuild their whole conversation from the shortened input
+        match self.native_prompt(mcp_definition)? {
+            Some(native_prompt) => {
+                let shortened_input = MCPInput { data, ..input.clone() };
+                llm_request.messages = native_prompt.prepare_llm_request(&shortened_input, mcp_definition).await?.messages;
+            }
+            None => {
+                let prompt = self.handlebars.render_template(&mcp_definition.template, &data)?;
+                if let Some(last_message) = llm_request.messages.last_mut() {
+                    last_message.content = prompt;
+                }
+            }
+        }
+        let estimated_tokens = estimate_request_tokens(llm_request, policy.reserved_completion_tokens);
+        if estimated_tokens > context_length {
+            return Err(exceeded(estimated_tokens).into());
+        }
+        Ok(summarization_usage)
+    }
+
+    /// Fails if `llm_request` no longer fits the context window, without shortening anything; for
+    /// follow-up turns, whose input fields were already shortened by `guard_context_window`.
+    pub(super) async fn check_context_window(
+        &self,
+        mcp_definition: &MCPDefinition,
+        routing_policy: &RoutingPolicy,
+        llm_request: &LLMRequest,
+    ) -> Result<(), ContextLengthExceeded> {
+        let policy = mcp_definition.context_overflow.clone().unwrap_or_default();
+        let estimated_tokens = estimate_request_tokens(llm_request, policy.reserved_completion_tokens);
+        if estimated_tokens <= MIN_GUARDED_PROMPT_TOKENS {
+            return Ok(());
+        }
+        match self.context_window(&policy, routing_policy, llm_request).await {
+            Some((model, context_length)) if estimated_tokens > context_length => Err(ContextLengthExceeded {
+                mcp_id: mcp_definition.id.clone(),
+                model,
+                estimated_tokens,
+                context_length,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    // Model expected to serve the request and its context length, when known
+    async fn context_window(&self, policy: &ContextOverflowPolicy, routing_policy: &RoutingPolicy, llm_request: &LLMRequest) -> Option<(String, u64)> {
+        // Fallback routes may use models with other windows; the first route is the one expected to serve
+        let route = routing_policy.routes.first()?;
+        let model = route.model.clone()
+            .filter(|model| !model.is_empty())
+            .unwrap_or_else(|| llm_request.model.clone());
+        let context_length = match self.external_api_client.context_length(&route.provider, &model).await {
+            Some(context_length) => Some(context_length),
+            None => policy.max_context_tokens,
+        }?;
+        Some((model, context_length))
+    }
+
+    /// Summary of `text` in about `target_tokens`, made with the caller's provider and model.
+    async fn summarize_for_context(&self, input: &MCPInput, text: &str, target_tokens: u64) -> Result<(String, Option<LLMTokenCounts>)> {
+        let caller_overrides = input.context_overrides.as_ref();
+        let summary_input = MCPInput {
+            mcp_id: SUMMARIZE_MCP_ID.to_string(),
+            // About three words per four tokens
+            data: json!({ "text_to_summarize": text, "max_length": (target_tokens * 3 / 4).max(50) }),
+            context_overrides: Some(MCPContextOverrides {
+                llm_provider: caller_overrides.and_then(|c| c.llm_provider.clone()),
+                llm_model: caller_overrides.and_then(|c| c.llm_model.clone()),
+                llm_parameters: None,
+                additional_context: None,
+                routing_policy: caller_overrides.and_then(|c| c.routing_policy.clone()),
+                bypass_cache: false,
+                prior_messages: Vec::new(),
+                tools_enabled: false,
+                task_id: caller_overrides.and_then(|c| c.task_id.clone()),
+            }),
+        };
+        let summary_output = Box::pin(self.invoke_mcp(summary_input)).await?.into_result()?;
+        let usage = summary_output.usage_metrics.as_ref().and_then(|metrics| metrics.llm_token_counts.clone());
+        let summary = summary_output.processed_content.as_ref()
+            .and_then(|content| content.get("summary"))
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("{} returned no summary", SUMMARIZE_MCP_ID))?;
+        // The model may not respect the requested length
+        Ok((truncate_to_tokens(summary.trim(), target_tokens), usage))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use crate::external_api_client::{LLMError, LLMMessage, LLMModelInfo, LLMResponse};
+    use crate::external_api_client::providers::LLMProvider;
+    use crate::external_api_client::providers::scripted_provider::ScriptedProvider;
+    use crate::mcp_manager::definitions::DefinitionSet;
+    use crate::mcp_manager::MCP_DEFINITIONS_DIR;
+    use std::path::Path;
+
+    /// Provider whose catalog gives `large-model` a 200k window; it is never called.
+    #[derive(Debug)]
+    struct CatalogProvider;
+
+    #[async_trait]
+    impl LLMProvider for CatalogProvider {
+        fn name(&self) -> String {
+            "Catalog".to_string()
+        }
+
+        async fn generate(&self, _request: LLMRequest) -> Result<LLMResponse, LLMError> {
+            Err(LLMError::InvalidRequest { provider: self.name(), status: None, message: "not expected".to_string() })
+        }
+
+        async fn list_models(&self) -> Result<Vec<LLMModelInfo>, anyhow::Error> {
+            Ok(vec![LLMModelInfo { id: "large-model".to_string(), name: None, context_length: Some(200_000), pricing: None, modality: None }])
+        }
+    }
+
+    #[test]
+    fn test_truncate_to_tokens() {
+        let text = "word ".repeat(1000);
+        assert_eq!(truncate_to_tokens("short", 10), "short");
+        let truncated = truncate_to_tokens(&text, 100);
+        assert!(truncated.ends_with(TRUNCATION_MARKER));
+        assert!(estimate_text_tokens(&truncated) <= 100);
+    }
+
+    #[tokio::test]
+    async fn test_guard_truncates_marked_fields_or_fails() {
+        let scripted = ScriptedProvider::from_entries(Vec::new()).unwrap();
+        let manager = MCPManager::for_test(Vec::new(), vec![Box::new(scripted)]);
+        let mut definition: MCPDefinition = serde_json::from_value(json!({
+            "id": "review_v1",
+            "description": "Reviews a deliverable",
+            "template": "Review this:\n{{deliverable}}",
+            "logic_module_path": null,
+            "default_llm_provider": "Scripted",
+            "default_llm_model": "scripted-model",
+            "default_llm_parameters": null,
+            "context_overflow": { "strategy": "truncate", "fields": ["deliverable"], "max_context_tokens": 8000 }
+        })).unwrap();
+        let input = MCPInput { mcp_id: "review_v1".to_string(), data: json!({ "deliverable": "x".repeat(60_000) }), context_overrides: None };
+        let policy = RoutingPolicy::single("Scripted", None);
+        let request = || LLMRequest::from_prompt("scripted-model", format!("Review this:\n{}", "x".repeat(60_000)), None);
+
+        let mut truncated = request();
+        manager.guard_context_window(&definition, &input, &policy, &mut truncated).await.unwrap();
+        assert!(estimate_request_tokens(&truncated, 1024) <= 8000);
+        assert!(truncated.last_user_prompt().starts_with("Review this:\nxxx"));
+        assert!(truncated.messages.last().unwrap().content.ends_with(TRUNCATION_MARKER));
+
+        definition.context_overflow.as_mut().unwrap().strategy = ContextOverflowStrategy::Fail;
+        let mut failing = request();
+        let error = manager.guard_context_window(&definition, &input, &policy, &mut failing).await.unwrap_err();
+        let exceeded = error.downcast_ref::<ContextLengthExceeded>().unwrap();
+        assert_eq!(exceeded.context_length, 8000);
+        assert_eq!(failing.messages, vec![LLMMessage::user(format!("Review this:\n{}", "x".repeat(60_000)))]);
+    }
+
+    #[tokio::test]
+    async fn test_catalog_context_length_takes_precedence_over_max_context_tokens() {
+        let manager = MCPManager::for_test(Vec::new(), vec![Box::new(CatalogProvider)]);
+        let definition: MCPDefinition = serde_json::from_value(json!({
+            "id": "review_v1",
+            "description": "Reviews a deliverable",
+            "template": "Review this:\n{{deliverable}}",
+            "logic_module_path": null,
+            "default_llm_provider": "Catalog",
+            "default_llm_model": "large-model",
+            "default_llm_parameters": null,
+            "context_overflow": { "strategy": "truncate", "fields": ["deliverable"], "max_context_tokens": 8000 }
+        })).unwrap();
+        let input = MCPInput { mcp_id: "review_v1".to_string(), data: json!({ "deliverable": "x".repeat(60_000) }), context_overrides: None };
+        let prompt = format!("Review this:\n{}", "x".repeat(60_000));
+
+        let mut request = LLMRequest::from_prompt("large-model", prompt.clone(), None);
+        manager.guard_context_window(&definition, &input, &RoutingPolicy::single("Catalog", None), &mut request).await.unwrap();
+        assert_eq!(request.messages, vec![LLMMessage::user(prompt.clone())]);
+
+        // The catalog does not list this model, so max_context_tokens applies
+        let mut unknown = LLMRequest::from_prompt("other-model", prompt, None);
+        manager.guard_context_window(&definition, &input, &RoutingPolicy::single("Catalog", None), &mut unknown).await.unwrap();
+        assert!(estimate_request_tokens(&unknown, 1024) <= 8000);
+    }
+
+    #[tokio::test]
+    async fn test_guard_summarizes_marked_fields_with_the_shipped_summarizer() {
+        let scripted = ScriptedProvider::from_json_str(r#"{ "responses": [
+            { "prompt_regex": "expert summarizer", "response": "{\"summary\": \"Bees pollinate flowers.\"}" }
+        ] }"#).unwrap();
+        let (loaded, errors) = DefinitionSet::read_directory(Path::new(MCP_DEFINITIONS_DIR), &DefinitionSet::default()).unwrap();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let summarizer = loaded.get(SUMMARIZE_MCP_ID).cloned().unwrap();
+        let manager = MCPManager::for_test(vec![summarizer], vec![Box::new(scripted)]);
+        let definition: MCPDefinition = serde_json::from_value(json!({
+            "id": "review_v1",
+            "description": "Reviews a deliverable",
+            "template": "Review this:\n{{deliverable}}",
+            "logic_module_path": null,
+            "default_llm_provider": "Scripted",
+            "default_llm_model": "scripted-model",
+            "default_llm_parameters": null,
+            "context_overflow": { "strategy": "summarize", "fields": ["deliverable"], "max_context_tokens": 8000 }
+        })).unwrap();
+        // The summary is made with the caller's provider and model
+        let overrides = MCPContextOverrides {
+            llm_provider: Some("Scripted".to_string()),
+            llm_model: Some("scripted-model".to_string()),
+            llm_parameters: None,
+            additional_context: None,
+            routing_policy: None,
+            bypass_cache: false,
+            prior_messages: Vec::new(),
+            tools_enabled: false,
+            task_id: None,
+        };
+        let input = MCPInput { mcp_id: "review_v1".to_string(), data: json!({ "deliverable": "x".repeat(60_000) }), context_overrides: Some(overrides) };
+
+        let mut request = LLMRequest::from_prompt("scripted-model", format!("Review this:\n{}", "x".repeat(60_000)), None);
+        manager.guard_context_window(&definition, &input, &RoutingPolicy::single("Scripted", None), &mut request).await.unwrap();
+        assert_eq!(request.last_user_prompt(), "Review this:\nBees pollinate flowers.");
+    }
+}
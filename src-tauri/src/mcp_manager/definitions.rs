@@ -0,0 +1,279 @@
+use anyhow::{anyhow, Result};
+use notify::{RecursiveMode, Watcher};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use super::MCPManager;
+use crate::common_types::MCPDefinition;
+
+// Editors save a file in several writes; changes closer together than this are reloaded once
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// MCP definitions by id, as loaded from the definitions directory.
+#[derive(Debug, Clone, Default)]
+pub struct DefinitionSet {
+    by_id: HashMap<String, MCPDefinition>,
+    // File each definition was read from, so a file that stops parsing keeps its last good version
+    sources: HashMap<PathBuf, String>,
+}
+
+impl DefinitionSet {
+    /// Set of definitions that were not read from files.
+    pub fn from_definitions(definitions: impl IntoIterator<Item = MCPDefinition>) -> Self {
+        DefinitionSet {
+            by_id: definitions.into_iter().map(|definition| (definition.id.clone(), definition)).collect(),
+            sources: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&MCPDefinition> {
+        self.by_id.get(id)
+    }
+
+    /// Reads every `*.mcp.json` file of `directory`. A file that cannot be read, parsed or used is
+    /// reported in the returned errors and keeps the version it had in `previous`, if any; a file
+    /// repeating an ID already loaded is reported and skipped.
+    pub fn read_directory(directory: &Path, previous: &DefinitionSet) -> Result<(DefinitionSet, Vec<DefinitionLoadError>)> {
+        if !directory.is_dir() {
+            return Err(anyhow!("Directory not found or is not a directory: {:?}", directory));
+        }
+
+        let mut paths: Vec<PathBuf> = fs::read_dir(directory)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && is_definition_file(path))
+            .collect();
+        // Sorted so the same duplicate is skipped on every load
+        paths.sort();
+
+        let mut loaded = DefinitionSet::default();
+        let mut errors = Vec::new();
+        for path in paths {
+            let parsed = fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|file_content| serde_json::from_str::<MCPDefinition>(&file_content).map_err(anyhow::Error::from))
+                .and_then(|definition| match definition_problem(&definition) {
+                    Some(problem) => Err(anyhow!(problem)),
+                    None => Ok(definition),
+                });
+            let definition = match parsed {
+                Ok(definition) => definition,
+                Err(e) => {
+                    eprintln!("Warning: Failed to load MCP definition from {:?}: {}", path, e);
+                    errors.push(DefinitionLoadError { path: path.display().to_string(), message: e.to_string() });
+                    match previous.sources.get(&path).and_then(|id| previous.by_id.get(id)) {
+                        Some(last_good) => last_good.clone(),
+                        None => continue,
+                    }
+                }
+            };
+
+            if loaded.by_id.contains_key(&definition.id) {
+                let message = format!("duplicate MCP definition ID '{}'", definition.id);
+                eprintln!("Warning: Skipping {} from {:?}.", message, path);
+                errors.push(DefinitionLoadError { path: path.display().to_string(), message });
+                continue;
+            }
+
+            println!("Loaded MCP definition: {}", definition.id);
+            loaded.sources.insert(path, definition.id.clone());
+            loaded.by_id.insert(definition.id.clone(), definition);
+        }
+        Ok((loaded, errors))
+    }
+}
+
+// Why a definition that parsed cannot be used, if it cannot
+fn definition_problem(definition: &MCPDefinition) -> Option<String> {
+    if definition.id.is_empty() {
+        return Some("definition has an empty ID".to_string());
+    }
+    if let Some(engine) = definition.template_engine.as_deref().filter(|engine| *engine != "handlebars") {
+        return Some(format!("unsupported template engine '{}'", engine));
+    }
+    // `template` may only be omitted when a native implementation builds the prompt
+    if definition.template.is_empty() && definition.logic_module_path.as_deref().unwrap_or_default().is_empty() {
+        return Some("definition has neither a template nor a logic_module_path".to_string());
+    }
+    None
+}
+
+/// A definition file that could not be read, parsed or used.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct DefinitionLoadError {
+    pub path: String,
+    pub message: String,
+}
+
+/// What changed when the definitions directory was reloaded.
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct DefinitionsReloaded {
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    pub removed: Vec<String>,
+    pub errors: Vec<DefinitionLoadError>,
+}
+
+fn is_definition_file(path: &Path) -> bool {
+    path.file_name().and_then(|name| name.to_str()).is_some_and(|name| name.ends_with(".mcp.json"))
+}
+
+impl MCPManager {
+    /// Re-reads the definitions directory and swaps the result in at once; invocations already
+    /// running keep the definitions they started with.
+    pub fn reload_definitions(&self, directory: &Path) -> Result<DefinitionsReloaded> {
+        let previous = self.definitions();
+        let (loaded, errors) = DefinitionSet::read_directory(directory, &previous)?;
+
+        let mut reloaded = DefinitionsReloaded { errors, ..Default::default() };
+        for (id, definition) in &loaded.by_id {
+            match previous.by_id.get(id) {
+                None => reloaded.added.push(id.clone()),
+                Some(previous_definition) if previous_definition != definition => reloaded.changed.push(id.clone()),
+                Some(_) => {}
+            }
+        }
+        reloaded.removed = previous.by_id.keys().filter(|id| !loaded.by_id.contains_key(*id)).cloned().collect();
+        reloaded.added.sort();
+        reloaded.changed.sort();
+        reloaded.removed.sort();
+
+        self.swap_definitions(loaded);
+        println!("Reloaded MCP definitions: {} added, {} changed, {} removed, {} failed to load",
+            reloaded.added.len(), reloaded.changed.len(), reloaded.removed.len(), reloaded.errors.len());
+        Ok(reloaded)
+    }
+
+    /// Watches `directory` and reloads the definitions when a `*.mcp.json` file is added, changed
+    /// or removed. Each reload is reported on the returned channel; watching stops once it is dropped.
+    /// Must be called from within a Tokio runtime.
+    pub fn watch_definitions(self: &Arc<Self>, directory: impl Into<PathBuf>) -> Result<mpsc::Receiver<DefinitionsReloaded>> {
+        let directory = directory.into();
+        // Capacity 1: a change noticed while a reload is pending is already covered by it
+        let (change_sender, mut change_receiver) = mpsc::channel::<()>(1);
+        let mut watcher = notify::recommended_watcher(move |result: notify::Result<notify::Event>| match result {
+            Ok(event) if event.paths.iter().any(|path| is_definition_file(path)) => {
+                let _ = change_sender.try_send(());
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("Warning: Error while watching MCP definitions: {}", e),
+        })?;
+        watcher.watch(&directory, RecursiveMode::NonRecursive)?;
+        println!("Watching MCP definitions in: {:?}", directory);
+
+        let (reload_sender, reload_receiver) = mpsc::channel(16);
+        // Weak, so the watch does not keep the manager alive
+        let manager = Arc::downgrade(self);
+        tokio::spawn(async move {
+            // The watcher stops when dropped, so it lives in this task
+            let _watcher = watcher;
+            while change_receiver.recv().await.is_some() {
+                tokio::time::sleep(RELOAD_DEBOUNCE).await;
+                let _ = change_receiver.try_recv();
+                let Some(manager) = manager.upgrade() else {
+                    break;
+                };
+                match manager.reload_definitions(&directory) {
+                    Ok(reloaded) => {
+                        if reload_sender.send(reloaded).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => eprintln!("Warning: Failed to reload MCP definitions from {:?}: {}", directory, e),
+                }
+            }
+        });
+        Ok(reload_receiver)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_definition(directory: &Path, file_name: &str, id: &str, template: &str) {
+        let definition = serde_json::json!({
+            "id": id,
+            "description": "Test definition",
+            "template": template,
+            "logic_module_path": null,
+            "default_llm_provider": null,
+            "default_llm_model": null,
+            "default_llm_parameters": null
+        });
+        fs::write(directory.join(file_name), definition.to_string()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reload_swaps_definitions_and_reports_parse_errors() {
+        let directory = std::env::temp_dir().join(format!("hive_mcps_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&directory).unwrap();
+        write_definition(&directory, "draft.mcp.json", "draft_v1", "Write about {{topic}}");
+        write_definition(&directory, "review.mcp.json", "review_v1", "Review {{text}}");
+
+        let manager = Arc::new(MCPManager::for_test(Vec::new(), Vec::new()));
+        let loaded = manager.load_definitions(directory.to_str().unwrap()).await.unwrap();
+        assert_eq!(loaded.added, vec!["draft_v1".to_string(), "review_v1".to_string()]);
+        assert!(manager.definitions().get("review_v1").is_some());
+        let mut reloads = manager.watch_definitions(&directory).unwrap();
+
+        write_definition(&directory, "draft.mcp.json", "draft_v1", "Write a short text about {{topic}}");
+        fs::remove_file(directory.join("review.mcp.json")).unwrap();
+        write_definition(&directory, "summary.mcp.json", "summary_v1", "Summarize {{text}}");
+        // The writes may straddle several reloads; wait for the one that sees all of them
+        loop {
+            let reloaded = tokio::time::timeout(Duration::from_secs(10), reloads.recv()).await.unwrap().unwrap();
+            assert!(reloaded.errors.is_empty());
+            let definitions = manager.definitions();
+            if definitions.get("draft_v1").unwrap().template.starts_with("Write a short text")
+                && definitions.get("review_v1").is_none()
+                && definitions.get("summary_v1").is_some()
+            {
+                break;
+            }
+        }
+
+        // A file that stops parsing keeps its last good version and is reported
+        fs::write(directory.join("draft.mcp.json"), "{ \"id\": \"draft_v1\", ").unwrap();
+        let (reloaded_from_disk, errors) = DefinitionSet::read_directory(&directory, &manager.definitions()).unwrap();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].path.ends_with("draft.mcp.json"));
+        assert!(reloaded_from_disk.get("draft_v1").unwrap().template.starts_with("Write a short text"));
+
+        // Definitions that parse but cannot be used are reported too
+        fs::write(directory.join("empty.mcp.json"), "{ \"id\": \"empty_v1\", \"description\": \"No prompt\", \"logic_module_path\": null }").unwrap();
+        write_definition(&directory, "no_id.mcp.json", "", "Hello");
+        write_definition(&directory, "summary_copy.mcp.json", "summary_v1", "Summarize {{text}} again");
+        let (reloaded_from_disk, errors) = DefinitionSet::read_directory(&directory, &manager.definitions()).unwrap();
+        assert!(reloaded_from_disk.get("empty_v1").is_none());
+        assert_eq!(reloaded_from_disk.get("summary_v1").unwrap().template, "Summarize {{text}}");
+        let mut failed_files: Vec<&str> = errors.iter().map(|error| error.path.rsplit(['/', '\\']).next().unwrap()).collect();
+        failed_files.sort();
+        assert_eq!(failed_files, vec!["draft.mcp.json", "empty.mcp.json", "no_id.mcp.json", "summary_copy.mcp.json"]);
+
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[tokio::test]
+    async fn test_startup_load_keeps_good_definitions_and_reports_bad_files() {
+        let directory = std::env::temp_dir().join(format!("hive_mcps_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&directory).unwrap();
+        write_definition(&directory, "draft.mcp.json", "draft_v1", "Write about {{topic}}");
+        write_definition(&directory, "draft_copy.mcp.json", "draft_v1", "Write again about {{topic}}");
+        write_definition(&directory, "no_id.mcp.json", "", "Hello");
+        fs::write(directory.join("broken.mcp.json"), "{ \"id\": ").unwrap();
+
+        let manager = MCPManager::for_test(Vec::new(), Vec::new());
+        let loaded = manager.load_definitions(directory.to_str().unwrap()).await.unwrap();
+        assert_eq!(loaded.added, vec!["draft_v1".to_string()]);
+        assert_eq!(manager.definitions().get("draft_v1").unwrap().template, "Write about {{topic}}");
+        assert_eq!(loaded.errors.len(), 3);
+
+        assert!(manager.load_definitions(directory.join("missing").to_str().unwrap()).await.is_err());
+        let _ = fs::remove_dir_all(&directory);
+    }
+}
@@ -0,0 +1,252 @@
+use serde_json::Value;
+
+/// Completion from which no JSON object could be read.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum JsonExtractionError {
+    #[error("completion contains no JSON object")]
+    NoObject,
+    #[error("completion contains no valid JSON object: {message}")]
+    Invalid { message: String },
+}
+
+/// Every JSON object that can be read from the completion. Models wrap their answer in markdown
+/// fences, surround it with prose or slip on the syntax, so the text is searched rather than parsed
+/// as a whole: the outermost `{...}` of the whole completion first, then those of its fenced blocks.
+/// An object that does not parse strictly is retried after repairing trailing commas, comments,
+/// Python literals (`True`, `None`), typographic quotes and raw newlines inside strings; the braces
+/// of one that still fails are skipped, unless it is never closed, in which case it is searched for
+/// nested objects. Largest first, so a `{}` in the prose does not win over the answer; never empty.
+pub fn extract_json_objects(completion: &str) -> Result<Vec<Value>, JsonExtractionError> {
+    let mut sources = vec![completion];
+    sources.extend(fenced_blocks(completion));
+
+    let mut candidates = Vec::new();
+    let mut first_error = None;
+    for source in sources {
+        // Objects nested in one that parsed belong to it, and those nested in a closed one that
+        // failed would each rescan and repair the rest of it
+        let mut covered_until = 0;
+        for (start, _) in source.match_indices('{') {
+            if start < covered_until {
+                continue;
+            }
+            let (object_text, closed) = balanced_object(&source[start..]);
+            let parsed = serde_json::from_str::<Value>(object_text).or_else(|e| {
+                serde_json::from_str::<Value>(&repair(object_text)).map_err(|_| e)
+            });
+            match parsed {
+                Ok(value) => {
+                    covered_until = start + object_text.len();
+                    candidates.push(Candidate { text_len: object_text.len(), value });
+                }
+                Err(e) => {
+                    if closed {
+                        covered_until = start + object_text.len();
+                    }
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+    }
+
+    if candidates.is_empty() {
+        return Err(match first_error {
+            Some(e) => JsonExtractionError::Invalid { message: e.to_string() },
+            None => JsonExtractionError::NoObject,
+        });
+    }
+    // Stable, so among equal sizes the whole completion's object stays ahead of a fenced copy
+    candidates.sort_by_key(|candidate| std::cmp::Reverse(candidate.text_len));
+    Ok(candidates.into_iter().map(|candidate| candidate.value).collect())
+}
+
+struct Candidate {
+    text_len: usize,
+    value: Value,
+}
+
+// Contents of ``` fenced blocks, in order; the info string ("json") after the opening fence is skipped
+fn fenced_blocks(text: &str) -> Vec<&str> {
+    let mut blocks = Vec::new();
+    let mut rest = text;
+    while let Some(open) = rest.find("```") {
+        let after_fence = &rest[open + 3..];
+        let body_start = after_fence.find('\n').map_or(after_fence.len(), |newline| newline + 1);
+        let body = &after_fence[body_start..];
+        match body.find("```") {
+            Some(close) => {
+                blocks.push(&body[..close]);
+                rest = &body[close + 3..];
+            }
+            None => {
+                // An unclosed fence, e.g. a completion cut off by max_tokens
+                blocks.push(body);
+                break;
+            }
+        }
+    }
+    blocks
+}
+
+// From an opening brace to its matching closing brace, or to the end when it is never closed;
+// tells which of the two it is
+fn balanced_object(text: &str) -> (&str, bool) {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (index, c) in text.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return (&text[..=index], true);
+                }
+            }
+            _ => {}
+        }
+    }
+    (text, false)
+}
+
+fn repair(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut repaired = String::with_capacity(text.len());
+    // Quote that opened the current string; a right typographic quote only closes one opened
+    // by a left one, elsewhere it is part of the text
+    let mut string_opener = None;
+    let mut index = 0;
+
+    while index < chars.len() {
+        let c = chars[index];
+        if let Some(opener) = string_opener {
+            match c {
+                '\\' if index + 1 < chars.len() => {
+                    repaired.push(c);
+                    repaired.push(chars[index + 1]);
+                    index += 1;
+                }
+                '"' => {
+                    repaired.push('"');
+                    string_opener = None;
+                }
+                '\u{201D}' if opener == '\u{201C}' => {
+                    repaired.push('"');
+                    string_opener = None;
+                }
+                '\n' => repaired.push_str("\\n"),
+                '\r' => repaired.push_str("\\r"),
+                '\t' => repaired.push_str("\\t"),
+                _ => repaired.push(c),
+            }
+            index += 1;
+            continue;
+        }
+
+        match c {
+            '"' | '\u{201C}' | '\u{201D}' => {
+                repaired.push('"');
+                string_opener = Some(c);
+            }
+            '/' if chars.get(index + 1) == Some(&'/') => {
+                while index < chars.len() && chars[index] != '\n' {
+                    index += 1;
+                }
+                continue;
+            }
+            '/' if chars.get(index + 1) == Some(&'*') => {
+                index += 2;
+                while index < chars.len() && !(chars[index] == '*' && chars.get(index + 1) == Some(&'/')) {
+                    index += 1;
+                }
+                index += 2;
+                continue;
+            }
+            ',' => {
+                let next = chars[index + 1..].iter().find(|c| !c.is_whitespace());
+                if !matches!(next, Some('}') | Some(']')) {
+                    repaired.push(c);
+                }
+            }
+            _ if c.is_ascii_alphabetic() => {
+                let word_end = chars[index..].iter().position(|c| !c.is_ascii_alphanumeric() && *c != '_').map_or(chars.len(), |end| index + end);
+                let word: String = chars[index..word_end].iter().collect();
+                repaired.push_str(match word.as_str() {
+                    "True" => "true",
+                    "False" => "false",
+                    "None" => "null",
+                    _ => &word,
+                });
+                index = word_end;
+                continue;
+            }
+            _ => repaired.push(c),
+        }
+        index += 1;
+    }
+    repaired
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_object_is_found_around_prose_and_fences() {
+        let fenced = "Here is the plan:\n```json\n{\"subtasks\": [{\"title\": \"Draft\"}]}\n```\nLet me know if {anything} is missing.";
+        assert_eq!(extract_json_objects(fenced).unwrap()[0], json!({ "subtasks": [{ "title": "Draft" }] }));
+
+        let prose = "Use the {name} placeholder. {\"generated_code\": \"fn main() {}\"} Hope this helps!";
+        assert_eq!(extract_json_objects(prose).unwrap()[0], json!({ "generated_code": "fn main() {}" }));
+
+        // An unclosed brace in the prose does not hide the answer after it
+        let unclosed = "Use {name as the placeholder. {\"a\": 1}";
+        assert_eq!(extract_json_objects(unclosed).unwrap()[0], json!({ "a": 1 }));
+
+        assert_eq!(extract_json_objects("No JSON here."), Err(JsonExtractionError::NoObject));
+        assert!(matches!(extract_json_objects("{\"a\": [1, 2"), Err(JsonExtractionError::Invalid { .. })));
+    }
+
+    #[test]
+    fn test_largest_object_wins_over_stray_braces() {
+        // A fence inside a string value does not displace the object holding it
+        let fence_in_value = "{\"generated_code\": \"fn main() {}\", \"explanation\": \"Paste it as\\n```rust\\n{}\\n```\"}";
+        assert_eq!(
+            extract_json_objects(fence_in_value).unwrap()[0],
+            json!({ "generated_code": "fn main() {}", "explanation": "Paste it as\n```rust\n{}\n```" })
+        );
+        let raw_fence_in_value = "Result:\n{\"explanation\": \"Wrap it:\n```rust\n{}\n```\", \"generated_code\": \"x\"}";
+        assert_eq!(
+            extract_json_objects(raw_fence_in_value).unwrap()[0],
+            json!({ "explanation": "Wrap it:\n```rust\n{}\n```", "generated_code": "x" })
+        );
+
+        let empty_in_prose = "An empty config is {} but here is yours: {\"subtasks\": []}";
+        assert_eq!(extract_json_objects(empty_in_prose).unwrap()[0], json!({ "subtasks": [] }));
+        assert_eq!(extract_json_objects(empty_in_prose).unwrap(), vec![json!({ "subtasks": [] }), json!({})]);
+    }
+
+    #[test]
+    fn test_common_syntax_slips_are_repaired() {
+        let sloppy = "{\n  // validation result\n  \"is_valid\": True,\n  \"feedback\": \u{201C}Too short\u{201D},\n  \"details\": None,\n  \"code\": \"line one\nline two\",\n  \"criteria_results\": [\"a\", \"b\",],\n}";
+        assert_eq!(
+            extract_json_objects(sloppy).unwrap()[0],
+            json!({ "is_valid": true, "feedback": "Too short", "details": null, "code": "line one\nline two", "criteria_results": ["a", "b"] })
+        );
+
+        // Right typographic quotes inside a plain string are text, not its end
+        let curly_in_value = "{\"feedback\": \"He said \u{201D}hi\u{201D}\",}";
+        assert_eq!(extract_json_objects(curly_in_value).unwrap()[0], json!({ "feedback": "He said \u{201D}hi\u{201D}" }));
+    }
+}
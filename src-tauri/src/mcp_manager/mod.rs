@@ -1,137 +1,673 @@
 use anyhow::{anyhow, Result};
-use std::collections::HashMap;
-use std::fs;
 use std::path::Path;
-use crate::common_types::{MCPDefinition, MCPInput, MCPOutput, MCPStatus};
+use crate::common_types::{MCPAttempt, MCPDefinition, MCPInput, MCPOutput, MCPStatus, MCPUsageMetrics};
 use serde_json::{self, Value};
 use handlebars::Handlebars;
 use uuid::Uuid;
-use std::sync::Arc;
-use crate::external_api_client::{ExternalApiClient, LLMRequest};
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+use tokio::sync::mpsc;
+use crate::external_api_client::{ExternalApiClient, LLMCallOptions, LLMError, LLMMessage, LLMParameters, LLMRequest, LLMResponse, LLMTokenCounts, RoutingPolicy};
 
+pub mod cancellation;
+pub mod context_guard;
+pub mod definitions;
+pub mod json_extraction;
+pub mod schema;
+pub mod tools;
 pub mod traits; // Declare the traits submodule
 
+use cancellation::TaskCancellationRegistry;
+use context_guard::ContextLengthExceeded;
+use definitions::{DefinitionSet, DefinitionsReloaded};
+use schema::{describe_violations, SchemaViolation};
+use tools::ToolRegistry;
+use traits::{MetaContextualPrompt, PromptRegistry};
+
+/// Directory of the `*.mcp.json` definition files, relative to the working directory.
+pub const MCP_DEFINITIONS_DIR: &str = "config/mcps/";
+
+// Tool-call round trips allowed in one MCP invocation before the model must answer
+const MAX_TOOL_ROUNDS: usize = 8;
+
+/// Streamed before the completion of a repair turn, after the rejected one.
+pub const REPAIR_STREAM_SEPARATOR: &str = "\n\n--- correction ---\n\n";
+
 #[derive(Debug)] // Added Debug derive
 pub struct MCPManager {
-    definitions: HashMap<String, MCPDefinition>,
+    // Swapped whole on reload, so an invocation never sees half of a reload
+    definitions: RwLock<Arc<DefinitionSet>>,
     handlebars: Handlebars<'static>,
     external_api_client: Arc<ExternalApiClient>,
+    tool_registry: Arc<ToolRegistry>,
+    cancellation: Arc<TaskCancellationRegistry>,
+    prompt_registry: Arc<PromptRegistry>,
 }
 
 impl MCPManager {
+    /// Manager without definitions; load them with `load_definitions`.
     pub async fn new(external_api_client: Arc<ExternalApiClient>) -> Result<Self> {
         println!("MCPManager created");
         let mut handlebars = Handlebars::new();
         handlebars.set_strict_mode(true); // Configure handlebars
-        let mut manager = MCPManager {
-            definitions: HashMap::new(),
+        let manager = MCPManager {
+            definitions: RwLock::new(Arc::new(DefinitionSet::default())),
             handlebars,
             external_api_client,
+            tool_registry: Arc::new(ToolRegistry::with_builtin_tools()),
+            cancellation: Arc::new(TaskCancellationRegistry::new()),
+            prompt_registry: Arc::new(PromptRegistry::default()),
         };
-        manager.load_definitions("config/mcps/").await?;
         Ok(manager)
     }
 
-    pub async fn load_definitions(&mut self, directory_path: &str) -> Result<()> {
-        let path = Path::new(directory_path);
-
-        if !path.exists() || !path.is_dir() {
-            return Err(anyhow!("Directory not found or is not a directory: {}", directory_path));
+    /// Manager serving `definitions` through `providers`, without reading the definitions directory.
+    #[cfg(test)]
+    pub(crate) fn for_test(definitions: impl IntoIterator<Item = MCPDefinition>, providers: Vec<Box<dyn crate::external_api_client::providers::LLMProvider>>) -> Self {
+        // Strict like the real manager, so tests catch templates that use absent fields
+        let mut handlebars = Handlebars::new();
+        handlebars.set_strict_mode(true);
+        MCPManager {
+            definitions: RwLock::new(Arc::new(DefinitionSet::from_definitions(definitions))),
+            handlebars,
+            external_api_client: Arc::new(ExternalApiClient::with_providers(providers)),
+            tool_registry: Arc::new(ToolRegistry::default()),
+            cancellation: Arc::new(TaskCancellationRegistry::new()),
+            prompt_registry: Arc::new(PromptRegistry::default()),
         }
+    }
 
+    /// Replaces the definitions with those of `directory_path`. Files that do not load are skipped
+    /// and listed in the returned report, like on a hot reload; only a missing directory fails.
+    pub async fn load_definitions(&self, directory_path: &str) -> Result<DefinitionsReloaded> {
         println!("Loading MCP definitions from: {}", directory_path);
+        self.reload_definitions(Path::new(directory_path))
+    }
 
-        for entry in fs::read_dir(path)? {
-            let entry = entry?;
-            let path = entry.path();
+    /// Definitions as currently loaded; a reload does not change a snapshot already taken.
+    pub fn definitions(&self) -> Arc<DefinitionSet> {
+        Arc::clone(&self.definitions.read().unwrap_or_else(|poisoned| poisoned.into_inner()))
+    }
 
-            if path.is_file() && path.extension().map_or(false, |ext| ext == "json" && path.to_string_lossy().ends_with(".mcp.json")) {
-                let file_content = fs::read_to_string(&path)?;
-                let definition: MCPDefinition = serde_json::from_str(&file_content)?;
+    fn swap_definitions(&self, definitions: DefinitionSet) {
+        *self.definitions.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Arc::new(definitions);
+    }
 
-                if definition.id.is_empty() {
-                    eprintln!("Warning: Skipping MCP definition from {:?} due to empty ID.", path);
-                    continue;
-                }
+    pub fn external_api_client(&self) -> &Arc<ExternalApiClient> {
+        &self.external_api_client
+    }
 
-                if self.definitions.contains_key(&definition.id) {
-                    eprintln!("Warning: Skipping duplicate MCP definition with ID '{}' from {:?}.", definition.id, path);
-                    continue;
-                }
+    /// Local tools MCPs can offer to the model; register orchestrator functions here.
+    pub fn tool_registry(&self) -> &Arc<ToolRegistry> {
+        &self.tool_registry
+    }
 
-                println!("Loaded MCP definition: {}", definition.id);
-                self.definitions.insert(definition.id.clone(), definition);
-            }
-        }
+    /// Cancellation tokens of tasks; calls made for a cancelled task are aborted.
+    pub fn cancellation_registry(&self) -> &Arc<TaskCancellationRegistry> {
+        &self.cancellation
+    }
 
-        Ok(())
+    /// Rust implementations of MCPs; register them before invoking the definitions that name them.
+    pub fn prompt_registry(&self) -> &Arc<PromptRegistry> {
+        &self.prompt_registry
+    }
+
+    // Implementation named by the definition's logic_module_path, if it names one
+    fn native_prompt(&self, mcp_definition: &MCPDefinition) -> Result<Option<Arc<dyn MetaContextualPrompt>>> {
+        let Some(logic_module) = mcp_definition.logic_module_path.as_deref().filter(|path| !path.is_empty()) else {
+            return Ok(None);
+        };
+        self.prompt_registry.get(logic_module)
+            .map(Some)
+            .ok_or_else(|| anyhow!("MCP {} uses logic module '{}', which is not registered", mcp_definition.id, logic_module))
     }
 
     pub async fn invoke_mcp(&self, input: MCPInput) -> Result<MCPOutput> {
+        self.execute_mcp(input, None).await
+    }
+
+    /// Invokes an MCP while streaming the completion's partial tokens through `chunk_sender`.
+    pub async fn invoke_mcp_streaming(&self, input: MCPInput, chunk_sender: mpsc::Sender<String>) -> Result<MCPOutput> {
+        self.execute_mcp(input, Some(chunk_sender)).await
+    }
+
+    async fn execute_mcp(&self, input: MCPInput, chunk_sender: Option<mpsc::Sender<String>>) -> Result<MCPOutput> {
         println!("Invoking MCP: {}", input.mcp_id);
 
+        let started_at = Instant::now();
         let request_id = Uuid::new_v4().to_string();
 
         // Retrieve Definition
-        let mcp_definition = self.definitions.get(&input.mcp_id).ok_or_else(|| {
+        // Snapshot, so a reload during the call does not change the definition under it
+        let definitions = self.definitions();
+        let mcp_definition = definitions.get(&input.mcp_id).ok_or_else(|| {
             anyhow!("MCP definition not found for ID: {}", input.mcp_id)
         })?;
 
-        // Prepare LLMRequest (for template-based MCPs)
-        // The template field is a String, based on common_types
-        let prompt = self.handlebars.render_template(&mcp_definition.template, &input.data)?;
+        // Inputs are checked before they reach the template, so a bad input fails here rather than as an odd prompt
+        if let Some(input_schema) = &mcp_definition.input_schema {
+            let violations = schema::validate(input_schema, &input.data);
+            if !violations.is_empty() {
+                let message = format!("Input of MCP {} does not match its input_schema: {}", input.mcp_id, describe_violations(&violations));
+                eprintln!("Warning: {}", message);
+                return Ok(MCPOutput {
+                    request_id,
+                    mcp_id: input.mcp_id,
+                    status: MCPStatus::InvalidInput { violations },
+                    llm_request_details: None,
+                    llm_response_details: None,
+                    processed_content: None,
+                    raw_completion: None,
+                    attempts: Vec::new(),
+                    error_message: Some(message),
+                    usage_metrics: None,
+                });
+            }
+        }
+
+        // Definitions naming a logic module are served by a registered Rust implementation instead of their template
+        let native_prompt = self.native_prompt(mcp_definition)?;
 
         // Determine LLM provider, model, and system prompt
         let context_overrides = input.context_overrides.as_ref();
 
-        let provider_name = context_overrides.and_then(|c| c.llm_provider.clone())
-            .or_else(|| mcp_definition.default_llm_provider.clone())
-            .ok_or_else(|| anyhow!("LLM provider not specified in input or MCP definition for ID: {}", input.mcp_id))?;
+        // An explicit routing policy wins; otherwise the chosen provider, then the definition's fallback routes
+        let routing_policy = match context_overrides.and_then(|c| c.routing_policy.clone()) {
+            Some(policy) => policy,
+            None => {
+                let provider_name = context_overrides.and_then(|c| c.llm_provider.clone())
+                    .or_else(|| mcp_definition.default_llm_provider.clone())
+                    .ok_or_else(|| anyhow!("LLM provider not specified in input or MCP definition for ID: {}", input.mcp_id))?;
+                RoutingPolicy::single(provider_name, None)
+                    .with_fallbacks(mcp_definition.fallback_routes.clone())
+            }
+        };
 
-        // LLMRequest requires a model name (String)
+        // Model for routes that do not pin one; when empty each route uses its provider's default model
         let model_name = context_overrides.and_then(|c| c.llm_model.clone())
-             .ok_or_else(|| anyhow!("LLM model not specified in input context overrides for ID: {}", input.mcp_id))?;
+             .or_else(|| mcp_definition.default_llm_model.clone())
+             .unwrap_or_default();
+
+        // Sampling parameters: definition defaults, then whatever the caller sets
+        let override_parameters: LLMParameters = match context_overrides.and_then(|c| c.llm_parameters.clone()) {
+            Some(value) => serde_json::from_value(value)
+                .map_err(|e| anyhow!("Invalid llm_parameters in context overrides for ID {}: {}", input.mcp_id, e))?,
+            None => LLMParameters::default(),
+        };
+        let default_parameters = mcp_definition.default_llm_parameters.clone().unwrap_or_default();
+
+        // Tools are only offered when the caller may use them and the definition lists some
+        let tools = if context_overrides.is_some_and(|c| c.tools_enabled) {
+            self.tool_registry.definitions(&mcp_definition.tools)
+        } else {
+            Vec::new()
+        };
+
+        let mut llm_request = match &native_prompt {
+            // The implementation builds the conversation; what it leaves unset comes from the definition and the caller
+            Some(native_prompt) => {
+                let mut request = native_prompt.prepare_llm_request(&input, mcp_definition).await?;
+                if request.model.is_empty() {
+                    request.model = model_name;
+                }
+                request.parameters = default_parameters.merged_with(&request.parameters).merged_with(&override_parameters);
+                if request.tools.is_empty() {
+                    request.tools = tools;
+                }
+                request
+            }
+            None => {
+                let prompt = self.handlebars.render_template(&mcp_definition.template, &input.data)?;
+
+                // System prompt from additional_context if it's a string
+                let system_prompt = context_overrides.and_then(|c| c.additional_context.as_ref())
+                    .and_then(|val| val.as_str().map(|s| s.to_string()));
+
+                // Conversation: system prompt, the definition's few-shot examples, earlier turns, then the rendered template
+                let mut messages: Vec<LLMMessage> = system_prompt.into_iter().map(LLMMessage::system).collect();
+                messages.extend(mcp_definition.few_shot_messages.iter().cloned());
+                if let Some(prior_messages) = context_overrides.map(|c| &c.prior_messages) {
+                    messages.extend(prior_messages.iter().cloned());
+                }
+                messages.push(LLMMessage::user(prompt));
+
+                LLMRequest {
+                    model: model_name,
+                    messages,
+                    parameters: default_parameters.merged_with(&override_parameters),
+                    tools,
+                }
+            }
+        };
+
+        let call_options = LLMCallOptions {
+            bypass_cache: context_overrides.is_some_and(|c| c.bypass_cache),
+            // The client's default deadline applies; the task's token aborts the call when it is cancelled
+            timeout: None,
+            cancellation: context_overrides
+                .and_then(|c| c.task_id.as_deref())
+                .map(|task_id| self.cancellation.task_token(task_id)),
+        };
+
+        // Large inputs are shortened before sending, rather than rejected by the provider with an opaque 400
+        let mut token_counts: Option<LLMTokenCounts> = match self.guard_context_window(mcp_definition, &input, &routing_policy, &mut llm_request).await {
+            Ok(summarization_usage) => summarization_usage,
+            Err(e) => match e.downcast::<ContextLengthExceeded>() {
+                Ok(exceeded) => {
+                    eprintln!("Warning: {}", exceeded);
+                    return Ok(MCPOutput {
+                        request_id,
+                        mcp_id: input.mcp_id,
+                        status: MCPStatus::ContextLengthExceeded {
+                            estimated_tokens: Some(exceeded.estimated_tokens),
+                            context_length: Some(exceeded.context_length),
+                        },
+                        llm_request_details: Some(llm_request),
+                        llm_response_details: None,
+                        processed_content: None,
+                        raw_completion: None,
+                        attempts: Vec::new(),
+                        error_message: Some(exceeded.to_string()),
+                        usage_metrics: None,
+                    });
+                }
+                Err(e) => return Err(e),
+            },
+        };
 
-        // System prompt from additional_context if it's a string
-        let system_prompt = context_overrides.and_then(|c| c.additional_context.as_ref())
-            .and_then(|val| val.as_str().map(|s| s.to_string()));
+        // Call ExternalApiClient. A completion that cannot be processed or does not match the output schema is
+        // sent back with its violations, asking the model to correct it, up to the definition's max_repair_attempts
+        let llm_call_started_at = Instant::now();
+        let mut attempts: Vec<MCPAttempt> = Vec::new();
+        let llm_call_result = loop {
+            let response = match self.complete_with_tools(&input.mcp_id, &routing_policy, &mut llm_request, &call_options, chunk_sender.as_ref(), &mut token_counts).await {
+                Ok(response) => response,
+                Err(e) => break Err(e),
+            };
+            // With an output schema the completion must hold a JSON object matching it, extracted from any
+            // fences or prose around it; otherwise it is passed on as text. Native MCPs process it themselves
+            let validation = match (&native_prompt, &mcp_definition.output_schema) {
+                (Some(native_prompt), output_schema) => match native_prompt.process_llm_response(&response, &input).await {
+                    Ok(processed) => {
+                        let violations = output_schema.as_ref().map(|output_schema| schema::validate(output_schema, &processed)).unwrap_or_default();
+                        if violations.is_empty() { Ok(processed) } else { Err(violations) }
+                    }
+                    Err(e) => Err(vec![SchemaViolation { pointer: String::new(), message: format!("completion could not be processed: {}", e) }]),
+                },
+                (None, Some(output_schema)) => schema::validate_completion(output_schema, &response.content),
+                (None, None) => Ok(serde_json::json!(response.content)),
+            };
+            attempts.push(MCPAttempt {
+                completion: response.content.clone(),
+                violations: validation.as_ref().err().cloned().unwrap_or_default(),
+            });
 
-        // Construct LLMRequest based on the definition in external_api_client/providers/mod.rs
-        let llm_request = LLMRequest {
-            model: model_name,
-            prompt,
-            system_prompt,
+            match validation {
+                Err(violations) if attempts.len() <= mcp_definition.max_repair_attempts as usize => {
+                    let mut repair_request = llm_request.clone();
+                    repair_request.messages.push(LLMMessage::assistant(response.content.clone()));
+                    repair_request.messages.push(LLMMessage::user(repair_prompt(mcp_definition.output_schema.as_ref(), &violations)));
+                    // The conversation grows with each turn; one that no longer fits is not sent
+                    if let Err(exceeded) = self.check_context_window(mcp_definition, &routing_policy, &repair_request).await {
+                        eprintln!("Warning: Not asking MCP {} for a correction: {}", input.mcp_id, exceeded);
+                        break Ok((response, Err(violations)));
+                    }
+                    eprintln!("Warning: Output of MCP {} was rejected, asking for a correction ({}/{}): {}",
+                        input.mcp_id, attempts.len(), mcp_definition.max_repair_attempts, describe_violations(&violations));
+                    llm_request = repair_request;
+                    // Streamed output already shows the rejected answer; mark where the corrected one starts
+                    if let Some(sender) = &chunk_sender {
+                        let _ = sender.send(REPAIR_STREAM_SEPARATOR.to_string()).await;
+                    }
+                }
+                validation => break Ok((response, validation)),
+            }
         };
+        let llm_call_duration_ms = llm_call_started_at.elapsed().as_millis() as u64;
 
-        // Call ExternalApiClient
-        let llm_call_result = self.external_api_client.call_llm(&provider_name, llm_request.clone()).await;
+        // Timings are recorded even for failed calls; tokens and cost only when the provider reported usage
+        // Price the model that actually served the call, which may be a fallback route's
+        let served_model = llm_call_result.as_ref().ok()
+            .and_then(|(response, _)| response.served_by.as_ref())
+            .and_then(|route| route.model.clone())
+            .unwrap_or_else(|| llm_request.model.clone());
+        let usage_metrics = MCPUsageMetrics {
+            estimated_cost_usd: token_counts.as_ref()
+                .and_then(|tokens| self.external_api_client.estimate_cost(&served_model, tokens)),
+            llm_token_counts: token_counts,
+            processing_time_ms: Some(started_at.elapsed().as_millis() as u64),
+            llm_call_duration_ms: Some(llm_call_duration_ms),
+            llm_model: Some(served_model),
+        };
 
         // Handle the result and populate MCPOutput
         match llm_call_result {
-            Ok(llm_response) => {
+            Ok((llm_response, validation)) => {
+                let (status, processed_content, error_message) = match validation {
+                    Ok(parsed) => (MCPStatus::Success, Some(parsed), None),
+                    Err(violations) => {
+                        let message = format!("Output of MCP {} was rejected after {} attempt(s): {}",
+                            input.mcp_id, attempts.len(), describe_violations(&violations));
+                        eprintln!("Warning: {}", message);
+                        (MCPStatus::MCPProcessingError { violations }, None, Some(message))
+                    }
+                };
+                let raw_completion = Some(llm_response.content.clone());
                 Ok(MCPOutput {
                     request_id,
                     mcp_id: input.mcp_id,
-                    status: MCPStatus::Success,
+                    status,
                     llm_request_details: Some(llm_request),
-                    llm_response_details: Some(llm_response.clone()),
-                    processed_content: Some(serde_json::json!(llm_response.content)),
-                    error_message: None,
-                    usage_metrics: None, // Populate usage_metrics if available from LLMResponse
+                    llm_response_details: Some(llm_response),
+                    processed_content,
+                    raw_completion,
+                    attempts,
+                    error_message,
+                    usage_metrics: Some(usage_metrics),
                 })
             }
             Err(e) => {
                 Ok(MCPOutput {
                     request_id,
                     mcp_id: input.mcp_id,
-                    status: MCPStatus::LLMError { provider_error: e.to_string() },
+                    status: MCPStatus::from(&e),
                     llm_request_details: Some(llm_request),
                     llm_response_details: None,
                     processed_content: None,
+                    raw_completion: None,
+                    attempts,
                     error_message: Some(e.to_string()),
-                    usage_metrics: None,
+                    usage_metrics: Some(usage_metrics),
                 })
             }
         }
     }
-}
\ No newline at end of file
+
+    // Calls the routed provider, running requested tools and sending their results back until the model answers
+    async fn complete_with_tools(
+        &self,
+        mcp_id: &str,
+        routing_policy: &RoutingPolicy,
+        llm_request: &mut LLMRequest,
+        call_options: &LLMCallOptions,
+        chunk_sender: Option<&mpsc::Sender<String>>,
+        token_counts: &mut Option<LLMTokenCounts>,
+    ) -> Result<LLMResponse, LLMError> {
+        let mut tool_rounds = 0;
+        loop {
+            let result = match chunk_sender {
+                Some(sender) => self.external_api_client.call_llm_stream(routing_policy, llm_request.clone(), call_options, sender.clone()).await,
+                None => self.external_api_client.call_llm(routing_policy, llm_request.clone(), call_options).await,
+            };
+            if let Some(usage) = result.as_ref().ok().and_then(|response| response.usage.as_ref()) {
+                let total = token_counts.get_or_insert_with(LLMTokenCounts::default);
+                total.prompt_tokens += usage.prompt_tokens;
+                total.completion_tokens += usage.completion_tokens;
+                total.total_tokens += usage.total_tokens;
+            }
+
+            match result {
+                Ok(response) if !response.tool_calls.is_empty() && tool_rounds < MAX_TOOL_ROUNDS => {
+                    tool_rounds += 1;
+                    llm_request.messages.push(LLMMessage::assistant_tool_calls(response.content.clone(), response.tool_calls.clone()));
+                    for call in &response.tool_calls {
+                        println!("MCP {} calling tool: {} ({})", mcp_id, call.name, call.id);
+                        let output = self.tool_registry.invoke(call).await;
+                        llm_request.messages.push(LLMMessage::tool_result(call.id.clone(), output));
+                    }
+                }
+                Ok(response) if !response.tool_calls.is_empty() => {
+                    return Err(LLMError::MalformedResponse {
+                        provider: response.served_by.map(|route| route.provider).unwrap_or_default(),
+                        truncated: false,
+                        message: format!("MCP {} exceeded {} tool-call rounds without a final answer", mcp_id, MAX_TOOL_ROUNDS),
+                    });
+                }
+                other => return other,
+            }
+        }
+    }
+}
+
+// Follow-up turn sent after a completion that cannot be processed or does not match the output schema
+fn repair_prompt(output_schema: Option<&Value>, violations: &[SchemaViolation]) -> String {
+    let problems: Vec<String> = violations.iter().map(|violation| format!("- {}", violation)).collect();
+    match output_schema {
+        Some(output_schema) => format!(
+            "Your previous answer could not be used. It must be a single JSON object matching this JSON Schema:\n{}\n\nProblems found:\n{}\n\nReply with the corrected JSON object only, without any other text.",
+            output_schema,
+            problems.join("\n")
+        ),
+        None => format!(
+            "Your previous answer could not be used.\n\nProblems found:\n{}\n\nReply with the corrected answer only, without any other text.",
+            problems.join("\n")
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::external_api_client::providers::scripted_provider::ScriptedProvider;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_input_and_output_schemas_are_enforced() {
+        let scripted = ScriptedProvider::from_json_str(r#"{ "responses": [
+            { "prompt_regex": "good", "response": "{\"draft_text\": \"A draft\"}" },
+            { "prompt_regex": "bad", "response": "{\"draft\": \"A draft\"}" },
+            { "prompt_regex": "wrapped", "response": "Here you go:\n```json\n{\"draft_text\": \"A draft\",}\n```" },
+            { "prompt_regex": "hopeless", "response": "I cannot write that." },
+            { "prompt_regex": "/draft_text: required property is missing", "response": "{\"draft_text\": \"A fixed draft\"}" },
+            { "prompt_regex": "no JSON object", "response": "Still no JSON, sorry." }
+        ] }"#).unwrap();
+        let definition: MCPDefinition = serde_json::from_value(json!({
+            "id": "draft_content_v1",
+            "description": "Drafts content",
+            "template": "Write about {{topic}}",
+            "logic_module_path": null,
+            "default_llm_provider": "Scripted",
+            "default_llm_model": "scripted-model",
+            "default_llm_parameters": null,
+            "input_schema": { "type": "object", "properties": { "topic": { "type": "string" } }, "required": ["topic"] },
+            "output_schema": { "type": "object", "properties": { "draft_text": { "type": "string" } }, "required": ["draft_text"] }
+        })).unwrap();
+        let manager = MCPManager::for_test([definition], vec![Box::new(scripted)]);
+        let input = |data: Value| MCPInput { mcp_id: "draft_content_v1".to_string(), data, context_overrides: None };
+
+        let output = manager.invoke_mcp(input(json!({ "topic": "good news" }))).await.unwrap();
+        assert_eq!(output.status, MCPStatus::Success);
+        assert_eq!(output.processed_content, Some(json!({ "draft_text": "A draft" })));
+
+        // Fences, prose and the trailing comma are stripped; the completion is kept as written
+        let output = manager.invoke_mcp(input(json!({ "topic": "wrapped news" }))).await.unwrap();
+        assert_eq!(output.processed_content, Some(json!({ "draft_text": "A draft" })));
+        assert!(output.raw_completion.unwrap().starts_with("Here you go:"));
+
+        // The violations are sent back and the corrected answer is used
+        let output = manager.invoke_mcp(input(json!({ "topic": "bad news" }))).await.unwrap();
+        assert_eq!(output.status, MCPStatus::Success);
+        assert_eq!(output.processed_content, Some(json!({ "draft_text": "A fixed draft" })));
+        assert_eq!(output.attempts.len(), 2);
+        assert_eq!(output.attempts[0].violations[0].pointer, "/draft_text");
+        assert!(output.attempts[1].violations.is_empty());
+
+        // One repair by default, then the call fails
+        let output = manager.invoke_mcp(input(json!({ "topic": "hopeless" }))).await.unwrap();
+        assert!(matches!(output.status, MCPStatus::MCPProcessingError { .. }));
+        assert_eq!(output.attempts.len(), 2);
+        assert_eq!(output.raw_completion.as_deref(), Some("Still no JSON, sorry."));
+
+        // Rejected before any call: the scripted provider has no answer for this prompt
+        let output = manager.invoke_mcp(input(json!({ "topic": 42 }))).await.unwrap();
+        let MCPStatus::InvalidInput { violations } = output.status else { panic!("expected InvalidInput") };
+        assert_eq!(violations[0].pointer, "/topic");
+        assert!(output.llm_request_details.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_repair_turns_are_marked_in_the_stream_and_kept_within_the_context_window() {
+        let long_answer = "No JSON here. ".repeat(300);
+        let scripted = ScriptedProvider::from_json_str(&json!({ "responses": [
+            { "prompt_regex": "^Write about bees", "response": "{\"draft\": \"A draft\"}" },
+            { "prompt_regex": "^Write about wasps", "response": long_answer },
+            { "prompt_regex": "required property is missing", "response": "{\"draft_text\": \"A fixed draft\"}" }
+        ] }).to_string()).unwrap();
+        let definition: MCPDefinition = serde_json::from_value(json!({
+            "id": "draft_content_v1",
+            "description": "Drafts content",
+            "template": "Write about {{topic}}",
+            "logic_module_path": null,
+            "default_llm_provider": "Scripted",
+            "default_llm_model": "scripted-model",
+            "default_llm_parameters": null,
+            "output_schema": { "type": "object", "properties": { "draft_text": { "type": "string" } }, "required": ["draft_text"] },
+            "context_overflow": { "strategy": "fail", "max_context_tokens": 8000 }
+        })).unwrap();
+        let manager = MCPManager::for_test([definition], vec![Box::new(scripted)]);
+        let input = |topic: String| MCPInput { mcp_id: "draft_content_v1".to_string(), data: json!({ "topic": topic }), context_overrides: None };
+
+        let (chunk_sender, mut chunk_receiver) = mpsc::channel(16);
+        let output = manager.invoke_mcp_streaming(input("bees".to_string()), chunk_sender).await.unwrap();
+        assert_eq!(output.status, MCPStatus::Success);
+        let mut chunks = Vec::new();
+        while let Some(chunk) = chunk_receiver.recv().await {
+            chunks.push(chunk);
+        }
+        assert_eq!(chunks, vec!["{\"draft\": \"A draft\"}", REPAIR_STREAM_SEPARATOR, "{\"draft_text\": \"A fixed draft\"}"]);
+
+        // The prompt fits, but not with the rejected answer and the repair prompt added to it
+        let output = manager.invoke_mcp(input(format!("wasps {}", "x".repeat(26_000)))).await.unwrap();
+        assert!(matches!(output.status, MCPStatus::MCPProcessingError { .. }));
+        assert_eq!(output.attempts.len(), 1);
+        assert_eq!(output.llm_request_details.unwrap().messages.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_shipped_definitions_accept_the_inputs_agents_build() {
+        let scripted = ScriptedProvider::from_json_str(r#"{ "responses": [
+            { "prompt_regex": "expert project planner", "response": "{\"subtasks\": [{\"title\": \"Draft\", \"description\": \"Write it\", \"dependencies\": []}]}" },
+            { "prompt_regex": "expert programmer", "response": "{\"generated_code\": \"fn main() {}\", \"explanation\": \"Does nothing\"}" },
+            { "prompt_regex": "writing assistant", "response": "{\"draft_text\": \"A draft\"}" },
+            { "prompt_regex": "research assistant", "response": "{\"summary\": \"Findings\", \"sources\": []}" },
+            { "prompt_regex": "meticulous validator", "response": "{\"is_valid\": true, \"feedback\": \"Fine\", \"criteria_results\": []}" },
+            { "prompt_regex": "expert summarizer", "response": "{\"summary\": \"Bees pollinate\"}" }
+        ] }"#).unwrap();
+        let (loaded, errors) = DefinitionSet::read_directory(Path::new(MCP_DEFINITIONS_DIR), &DefinitionSet::default()).unwrap();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let ids = ["decompose_task_v1", "generate_code_v1", "draft_content_v1", "perform_basic_research_v1", "validate_content_v1", "SummarizeTextV1"];
+        let definitions = ids.iter().map(|id| {
+            let mut definition = loaded.get(id).cloned().unwrap_or_else(|| panic!("{} is not shipped", id));
+            definition.default_llm_provider = Some("Scripted".to_string());
+            definition.default_llm_model = Some("scripted-model".to_string());
+            definition.fallback_routes.clear();
+            definition
+        });
+        let manager = MCPManager::for_test(definitions.collect::<Vec<_>>(), vec![Box::new(scripted)]);
+
+        // The inputs the agents send, with and without their optional fields
+        let inputs = [
+            ("decompose_task_v1", json!({ "objective": "Write a blog post" })),
+            ("decompose_task_v1", json!({ "objective": "Write a blog post", "context": "For beginners" })),
+            ("generate_code_v1", json!({ "instruction": "Print hello", "language": "rust" })),
+            ("generate_code_v1", json!({ "instruction": "Print hello", "language": "rust", "context": "fn main() {}" })),
+            ("draft_content_v1", json!({ "topic": "Bees" })),
+            ("draft_content_v1", json!({ "topic": "Bees", "key_points": ["Pollination"], "style_guide": "casual", "research_context": "Bees pollinate" })),
+            ("perform_basic_research_v1", json!({ "query": "Bees", "num_results_to_summarize": 3 })),
+            ("validate_content_v1", json!({ "validation_criteria": ["Is factual"], "text_content": "Bees pollinate." })),
+            ("validate_content_v1", json!({ "validation_criteria": ["Check for syntax errors"], "code_content": "fn main() {}" })),
+            ("SummarizeTextV1", json!({ "text_to_summarize": "Bees pollinate flowers." })),
+            ("SummarizeTextV1", json!({ "text_to_summarize": "Bees pollinate flowers.", "max_length": 10 })),
+        ];
+        for (mcp_id, data) in inputs {
+            let input = MCPInput { mcp_id: mcp_id.to_string(), data: data.clone(), context_overrides: None };
+            let output = manager.invoke_mcp(input).await.unwrap();
+            assert_eq!(output.status, MCPStatus::Success, "{} with {}", mcp_id, data);
+        }
+    }
+
+    // Native MCP with a typed output: the model answers with a bare number
+    struct WordCountPrompt;
+
+    #[derive(serde::Serialize)]
+    struct WordCount {
+        words: u64,
+    }
+
+    #[async_trait::async_trait]
+    impl MetaContextualPrompt for WordCountPrompt {
+        fn id(&self) -> &'static str {
+            "word_count"
+        }
+
+        async fn prepare_llm_request(&self, input: &MCPInput, _definition: &MCPDefinition) -> Result<LLMRequest> {
+            let text = input.data["text"].as_str().ok_or_else(|| anyhow!("text is missing"))?;
+            Ok(LLMRequest {
+                model: String::new(),
+                messages: vec![LLMMessage::system("Answer with a number only."), LLMMessage::user(format!("Count the words: {}", text))],
+                parameters: LLMParameters::default(),
+                tools: Vec::new(),
+            })
+        }
+
+        async fn process_llm_response(&self, response: &LLMResponse, _input: &MCPInput) -> Result<Value> {
+            let words = response.content.trim().parse()?;
+            Ok(serde_json::to_value(WordCount { words })?)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_native_mcp_is_dispatched_by_logic_module() {
+        let scripted = ScriptedProvider::from_json_str(r#"{ "responses": [
+            { "prompt_regex": "Count the words", "response": " 3\n" }
+        ] }"#).unwrap();
+        let definition: MCPDefinition = serde_json::from_value(json!({
+            "id": "count_words_v1",
+            "description": "Counts words",
+            "logic_module_path": "word_count",
+            "default_llm_provider": "Scripted",
+            "default_llm_model": "scripted-model",
+            "default_llm_parameters": { "temperature": 0.0 },
+            "output_schema": { "type": "object", "properties": { "words": { "type": "integer" } }, "required": ["words"] }
+        })).unwrap();
+        let manager = MCPManager::for_test([definition], vec![Box::new(scripted)]);
+        let input = MCPInput { mcp_id: "count_words_v1".to_string(), data: json!({ "text": "one two three" }), context_overrides: None };
+
+        let unregistered = manager.invoke_mcp(input.clone()).await.unwrap_err();
+        assert!(unregistered.to_string().contains("not registered"));
+
+        manager.prompt_registry().register(Arc::new(WordCountPrompt));
+        let output = manager.invoke_mcp(input).await.unwrap();
+        assert_eq!(output.status, MCPStatus::Success);
+        assert_eq!(output.processed_content, Some(json!({ "words": 3 })));
+        let request = output.llm_request_details.unwrap();
+        assert_eq!(request.model, "scripted-model");
+        assert_eq!(request.parameters.temperature, Some(0.0));
+    }
+
+    #[tokio::test]
+    async fn test_native_parse_failure_is_repaired_without_output_schema() {
+        let scripted = ScriptedProvider::from_json_str(r#"{ "responses": [
+            { "prompt_regex": "Count the words", "response": "About four." },
+            { "prompt_regex": "could not be processed", "response": "4" }
+        ] }"#).unwrap();
+        let definition: MCPDefinition = serde_json::from_value(json!({
+            "id": "count_words_v1",
+            "description": "Counts words",
+            "logic_module_path": "word_count",
+            "default_llm_provider": "Scripted",
+            "default_llm_model": "scripted-model",
+            "default_llm_parameters": null
+        })).unwrap();
+        let manager = MCPManager::for_test([definition], vec![Box::new(scripted)]);
+        manager.prompt_registry().register(Arc::new(WordCountPrompt));
+
+        let input = MCPInput { mcp_id: "count_words_v1".to_string(), data: json!({ "text": "one two three four" }), context_overrides: None };
+        let output = manager.invoke_mcp(input).await.unwrap();
+        assert_eq!(output.status, MCPStatus::Success);
+        assert_eq!(output.processed_content, Some(json!({ "words": 4 })));
+        assert_eq!(output.attempts.len(), 2);
+        let repair = output.llm_request_details.unwrap().last_user_prompt().to_string();
+        assert!(!repair.contains("JSON Schema"));
+    }
+}
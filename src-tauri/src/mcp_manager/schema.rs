@@ -0,0 +1,259 @@
+use regex::Regex;
+use serde_json::{Map, Value};
+
+use super::json_extraction::extract_json_objects;
+pub use crate::common_types::SchemaViolation;
+
+/// Violations joined on one line, for error messages.
+pub fn describe_violations(violations: &[SchemaViolation]) -> String {
+    violations.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+}
+
+/// Checks `instance` against `schema`. Covers the JSON Schema keywords MCP definitions use:
+/// `type`, `enum`, `const`, `properties`, `required`, `additionalProperties`, `items`,
+/// length and count bounds, `pattern`, numeric bounds, `allOf`, `anyOf` and `oneOf`.
+/// Other keywords (`description`, `default`, `$ref`...) are ignored.
+pub fn validate(schema: &Value, instance: &Value) -> Vec<SchemaViolation> {
+    let mut violations = Vec::new();
+    validate_at(schema, instance, "", &mut violations);
+    violations
+}
+
+/// Extracts the JSON object from a completion and checks it against `output_schema`. When the
+/// completion holds several objects, the largest one that matches wins; if none does, the
+/// violations of the largest are reported.
+pub fn validate_completion(output_schema: &Value, completion: &str) -> Result<Value, Vec<SchemaViolation>> {
+    let candidates = extract_json_objects(completion).map_err(|e| vec![SchemaViolation {
+        pointer: String::new(),
+        message: e.to_string(),
+    }])?;
+    let mut first_violations = None;
+    for candidate in candidates {
+        let violations = validate(output_schema, &candidate);
+        if violations.is_empty() {
+            return Ok(candidate);
+        }
+        first_violations.get_or_insert(violations);
+    }
+    Err(first_violations.unwrap_or_default())
+}
+
+fn validate_at(schema: &Value, instance: &Value, pointer: &str, violations: &mut Vec<SchemaViolation>) {
+    let Some(schema) = schema.as_object() else {
+        // `false` rejects everything, `true` and non-object schemas accept everything
+        if schema == &Value::Bool(false) {
+            violations.push(violation(pointer, "no value is allowed here".to_string()));
+        }
+        return;
+    };
+    let mut fail = |message: String| violations.push(violation(pointer, message));
+
+    if let Some(expected) = schema.get("type") {
+        let type_names: Vec<&str> = match expected {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !type_names.is_empty() && !type_names.iter().any(|name| has_type(instance, name)) {
+            fail(format!("expected {}, found {}", type_names.join(" or "), type_name(instance)));
+            // Keywords for the expected type would only repeat the mismatch
+            return;
+        }
+    }
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(instance) {
+            fail(format!("{} is not one of {}", instance, Value::Array(allowed.clone())));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if expected != instance {
+            fail(format!("expected {}, found {}", expected, instance));
+        }
+    }
+
+    match instance {
+        Value::Object(object) => validate_object(schema, object, pointer, violations),
+        Value::Array(items) => validate_array(schema, items, pointer, violations),
+        Value::String(text) => {
+            let length = text.chars().count() as u64;
+            if let Some(min_length) = schema.get("minLength").and_then(Value::as_u64) {
+                if length < min_length {
+                    violations.push(violation(pointer, format!("string is shorter than {} characters", min_length)));
+                }
+            }
+            if let Some(max_length) = schema.get("maxLength").and_then(Value::as_u64) {
+                if length > max_length {
+                    violations.push(violation(pointer, format!("string is longer than {} characters", max_length)));
+                }
+            }
+            if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+                match Regex::new(pattern) {
+                    Ok(regex) if !regex.is_match(text) => violations.push(violation(pointer, format!("string does not match pattern {}", pattern))),
+                    Ok(_) => {}
+                    Err(e) => eprintln!("Warning: Ignoring invalid schema pattern {}: {}", pattern, e),
+                }
+            }
+        }
+        Value::Number(number) => {
+            let value = number.as_f64().unwrap_or_default();
+            let bounds = [
+                ("minimum", value < schema.get("minimum").and_then(Value::as_f64).unwrap_or(f64::NEG_INFINITY)),
+                ("maximum", value > schema.get("maximum").and_then(Value::as_f64).unwrap_or(f64::INFINITY)),
+                ("exclusiveMinimum", value <= schema.get("exclusiveMinimum").and_then(Value::as_f64).unwrap_or(f64::NEG_INFINITY)),
+                ("exclusiveMaximum", value >= schema.get("exclusiveMaximum").and_then(Value::as_f64).unwrap_or(f64::INFINITY)),
+            ];
+            for (keyword, out_of_bounds) in bounds {
+                if out_of_bounds {
+                    violations.push(violation(pointer, format!("{} is outside the {} of {}", number, keyword, schema[keyword])));
+                }
+            }
+        }
+        _ => {}
+    }
+
+    validate_combinators(schema, instance, pointer, violations);
+}
+
+fn validate_object(schema: &Map<String, Value>, object: &Map<String, Value>, pointer: &str, violations: &mut Vec<SchemaViolation>) {
+    if let Some(required) = schema.get("required").and_then(Value::as_array) {
+        for name in required.iter().filter_map(Value::as_str) {
+            if !object.contains_key(name) {
+                violations.push(violation(&child_pointer(pointer, name), "required property is missing".to_string()));
+            }
+        }
+    }
+    let properties = schema.get("properties").and_then(Value::as_object);
+    for (name, value) in object {
+        let child = child_pointer(pointer, name);
+        match properties.and_then(|properties| properties.get(name)) {
+            Some(property_schema) => validate_at(property_schema, value, &child, violations),
+            None => match schema.get("additionalProperties") {
+                Some(Value::Bool(false)) => violations.push(violation(&child, "property is not allowed".to_string())),
+                Some(additional_schema) => validate_at(additional_schema, value, &child, violations),
+                None => {}
+            },
+        }
+    }
+}
+
+fn validate_array(schema: &Map<String, Value>, items: &[Value], pointer: &str, violations: &mut Vec<SchemaViolation>) {
+    if let Some(min_items) = schema.get("minItems").and_then(Value::as_u64) {
+        if (items.len() as u64) < min_items {
+            violations.push(violation(pointer, format!("array has fewer than {} items", min_items)));
+        }
+    }
+    if let Some(max_items) = schema.get("maxItems").and_then(Value::as_u64) {
+        if (items.len() as u64) > max_items {
+            violations.push(violation(pointer, format!("array has more than {} items", max_items)));
+        }
+    }
+    if let Some(item_schema) = schema.get("items") {
+        for (index, item) in items.iter().enumerate() {
+            validate_at(item_schema, item, &child_pointer(pointer, &index.to_string()), violations);
+        }
+    }
+}
+
+fn validate_combinators(schema: &Map<String, Value>, instance: &Value, pointer: &str, violations: &mut Vec<SchemaViolation>) {
+    if let Some(all_of) = schema.get("allOf").and_then(Value::as_array) {
+        for subschema in all_of {
+            validate_at(subschema, instance, pointer, violations);
+        }
+    }
+    if let Some(any_of) = schema.get("anyOf").and_then(Value::as_array) {
+        if !any_of.iter().any(|subschema| validate_from(subschema, instance, pointer).is_empty()) {
+            violations.push(violation(pointer, "value matches none of the anyOf schemas".to_string()));
+        }
+    }
+    if let Some(one_of) = schema.get("oneOf").and_then(Value::as_array) {
+        let matching = one_of.iter().filter(|subschema| validate_from(subschema, instance, pointer).is_empty()).count();
+        if matching != 1 {
+            violations.push(violation(pointer, format!("value matches {} of the oneOf schemas instead of exactly one", matching)));
+        }
+    }
+}
+
+fn validate_from(schema: &Value, instance: &Value, pointer: &str) -> Vec<SchemaViolation> {
+    let mut violations = Vec::new();
+    validate_at(schema, instance, pointer, &mut violations);
+    violations
+}
+
+fn has_type(instance: &Value, name: &str) -> bool {
+    match name {
+        "object" => instance.is_object(),
+        "array" => instance.is_array(),
+        "string" => instance.is_string(),
+        "boolean" => instance.is_boolean(),
+        "null" => instance.is_null(),
+        "number" => instance.is_number(),
+        // 1.0 is an integer for JSON Schema
+        "integer" => instance.as_f64().is_some_and(|number| number.fract() == 0.0),
+        _ => true,
+    }
+}
+
+fn type_name(instance: &Value) -> &'static str {
+    match instance {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+// RFC 6901: '~' and '/' in a token are escaped as "~0" and "~1"
+fn child_pointer(pointer: &str, token: &str) -> String {
+    format!("{}/{}", pointer, token.replace('~', "~0").replace('/', "~1"))
+}
+
+fn violation(pointer: &str, message: String) -> SchemaViolation {
+    SchemaViolation { pointer: pointer.to_string(), message }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_violations_carry_json_pointers() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "is_valid": { "type": "boolean" },
+                "criteria_results": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": { "criterion": { "type": "string" }, "passed": { "type": "boolean" } },
+                        "required": ["criterion", "passed"]
+                    }
+                },
+                "a/b": { "type": "integer" }
+            },
+            "required": ["is_valid", "feedback"]
+        });
+        let instance = json!({
+            "is_valid": "yes",
+            "criteria_results": [ { "criterion": "Is factual", "passed": true }, { "criterion": "Length" } ],
+            "a/b": 1.5
+        });
+
+        let mut pointers: Vec<String> = validate(&schema, &instance).into_iter().map(|v| v.pointer).collect();
+        pointers.sort();
+        assert_eq!(pointers, vec!["/a~1b", "/criteria_results/1/passed", "/feedback", "/is_valid"]);
+        assert!(validate(&schema, &json!({ "is_valid": true, "feedback": "ok", "criteria_results": [] })).is_empty());
+
+        let not_json = validate_completion(&schema, "Sure! Here is the result").unwrap_err();
+        assert_eq!(not_json[0].pointer, "");
+        let fenced = validate_completion(&schema, "```json\n{\"is_valid\": true, \"feedback\": \"ok\"}\n```").unwrap();
+        assert_eq!(fenced, json!({ "is_valid": true, "feedback": "ok" }));
+
+        // The object that matches is picked over a larger one that does not
+        let two_objects = "Input was {\"text\": \"a rather long piece of text to check\"}, result: {\"is_valid\": true, \"feedback\": \"ok\"}";
+        assert_eq!(validate_completion(&schema, two_objects).unwrap(), json!({ "is_valid": true, "feedback": "ok" }));
+    }
+}
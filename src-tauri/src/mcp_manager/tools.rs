@@ -0,0 +1,129 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use crate::external_api_client::{LLMTool, LLMToolCall};
+
+/// Local function the orchestrator exposes to models through native tool calling.
+#[async_trait]
+pub trait LocalTool: Send + Sync {
+    /// Name, description and JSON Schema of the arguments, as offered to the model.
+    fn definition(&self) -> LLMTool;
+    async fn invoke(&self, arguments: Value) -> Result<Value>;
+}
+
+/// Tools available to MCPs. An MCP only offers the tools it lists in its definition,
+/// and only to agents that can use tools.
+#[derive(Default)]
+pub struct ToolRegistry {
+    tools: RwLock<HashMap<String, Arc<dyn LocalTool>>>,
+}
+
+impl std::fmt::Debug for ToolRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names: Vec<String> = self.tools.read().unwrap_or_else(|poisoned| poisoned.into_inner()).keys().cloned().collect();
+        f.debug_struct("ToolRegistry").field("tools", &names).finish()
+    }
+}
+
+impl ToolRegistry {
+    /// Registry holding the built-in tools.
+    pub fn with_builtin_tools() -> Self {
+        let registry = ToolRegistry::default();
+        registry.register(Arc::new(CurrentDateTimeTool));
+        registry
+    }
+
+    /// Adds a tool, replacing any tool registered under the same name.
+    pub fn register(&self, tool: Arc<dyn LocalTool>) {
+        let name = tool.definition().name;
+        self.tools.write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(name, tool);
+    }
+
+    /// Definitions of the named tools; unknown names are skipped with a warning.
+    pub fn definitions(&self, names: &[String]) -> Vec<LLMTool> {
+        let tools = self.tools.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        names.iter()
+            .filter_map(|name| {
+                let tool = tools.get(name).map(|tool| tool.definition());
+                if tool.is_none() {
+                    eprintln!("Warning: Tool '{}' is not registered", name);
+                }
+                tool
+            })
+            .collect()
+    }
+
+    /// Runs a call requested by the model and returns the text sent back as the tool result.
+    /// Failures are reported to the model as `{"error": ...}` so it can correct itself.
+    pub async fn invoke(&self, call: &LLMToolCall) -> String {
+        match self.try_invoke(call).await {
+            Ok(output) => output.to_string(),
+            Err(e) => {
+                eprintln!("Warning: Tool call {} ({}) failed: {}", call.id, call.name, e);
+                json!({ "error": e.to_string() }).to_string()
+            }
+        }
+    }
+
+    async fn try_invoke(&self, call: &LLMToolCall) -> Result<Value> {
+        let tool = self.tools.read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(&call.name)
+            .cloned()
+            .ok_or_else(|| anyhow!("Unknown tool '{}'", call.name))?;
+        // Models send "" rather than "{}" for calls without arguments
+        let arguments = if call.arguments.trim().is_empty() {
+            json!({})
+        } else {
+            serde_json::from_str(&call.arguments)
+                .map_err(|e| anyhow!("Invalid JSON arguments for tool '{}': {}", call.name, e))?
+        };
+        tool.invoke(arguments).await
+    }
+}
+
+/// Current date and time in UTC, which models cannot know on their own.
+struct CurrentDateTimeTool;
+
+#[async_trait]
+impl LocalTool for CurrentDateTimeTool {
+    fn definition(&self) -> LLMTool {
+        LLMTool {
+            name: "current_datetime".to_string(),
+            description: "Returns the current date and time in UTC, in RFC 3339 format.".to_string(),
+            parameters: json!({ "type": "object", "properties": {} }),
+        }
+    }
+
+    async fn invoke(&self, _arguments: Value) -> Result<Value> {
+        Ok(json!({ "utc": chrono::Utc::now().to_rfc3339() }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(name: &str, arguments: &str) -> LLMToolCall {
+        LLMToolCall { id: "call_1".to_string(), name: name.to_string(), arguments: arguments.to_string() }
+    }
+
+    #[tokio::test]
+    async fn test_invoke_reports_failures_to_the_model() {
+        let registry = ToolRegistry::with_builtin_tools();
+        assert_eq!(registry.definitions(&["current_datetime".to_string(), "missing".to_string()]).len(), 1);
+
+        let output: Value = serde_json::from_str(&registry.invoke(&call("current_datetime", "")).await).unwrap();
+        assert!(output["utc"].is_string());
+
+        let unknown: Value = serde_json::from_str(&registry.invoke(&call("missing", "{}")).await).unwrap();
+        assert_eq!(unknown["error"], "Unknown tool 'missing'");
+        let malformed: Value = serde_json::from_str(&registry.invoke(&call("current_datetime", "{oops")).await).unwrap();
+        assert!(malformed["error"].as_str().unwrap().starts_with("Invalid JSON arguments"));
+    }
+}
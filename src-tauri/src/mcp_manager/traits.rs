@@ -1,22 +1,47 @@
-use crate::common_types::{MCPInput, MCPOutput}; // Adjust paths as needed
+use crate::common_types::{MCPDefinition, MCPInput};
 use async_trait::async_trait;
-use anyhow::Result; // Or your project's preferred error type
-
-// Assuming LLMRequest and LLMResponse are defined elsewhere, e.g., in common_types or external_api_client
-// For now, let's assume they are in common_types for the trait definition to compile.
-// If they are not, this will need adjustment later.
-// Placeholder definitions if they don't exist:
-// #[derive(Debug, Clone, Serialize, Deserialize)]
-// pub struct LLMRequest { /* ... */ }
-// #[derive(Debug, Clone, Serialize, Deserialize)]
-// pub struct LLMResponse { /* ... */ }
-use crate::external_api_client::LLMRequest; // Assuming LLMRequest is here
-use crate::external_api_client::LLMResponse; // Assuming LLMResponse is here
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
 
+use crate::external_api_client::LLMRequest;
+use crate::external_api_client::LLMResponse;
 
+/// MCP implemented in Rust instead of a handlebars template. A definition whose
+/// `logic_module_path` names the implementation's id is dispatched to it.
 #[async_trait]
 pub trait MetaContextualPrompt: Send + Sync {
     fn id(&self) -> &'static str;
-    async fn prepare_llm_request(&self, input: &MCPInput, context: &serde_json::Value) -> Result<LLMRequest>;
+    /// Builds the whole conversation for `input`. An empty model, unset parameters and empty tools
+    /// are filled from the definition and the caller's overrides.
+    async fn prepare_llm_request(&self, input: &MCPInput, definition: &MCPDefinition) -> Result<LLMRequest>;
+    /// Turns the completion into `processed_content`, e.g. by parsing it into a typed struct and
+    /// serializing that back. An error counts as invalid output and may trigger a repair turn.
     async fn process_llm_response(&self, response: &LLMResponse, input: &MCPInput) -> Result<serde_json::Value>;
-}
\ No newline at end of file
+}
+
+/// Native MCP implementations, by id.
+#[derive(Default)]
+pub struct PromptRegistry {
+    prompts: RwLock<HashMap<String, Arc<dyn MetaContextualPrompt>>>,
+}
+
+impl std::fmt::Debug for PromptRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ids: Vec<String> = self.prompts.read().unwrap_or_else(|poisoned| poisoned.into_inner()).keys().cloned().collect();
+        f.debug_struct("PromptRegistry").field("prompts", &ids).finish()
+    }
+}
+
+impl PromptRegistry {
+    /// Adds an implementation, replacing any registered under the same id.
+    pub fn register(&self, prompt: Arc<dyn MetaContextualPrompt>) {
+        self.prompts.write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(prompt.id().to_string(), prompt);
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<dyn MetaContextualPrompt>> {
+        self.prompts.read().unwrap_or_else(|poisoned| poisoned.into_inner()).get(id).cloned()
+    }
+}
@@ -6,7 +6,7 @@ use anyhow::{Context, Result, anyhow};
 
 use std::env;
 
-use crate::common_types::task_graph_defs::{TaskNode, TaskEdge};
+use crate::common_types::task_graph_defs::{TaskNode, TaskEdge, TaskUsage};
 use crate::common_types::sprint_defs::Sprint;
 use crate::common_types::task_defs::{TaskSpecification, TaskStatus};
 use crate::common_types::sprint_defs::SprintStatus;
@@ -23,6 +23,14 @@ pub fn establish_connection() -> Result<Connection> {
     Ok(conn)
 }
 
+/// Database with the app's tables that lives in memory, for tests.
+#[cfg(test)]
+pub(crate) fn establish_in_memory_connection() -> Result<Connection> {
+    let conn = Connection::open_in_memory()?;
+    create_tables(&conn)?;
+    Ok(conn)
+}
+
 fn create_tables(conn: &Connection) -> Result<()> {
     conn.execute(
         "CREATE TABLE IF NOT EXISTS tasks (
@@ -44,10 +52,25 @@ fn create_tables(conn: &Connection) -> Result<()> {
             updated_at TEXT NOT NULL,
             assigned_agent_id TEXT,
             error_message TEXT,
-            sprint_id TEXT
+            sprint_id TEXT,
+            llm_calls INTEGER NOT NULL DEFAULT 0,
+            prompt_tokens INTEGER NOT NULL DEFAULT 0,
+            completion_tokens INTEGER NOT NULL DEFAULT 0,
+            total_tokens INTEGER NOT NULL DEFAULT 0,
+            estimated_cost_usd REAL NOT NULL DEFAULT 0
         )",
         [],
     )?;
+    // Databases created before usage accounting lack the usage columns
+    for (column, definition) in [
+        ("llm_calls", "INTEGER NOT NULL DEFAULT 0"),
+        ("prompt_tokens", "INTEGER NOT NULL DEFAULT 0"),
+        ("completion_tokens", "INTEGER NOT NULL DEFAULT 0"),
+        ("total_tokens", "INTEGER NOT NULL DEFAULT 0"),
+        ("estimated_cost_usd", "REAL NOT NULL DEFAULT 0"),
+    ] {
+        add_column_if_missing(conn, "tasks", column, definition)?;
+    }
     conn.execute(
         "CREATE TABLE IF NOT EXISTS task_dependencies (
             id TEXT PRIMARY KEY,
@@ -76,6 +99,17 @@ fn create_tables(conn: &Connection) -> Result<()> {
     Ok(())
 }
 
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let existing_columns = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    if !existing_columns.iter().any(|c| c == column) {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+    }
+    Ok(())
+}
+
 // TaskNode persistence
 pub fn save_task(conn: &Connection, task_node: &TaskNode) -> Result<()> {
     let task_spec_json = serde_json::to_string(&task_node.task_spec).unwrap_or_default();
@@ -84,8 +118,8 @@ pub fn save_task(conn: &Connection, task_node: &TaskNode) -> Result<()> {
     let retry_policy_json = serde_json::to_string(&task_node.retry_policy).unwrap_or_default();
 
     conn.execute(
-        "INSERT OR REPLACE INTO tasks (id, name, description, task_spec, status, agent_role_type, mcp_id, inputs, outputs, retry_count, retry_policy, priority, estimated_duration_ms, actual_duration_ms, created_at, updated_at, assigned_agent_id, error_message, sprint_id)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)",
+        "INSERT OR REPLACE INTO tasks (id, name, description, task_spec, status, agent_role_type, mcp_id, inputs, outputs, retry_count, retry_policy, priority, estimated_duration_ms, actual_duration_ms, created_at, updated_at, assigned_agent_id, error_message, sprint_id, llm_calls, prompt_tokens, completion_tokens, total_tokens, estimated_cost_usd)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24)",
         params![
             task_node.id.to_string(),
             task_node.name,
@@ -105,14 +139,19 @@ pub fn save_task(conn: &Connection, task_node: &TaskNode) -> Result<()> {
             task_node.updated_at.to_rfc3339(),
             task_node.assigned_agent_id,
             task_node.error_message,
-            task_node.sprint_id.map(|id| id.to_string())
+            task_node.sprint_id.map(|id| id.to_string()),
+            task_node.usage.llm_calls,
+            task_node.usage.prompt_tokens as i64,
+            task_node.usage.completion_tokens as i64,
+            task_node.usage.total_tokens as i64,
+            task_node.usage.estimated_cost_usd
         ],
     )?;
     Ok(())
 }
 
 pub fn load_task(conn: &Connection, task_id: Uuid) -> Result<Option<TaskNode>> {
-    let mut stmt = conn.prepare("SELECT id, name, description, task_spec, status, agent_role_type, mcp_id, inputs, outputs, retry_count, retry_policy, priority, estimated_duration_ms, actual_duration_ms, created_at, updated_at, assigned_agent_id, error_message, sprint_id FROM tasks WHERE id = ?1")?;
+    let mut stmt = conn.prepare("SELECT id, name, description, task_spec, status, agent_role_type, mcp_id, inputs, outputs, retry_count, retry_policy, priority, estimated_duration_ms, actual_duration_ms, created_at, updated_at, assigned_agent_id, error_message, sprint_id, llm_calls, prompt_tokens, completion_tokens, total_tokens, estimated_cost_usd FROM tasks WHERE id = ?1")?;
     let mut rows = stmt.query(params![task_id.to_string()])?;
 
     if let Some(row) = rows.next()? {
@@ -135,6 +174,13 @@ pub fn load_task(conn: &Connection, task_id: Uuid) -> Result<Option<TaskNode>> {
         let assigned_agent_id: Option<String> = row.get(16)?;
         let error_message: Option<String> = row.get(17)?;
         let sprint_id_str: Option<String> = row.get(18)?;
+        let usage = TaskUsage {
+            llm_calls: row.get(19)?,
+            prompt_tokens: row.get::<_, i64>(20)? as u64,
+            completion_tokens: row.get::<_, i64>(21)? as u64,
+            total_tokens: row.get::<_, i64>(22)? as u64,
+            estimated_cost_usd: row.get(23)?,
+        };
 
         let task_spec: TaskSpecification = serde_json::from_str(&task_spec_json).map_err(|e| rusqlite::Error::InvalidParameterName(format!("Failed to deserialize task_spec: {}", e)))?;
         let status: TaskStatus = status_str.parse().map_err(|e| rusqlite::Error::InvalidParameterName(format!("Failed to parse TaskStatus: {}", e)))?;
@@ -167,6 +213,7 @@ pub fn load_task(conn: &Connection, task_id: Uuid) -> Result<Option<TaskNode>> {
             assigned_agent_id,
             error_message,
             sprint_id,
+            usage,
         }))
     } else {
         Ok(None)
@@ -174,7 +221,7 @@ pub fn load_task(conn: &Connection, task_id: Uuid) -> Result<Option<TaskNode>> {
 }
 
 pub fn load_all_tasks(conn: &Connection) -> Result<Vec<crate::common_types::task_graph_defs::TaskNode>> {
-    let mut stmt = conn.prepare("SELECT id, name, description, task_spec, status, agent_role_type, mcp_id, inputs, outputs, retry_count, retry_policy, priority, estimated_duration_ms, actual_duration_ms, created_at, updated_at, assigned_agent_id, error_message, sprint_id FROM tasks").map_err(anyhow::Error::from)?;
+    let mut stmt = conn.prepare("SELECT id, name, description, task_spec, status, agent_role_type, mcp_id, inputs, outputs, retry_count, retry_policy, priority, estimated_duration_ms, actual_duration_ms, created_at, updated_at, assigned_agent_id, error_message, sprint_id, llm_calls, prompt_tokens, completion_tokens, total_tokens, estimated_cost_usd FROM tasks").map_err(anyhow::Error::from)?;
     let task_nodes = stmt.query_map([], |row: &rusqlite::Row| -> rusqlite::Result<crate::common_types::task_graph_defs::TaskNode> {
         let id_str: String = row.get(0)?;
         let name: String = row.get(1)?;
@@ -195,6 +242,13 @@ pub fn load_all_tasks(conn: &Connection) -> Result<Vec<crate::common_types::task
         let assigned_agent_id: Option<String> = row.get(16)?;
         let error_message: Option<String> = row.get(17)?;
         let sprint_id_str: Option<String> = row.get(18)?;
+        let usage = TaskUsage {
+            llm_calls: row.get(19)?,
+            prompt_tokens: row.get::<_, i64>(20)? as u64,
+            completion_tokens: row.get::<_, i64>(21)? as u64,
+            total_tokens: row.get::<_, i64>(22)? as u64,
+            estimated_cost_usd: row.get(23)?,
+        };
 
         let task_spec: TaskSpecification = serde_json::from_str(task_spec_json.as_str()).map_err(|e| rusqlite::Error::InvalidParameterName(format!("Failed to deserialize task_spec: {}", e)))?;
         let status: TaskStatus = status_str.parse().map_err(|e| rusqlite::Error::InvalidParameterName(format!("Failed to parse TaskStatus: {}", e)))?;
@@ -234,6 +288,7 @@ pub fn load_all_tasks(conn: &Connection) -> Result<Vec<crate::common_types::task
             assigned_agent_id,
             error_message,
             sprint_id,
+            usage,
         })
     })?
     .collect::<rusqlite::Result<Vec<crate::common_types::task_graph_defs::TaskNode>>>()
@@ -385,4 +440,58 @@ pub fn load_all_sprints(conn: &Connection) -> Result<Vec<crate::common_types::sp
     .map_err(anyhow::Error::from)?;
 
     Ok(sprints)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_columns_are_added_to_older_databases() {
+        let conn = Connection::open_in_memory().unwrap();
+        // The tasks table as created before usage accounting
+        conn.execute(
+            "CREATE TABLE tasks (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                description TEXT NOT NULL,
+                task_spec TEXT NOT NULL,
+                status TEXT NOT NULL,
+                agent_role_type TEXT,
+                mcp_id TEXT,
+                inputs TEXT NOT NULL,
+                outputs TEXT NOT NULL,
+                retry_count INTEGER NOT NULL,
+                retry_policy TEXT,
+                priority INTEGER NOT NULL,
+                estimated_duration_ms INTEGER,
+                actual_duration_ms INTEGER,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                assigned_agent_id TEXT,
+                error_message TEXT,
+                sprint_id TEXT
+            )",
+            [],
+        ).unwrap();
+        let task_id = Uuid::new_v4();
+        let now = Utc::now().to_rfc3339();
+        conn.execute(
+            "INSERT INTO tasks (id, name, description, task_spec, status, inputs, outputs, retry_count, priority, created_at, updated_at)
+                VALUES (?1, 'Old task', 'Saved before usage accounting', ?2, ?3, '[]', '[]', 0, 0, ?4, ?4)",
+            params![task_id.to_string(), serde_json::to_string(&TaskSpecification::default()).unwrap(), TaskStatus::Completed.to_string(), now],
+        ).unwrap();
+
+        // Running the migration twice leaves the table as it is
+        create_tables(&conn).unwrap();
+        create_tables(&conn).unwrap();
+
+        let mut task = load_task(&conn, task_id).unwrap().unwrap();
+        assert_eq!(task.name, "Old task");
+        assert_eq!(task.usage, TaskUsage::default());
+
+        task.usage = TaskUsage { llm_calls: 2, prompt_tokens: 150, completion_tokens: 30, total_tokens: 180, estimated_cost_usd: 0.25 };
+        save_task(&conn, &task).unwrap();
+        assert_eq!(load_task(&conn, task_id).unwrap().unwrap().usage, task.usage);
+        assert_eq!(load_all_tasks(&conn).unwrap(), vec![task]);
+    }
+}
@@ -6,6 +6,7 @@ use crate::core_orchestrator::CoreOrchestrator;
 use crate::common_types::{AgentConfig, AgentRole, TaskSpecification};
 use crate::common_types::generate_id;
 use anyhow::anyhow; // Import anyhow for error handling
+use crate::external_api_client::credential_store::CredentialSecret;
 
 // Placeholder for Tauri commands
 #[tauri::command]
@@ -44,6 +45,7 @@ pub async fn execute_agent_task(
                 role: agent_role.clone(),
                 llm_model: Some(llm_model.clone()),
                 llm_provider_name: Some("OpenRouter".to_string()),
+                llm_parameters: None,
                 specialized_config: None,
             }
         ];
@@ -98,4 +100,136 @@ pub async fn execute_agent_task(
     };
     log::debug!("DEBUG: [tauri_bindings::execute_agent_task] - Releasing orchestrator lock and returning result.");
     result
-}
\ No newline at end of file
+}
+
+/// Models offered by `provider` (OpenRouter when omitted), for the model selector of the frontend.
+#[tauri::command]
+pub async fn list_llm_models(
+    state: State<'_, crate::AppState>,
+    provider: Option<String>,
+) -> Result<Vec<crate::external_api_client::LLMModelInfo>, String> {
+    let provider_name = provider.unwrap_or_else(|| "OpenRouter".to_string());
+    log::debug!("DEBUG: [tauri_bindings::list_llm_models] - Listing models of provider '{}'.", provider_name);
+    state.external_api_client.list_models(&provider_name).await.map_err(|e| {
+        log::error!("ERROR: [tauri_bindings::list_llm_models] - Failed to list models of '{}': {}", provider_name, e);
+        e.to_string()
+    })
+}
+
+/// Registered LLM providers and whether each has credentials.
+#[tauri::command]
+pub async fn list_llm_providers(
+    state: State<'_, crate::AppState>,
+) -> Result<Vec<crate::external_api_client::ProviderStatus>, String> {
+    Ok(state.external_api_client.provider_statuses())
+}
+
+/// Sets the API key of `provider` after checking it against the provider with a cheap call, and saves it in the credential store.
+#[tauri::command]
+pub async fn set_provider_api_key(
+    state: State<'_, crate::AppState>,
+    provider: String,
+    api_key: String,
+) -> Result<(), String> {
+    log::debug!("DEBUG: [tauri_bindings::set_provider_api_key] - Setting API key of provider '{}'.", provider);
+    state.external_api_client.set_provider_api_key(&provider, api_key).await.map_err(|e| {
+        log::error!("ERROR: [tauri_bindings::set_provider_api_key] - Failed to set API key of '{}': {}", provider, e);
+        e.to_string()
+    })
+}
+
+/// Whether the credential store exists and is unlocked, so the frontend knows to ask for its passphrase.
+#[tauri::command]
+pub async fn credential_store_status(
+    state: State<'_, crate::AppState>,
+) -> Result<crate::external_api_client::CredentialStoreStatus, String> {
+    Ok(state.external_api_client.credential_store_status())
+}
+
+/// Unlocks the credential store with a passphrase or a key file and loads the stored API keys.
+/// The store is only created, protected by that secret, when `create` confirms it. Returns the providers that got a key.
+#[tauri::command]
+pub async fn unlock_credential_store(
+    state: State<'_, crate::AppState>,
+    passphrase: Option<String>,
+    key_file: Option<String>,
+    create: Option<bool>,
+) -> Result<Vec<String>, String> {
+    let secret = match (passphrase, key_file) {
+        (_, Some(key_file)) => CredentialSecret::KeyFile(key_file.into()),
+        (Some(passphrase), None) => CredentialSecret::Passphrase(passphrase),
+        (None, None) => return Err("A passphrase or a key file is required".to_string()),
+    };
+    // Argon2 takes a noticeable time, so it must not run on an async worker
+    let external_api_client = Arc::clone(&state.external_api_client);
+    let create = create.unwrap_or(false);
+    tokio::task::spawn_blocking(move || external_api_client.unlock_credentials(&secret, create))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| {
+            log::error!("ERROR: [tauri_bindings::unlock_credential_store] - Failed to unlock the credential store: {}", e);
+            e.to_string()
+        })
+}
+
+/// Replaces the API key of a provider that already has one, after checking the new key.
+#[tauri::command]
+pub async fn rotate_provider_api_key(
+    state: State<'_, crate::AppState>,
+    provider: String,
+    api_key: String,
+) -> Result<(), String> {
+    log::debug!("DEBUG: [tauri_bindings::rotate_provider_api_key] - Rotating API key of provider '{}'.", provider);
+    state.external_api_client.rotate_provider_api_key(&provider, api_key).await.map_err(|e| {
+        log::error!("ERROR: [tauri_bindings::rotate_provider_api_key] - Failed to rotate API key of '{}': {}", provider, e);
+        e.to_string()
+    })
+}
+
+/// Checks the API key `provider` currently uses against the provider.
+#[tauri::command]
+pub async fn test_provider_api_key(
+    state: State<'_, crate::AppState>,
+    provider: String,
+) -> Result<(), String> {
+    state.external_api_client.test_provider_api_key(&provider).await.map_err(|e| e.to_string())
+}
+
+/// Removes the API key of `provider` from the credential store; the provider is then not configured.
+#[tauri::command]
+pub async fn delete_provider_api_key(
+    state: State<'_, crate::AppState>,
+    provider: String,
+) -> Result<(), String> {
+    log::debug!("DEBUG: [tauri_bindings::delete_provider_api_key] - Deleting API key of provider '{}'.", provider);
+    state.external_api_client.delete_provider_api_key(&provider).map_err(|e| {
+        log::error!("ERROR: [tauri_bindings::delete_provider_api_key] - Failed to delete API key of '{}': {}", provider, e);
+        e.to_string()
+    })
+}
+
+/// Cancels a running task: its in-flight LLM call is aborted and the task fails as cancelled.
+#[tauri::command]
+pub async fn cancel_task(
+    state: State<'_, crate::AppState>,
+    task_id: String,
+) -> Result<(), String> {
+    log::info!("DEBUG: [tauri_bindings::cancel_task] - Cancelling task '{}'.", task_id);
+    if !state.task_cancellation.cancel_task(&task_id) {
+        return Err(format!("Task {} is not running", task_id));
+    }
+    Ok(())
+}
+
+/// Cancels every running task of a graph and keeps its remaining tasks from being scheduled.
+#[tauri::command]
+pub async fn cancel_graph(
+    state: State<'_, crate::AppState>,
+    graph_id: String,
+) -> Result<(), String> {
+    log::info!("DEBUG: [tauri_bindings::cancel_graph] - Cancelling task graph '{}'.", graph_id);
+    if !state.task_cancellation.cancel_graph(&graph_id) {
+        return Err(format!("Task graph {} is not running", graph_id));
+    }
+    Ok(())
+}